@@ -0,0 +1,325 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use component::Transform;
+use entity::{EntityAllocator, EntityId};
+use event::{push_event, Event};
+use nalgebra_glm::Vec3;
+use network_utils::NetworkId;
+use serde::{Deserialize, Serialize};
+
+const ARCHETYPES_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../res/archetypes.ron");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ComponentKind {
+    Camera,
+    Hierarchy,
+    Network,
+    Physics,
+    StaticMesh,
+}
+
+/// A component of an archetype along with its initial data
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ComponentDesc {
+    Camera,
+    /// Lets the entity be attached to others, and others to it
+    Hierarchy,
+    Network,
+    Physics,
+    StaticMesh {
+        mesh: String,
+    },
+}
+
+impl ComponentDesc {
+    pub fn kind(&self) -> ComponentKind {
+        match self {
+            ComponentDesc::Camera => ComponentKind::Camera,
+            ComponentDesc::Hierarchy => ComponentKind::Hierarchy,
+            ComponentDesc::Network => ComponentKind::Network,
+            ComponentDesc::Physics => ComponentKind::Physics,
+            ComponentDesc::StaticMesh { .. } => ComponentKind::StaticMesh,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct ArchetypeDesc {
+    pub components: Vec<ComponentDesc>,
+}
+
+impl ArchetypeDesc {
+    pub fn has_component(&self, kind: ComponentKind) -> bool {
+        self.components
+            .iter()
+            .any(|component| component.kind() == kind)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Archetypes {
+    archetypes: HashMap<String, ArchetypeDesc>,
+}
+
+#[derive(Debug)]
+pub enum ArchetypesError {
+    Io {
+        path: PathBuf,
+        err: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    DuplicateComponent {
+        path: PathBuf,
+        archetype: String,
+        kind: ComponentKind,
+    },
+}
+
+impl fmt::Display for ArchetypesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchetypesError::Io { path, err } => write!(f, "{}: {}", path.display(), err),
+            ArchetypesError::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            ArchetypesError::DuplicateComponent {
+                path,
+                archetype,
+                kind,
+            } => write!(
+                f,
+                "{}: archetype '{}' has more than one {:?} component",
+                path.display(),
+                archetype,
+                kind
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArchetypesError {}
+
+impl Archetypes {
+    /// Loads `res/archetypes.ron`
+    pub fn load() -> Result<Self, ArchetypesError> {
+        let path = Path::new(ARCHETYPES_PATH);
+        let source = fs::read_to_string(path).map_err(|err| ArchetypesError::Io {
+            path: path.to_path_buf(),
+            err,
+        })?;
+
+        Self::parse(&source, path)
+    }
+
+    /// `path` is only used for error reporting
+    pub fn parse(source: &str, path: &Path) -> Result<Self, ArchetypesError> {
+        let archetypes: Archetypes =
+            ron::from_str(source).map_err(|err| ArchetypesError::Parse {
+                path: path.to_path_buf(),
+                line: err.position.line,
+                column: err.position.col,
+                message: err.code.to_string(),
+            })?;
+
+        for (name, archetype) in &archetypes.archetypes {
+            let mut kinds = HashSet::new();
+            for component in &archetype.components {
+                if !kinds.insert(component.kind()) {
+                    return Err(ArchetypesError::DuplicateComponent {
+                        path: path.to_path_buf(),
+                        archetype: name.clone(),
+                        kind: component.kind(),
+                    });
+                }
+            }
+        }
+
+        Ok(archetypes)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ArchetypeDesc> {
+        self.archetypes.get(name)
+    }
+}
+
+/// An archetype name plus per-instance data that overrides or complements its initial data
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpawnParams {
+    pub archetype: String,
+    pub transform: Transform,
+    pub velocity: Vec3,
+    pub network_id: Option<NetworkId>,
+    /// Replaces the mesh of the archetype's static mesh component
+    pub mesh: Option<String>,
+}
+
+/// Pushed by the registry for every entity it spawns or destroys, so that journals capture
+/// entity lifetimes
+#[derive(Serialize, Deserialize)]
+pub enum EntityLifecycle {
+    Spawned(SpawnParams),
+    Destroyed,
+}
+
+impl Event for EntityLifecycle {}
+
+#[derive(Debug)]
+pub enum SpawnError {
+    UnknownArchetype(String),
+    MissingNetworkId(String),
+    DuplicateNetworkId(NetworkId),
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpawnError::UnknownArchetype(name) => write!(f, "unknown archetype '{}'", name),
+            SpawnError::MissingNetworkId(name) => {
+                write!(f, "archetype '{}' is networked but has no network_id", name)
+            }
+            SpawnError::DuplicateNetworkId(network_id) => {
+                write!(f, "network_id {} is already used", network_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpawnError {}
+
+/// A spawned entity. Remembers which components were built so that destroying it through the
+/// registry tears down exactly those components.
+pub struct Entity {
+    entity_id: EntityId,
+    archetype: String,
+    mesh: Option<String>,
+    network_id: Option<NetworkId>,
+    components: Vec<ComponentKind>,
+}
+
+impl Entity {
+    pub fn entity_id(&self) -> EntityId {
+        self.entity_id
+    }
+
+    pub fn archetype(&self) -> &str {
+        &self.archetype
+    }
+
+    /// Mesh override the entity was spawned with
+    pub fn mesh(&self) -> Option<&str> {
+        self.mesh.as_deref()
+    }
+
+    pub fn network_id(&self) -> Option<NetworkId> {
+        self.network_id
+    }
+}
+
+struct ComponentBuilder<S> {
+    create: fn(&mut S, EntityId, &ComponentDesc, &SpawnParams),
+    destroy: fn(&mut S, EntityId),
+}
+
+/// Archetype definitions paired with one side's knowledge of how to build each component kind
+/// out of its systems `S`. Component kinds without a registered builder are skipped, e.g. the
+/// server has no graphics components.
+pub struct ArchetypeRegistry<S> {
+    archetypes: Archetypes,
+    builders: HashMap<ComponentKind, ComponentBuilder<S>>,
+}
+
+impl<S> ArchetypeRegistry<S> {
+    pub fn new(archetypes: Archetypes) -> Self {
+        Self {
+            archetypes,
+            builders: HashMap::new(),
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        kind: ComponentKind,
+        create: fn(&mut S, EntityId, &ComponentDesc, &SpawnParams),
+        destroy: fn(&mut S, EntityId),
+    ) {
+        let previous = self
+            .builders
+            .insert(kind, ComponentBuilder { create, destroy });
+        debug_assert!(previous.is_none(), "{:?} registered twice", kind);
+    }
+
+    pub fn archetypes(&self) -> &Archetypes {
+        &self.archetypes
+    }
+
+    pub fn spawn(
+        &self,
+        params: &SpawnParams,
+        systems: &mut S,
+        entity_allocator: &mut EntityAllocator,
+    ) -> Result<Entity, SpawnError> {
+        let archetype = self
+            .archetypes
+            .get(&params.archetype)
+            .ok_or_else(|| SpawnError::UnknownArchetype(params.archetype.clone()))?;
+
+        if archetype.has_component(ComponentKind::Network) && params.network_id.is_none() {
+            return Err(SpawnError::MissingNetworkId(params.archetype.clone()));
+        }
+
+        let entity_id = entity_allocator.allocate();
+        let mut components = Vec::with_capacity(archetype.components.len());
+
+        for component in &archetype.components {
+            let builder = match self.builders.get(&component.kind()) {
+                Some(builder) => builder,
+                None => continue,
+            };
+
+            match (component, &params.mesh) {
+                (ComponentDesc::StaticMesh { .. }, Some(mesh)) => {
+                    let component = ComponentDesc::StaticMesh { mesh: mesh.clone() };
+                    (builder.create)(systems, entity_id, &component, params);
+                }
+                _ => (builder.create)(systems, entity_id, component, params),
+            }
+
+            components.push(component.kind());
+        }
+
+        push_event(entity_id, EntityLifecycle::Spawned(params.clone()));
+
+        Ok(Entity {
+            entity_id,
+            archetype: params.archetype.clone(),
+            mesh: params.mesh.clone(),
+            network_id: params.network_id,
+            components,
+        })
+    }
+
+    pub fn destroy(&self, entity: Entity, systems: &mut S, entity_allocator: &mut EntityAllocator) {
+        for kind in entity.components.iter().rev() {
+            (self.builders[kind].destroy)(systems, entity.entity_id);
+        }
+
+        entity_allocator.free(entity.entity_id);
+
+        push_event(entity.entity_id, EntityLifecycle::Destroyed);
+    }
+}
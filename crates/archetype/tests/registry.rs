@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use archetype::{
+    ArchetypeRegistry, Archetypes, ComponentDesc, ComponentKind, EntityLifecycle, SpawnError,
+    SpawnParams,
+};
+use component::Transform;
+use entity::{EntityAllocator, EntityId};
+use event::EventManager;
+use nalgebra_glm::Vec3;
+
+const ARCHETYPES: &str = r#"{
+    "camera": [camera, hierarchy],
+    "player": [network, physics, static_mesh(mesh: "suzanne"), hierarchy],
+}"#;
+
+/// Records every component built and torn down
+#[derive(Default)]
+struct Systems {
+    log: Vec<(&'static str, EntityId, String)>,
+}
+
+fn registry() -> ArchetypeRegistry<Systems> {
+    let archetypes = Archetypes::parse(ARCHETYPES, Path::new("archetypes.ron")).unwrap();
+    let mut registry = ArchetypeRegistry::<Systems>::new(archetypes);
+
+    // no builders for network and hierarchy, like a side without those systems
+    registry.register(
+        ComponentKind::Camera,
+        |systems, entity_id, _, _| systems.log.push(("create", entity_id, "camera".into())),
+        |systems, entity_id| systems.log.push(("destroy", entity_id, "camera".into())),
+    );
+    registry.register(
+        ComponentKind::Physics,
+        |systems, entity_id, _, _| systems.log.push(("create", entity_id, "physics".into())),
+        |systems, entity_id| systems.log.push(("destroy", entity_id, "physics".into())),
+    );
+    registry.register(
+        ComponentKind::StaticMesh,
+        |systems, entity_id, component, _| {
+            if let ComponentDesc::StaticMesh { mesh } = component {
+                systems.log.push(("create", entity_id, mesh.clone()));
+            }
+        },
+        |systems, entity_id| systems.log.push(("destroy", entity_id, "mesh".into())),
+    );
+
+    registry
+}
+
+fn params(archetype: &str, network_id: Option<u16>, mesh: Option<&str>) -> SpawnParams {
+    SpawnParams {
+        archetype: archetype.to_string(),
+        transform: Transform::default(),
+        velocity: Vec3::zeros(),
+        network_id,
+        mesh: mesh.map(str::to_string),
+    }
+}
+
+fn event_manager() -> EventManager {
+    let event_manager = EventManager::new();
+    event_manager.bus().register_current_thread();
+    event_manager
+}
+
+#[test]
+fn destroys_exactly_the_components_it_built() {
+    let mut event_manager = event_manager();
+    let registry = registry();
+    let mut systems = Systems::default();
+    let mut allocator = EntityAllocator::new();
+
+    let player = registry
+        .spawn(
+            &params("player", Some(7), None),
+            &mut systems,
+            &mut allocator,
+        )
+        .unwrap();
+    let camera = registry
+        .spawn(&params("camera", None, None), &mut systems, &mut allocator)
+        .unwrap();
+    let (player_id, camera_id) = (player.entity_id(), camera.entity_id());
+    assert_eq!(player.network_id(), Some(7));
+
+    assert_eq!(
+        systems.log,
+        vec![
+            ("create", player_id, "physics".to_string()),
+            ("create", player_id, "suzanne".to_string()),
+            ("create", camera_id, "camera".to_string()),
+        ]
+    );
+    systems.log.clear();
+
+    registry.destroy(player, &mut systems, &mut allocator);
+    assert_eq!(
+        systems.log,
+        vec![
+            ("destroy", player_id, "mesh".to_string()),
+            ("destroy", player_id, "physics".to_string()),
+        ]
+    );
+    assert!(!allocator.is_alive(player_id));
+    assert!(allocator.is_alive(camera_id));
+
+    registry.destroy(camera, &mut systems, &mut allocator);
+    assert_eq!(systems.log.len(), 3);
+    assert!(allocator.is_empty());
+
+    event_manager.distribute(&mut []);
+    assert_eq!(event_manager.event_count::<EntityLifecycle>(), 4);
+}
+
+#[test]
+fn overrides_the_archetype_mesh() {
+    let _event_manager = event_manager();
+    let registry = registry();
+    let mut systems = Systems::default();
+    let mut allocator = EntityAllocator::new();
+
+    let player = registry
+        .spawn(
+            &params("player", Some(1), Some("cube")),
+            &mut systems,
+            &mut allocator,
+        )
+        .unwrap();
+
+    assert_eq!(player.mesh(), Some("cube"));
+    assert!(systems
+        .log
+        .contains(&("create", player.entity_id(), "cube".to_string())));
+}
+
+#[test]
+fn builds_nothing_when_spawning_fails() {
+    let _event_manager = event_manager();
+    let registry = registry();
+    let mut systems = Systems::default();
+    let mut allocator = EntityAllocator::new();
+
+    match registry.spawn(&params("monster", None, None), &mut systems, &mut allocator) {
+        Err(SpawnError::UnknownArchetype(name)) => assert_eq!(name, "monster"),
+        _ => panic!("spawned an unknown archetype"),
+    }
+
+    match registry.spawn(&params("player", None, None), &mut systems, &mut allocator) {
+        Err(SpawnError::MissingNetworkId(name)) => assert_eq!(name, "player"),
+        _ => panic!("spawned a networked archetype without a network ID"),
+    }
+
+    assert!(systems.log.is_empty());
+    assert!(allocator.is_empty());
+}
@@ -1,4 +1,5 @@
-use component::Component;
+use component::InputAcceleration;
+use entity::EntityId;
 use event::push_event;
 use nalgebra_glm::Vec2;
 use winit::event::{ElementState, Event as InputEvent, ScanCode, WindowEvent};
@@ -38,7 +39,7 @@ impl System {
     }
 
     pub async fn flush_input(&self) {
-        push_event(0, Component::InputAcceleration(self.input_acceleration));
+        push_event(EntityId::NONE, InputAcceleration(self.input_acceleration));
     }
 
     fn handle_keypress(&mut self, scancode: ScanCode, state: ElementState) {
@@ -1,10 +1,17 @@
-use std::num::Wrapping;
+use std::{
+    num::Wrapping,
+    path::{Path, PathBuf},
+};
 
-use ::entity::EntityId;
-use component::Component;
-use event::{EventListener, EventManager};
+use archetype::{ArchetypeRegistry, Archetypes, ComponentDesc, ComponentKind, Entity, SpawnParams};
+use component::{SimulationStep, Transform};
+use entity::{EntityAllocator, EntityId};
+use event::{push_event, EventListener, EventManager, JournalError, JournalWriter};
 use gfx::Graphics;
-use system::{Timestamp, TIMESTEP, TIMESTEP_F32};
+use level::Level;
+use network_utils::NetworkId;
+use sim_network_client::Replication;
+use system::{ScheduledSystem, Scheduler, Stage, Tick, Timestamp, TIMESTEP, TIMESTEP_F32};
 use task::{run_parallel, Executor};
 use winit::{
     event::{Event, WindowEvent},
@@ -12,45 +19,105 @@ use winit::{
     window::Window,
 };
 
-use crate::entity::Entity;
-
-mod entity;
 mod input;
 
+/// Frames captured by profile()
+const PROFILE_FRAMES: u32 = 300;
+
 pub struct Client {
     event_manager: EventManager,
     task_executor: Executor,
+    /// Simulation and graphics systems are scheduled separately, so that rendering the frame
+    /// can overlap drawing the previous one. They share no resources.
+    simulation_scheduler: Scheduler,
+    graphics_scheduler: Scheduler,
     last_sim_instant: std::time::Instant,
     last_frame_instant: std::time::Instant,
     timestamp: Timestamp,
+    entity_allocator: EntityAllocator,
     entities: Vec<Entity>,
-    graphics: Graphics,
+    archetypes: ArchetypeRegistry<Systems>,
+    /// Links from level entities to networked entities which the server hasn't replicated yet
+    pending_links: Vec<(NetworkId, PendingLink)>,
+    /// Where to save the profile being captured, and the frames left to capture
+    profile: Option<(PathBuf, u32)>,
     systems: Systems,
 }
 
+enum PendingLink {
+    CameraTarget,
+    Child {
+        entity_id: EntityId,
+        local: Transform,
+    },
+}
+
 impl Client {
-    pub fn new(event_loop: &EventLoop<()>) -> Self {
+    pub fn new(event_loop: &EventLoop<()>, archetypes: Archetypes) -> Self {
         let window = Window::new(event_loop).unwrap();
 
+        let mut archetypes = ArchetypeRegistry::new(archetypes);
+        Systems::register_components(&mut archetypes);
+
         let event_manager = EventManager::new();
-        let (task_executor, thread_ids) = Executor::new(|| unsafe {
-            event::add_event_sender();
-        });
+        // spawns and simulation steps are pushed from this thread, and must not go through the
+        // fallback queue, which every world in the process drains
+        event_manager.bus().register_current_thread();
+        let event_bus = event_manager.bus().clone();
+        let (task_executor, thread_ids) =
+            Executor::new(move || event_bus.register_current_thread());
+
+        let mut systems = Systems::new(Graphics::new(window, &thread_ids));
 
         Self {
             event_manager,
             task_executor,
+            simulation_scheduler: Scheduler::new(&systems.simulation.scheduled()),
+            graphics_scheduler: Scheduler::new(&systems.graphics.scheduled()),
             last_sim_instant: std::time::Instant::now(),
             last_frame_instant: std::time::Instant::now(),
             timestamp: Wrapping(0),
+            entity_allocator: EntityAllocator::new(),
             entities: Vec::new(),
-            graphics: Graphics::new(window, &thread_ids),
-            systems: Systems::new(),
+            archetypes,
+            pending_links: Vec::new(),
+            profile: None,
+            systems,
         }
     }
 
-    pub fn run(mut self, event_loop: EventLoop<()>) -> ! {
-        self.load_level();
+    /// See EventManager::set_deterministic()
+    pub fn set_deterministic_events(&mut self, deterministic: bool) {
+        self.event_manager.set_deterministic(deterministic);
+    }
+
+    /// Records every event to a journal at `path`, for replaying the session offline
+    pub fn record_journal(&mut self, path: &Path) -> Result<(), JournalError> {
+        let journal = JournalWriter::create(path)?;
+        self.event_manager.set_journal(Some(journal));
+        Ok(())
+    }
+
+    /// Profiles the executor's tasks over the next PROFILE_FRAMES frames, then saves a Chrome
+    /// trace to `path`
+    pub fn profile(&mut self, path: PathBuf) {
+        self.task_executor.start_profiling();
+        self.profile = Some((path, PROFILE_FRAMES));
+    }
+
+    /// Describes which systems run concurrently in each stage, and why the others wait
+    pub fn dump_schedule(&mut self) -> String {
+        let simulation = self
+            .simulation_scheduler
+            .dump(&self.systems.simulation.scheduled());
+        let graphics = self
+            .graphics_scheduler
+            .dump(&self.systems.graphics.scheduled());
+        format!("simulation\n{}graphics\n{}", simulation, graphics)
+    }
+
+    pub fn run(mut self, event_loop: EventLoop<()>, level: &Level) -> ! {
+        self.load_level(level);
 
         self.last_sim_instant = std::time::Instant::now();
         self.last_frame_instant = std::time::Instant::now();
@@ -82,9 +149,8 @@ impl Client {
     }
 
     fn distribute_events(&mut self) {
-        self.event_manager.distribute(|entity_id, component| {
-            self.systems.receive_event(entity_id, component);
-        });
+        self.event_manager.set_timestamp(self.timestamp);
+        self.event_manager.distribute(&mut self.systems.listeners());
     }
 
     fn frame(&mut self) {
@@ -100,57 +166,219 @@ impl Client {
         while now.duration_since(self.last_sim_instant) > TIMESTEP {
             self.last_sim_instant += TIMESTEP;
 
-            {
-                let mut simulate = self.systems.simulation.simulate(self.timestamp);
-                self.task_executor.execute_blocking(&mut simulate);
-            }
+            push_event(EntityId::NONE, SimulationStep(self.timestamp));
+
+            self.run_stage(Stage::Simulate, 0.0, 0.0);
 
             self.distribute_events();
+            self.replicate();
 
             self.timestamp += Wrapping(1);
         }
 
         // todo: we cannot parallelize simulation and rendering because of event distribution
 
-        // render
+        // render, while drawing the render transforms distributed last frame
 
-        let mut render = async {
-            let rem = TIMESTEP - now.duration_since(self.last_sim_instant);
-            let frame_interp = 1.0 - rem.as_secs_f32() / TIMESTEP_F32;
+        let rem = TIMESTEP - now.duration_since(self.last_sim_instant);
+        let frame_interp = 1.0 - rem.as_secs_f32() / TIMESTEP_F32;
 
-            let mut simulation = self.systems.simulation.render(delta_time, frame_interp);
+        {
+            let tick = self.tick(delta_time, frame_interp);
+            let simulation_scheduler = &self.simulation_scheduler;
+            let graphics_scheduler = &self.graphics_scheduler;
+            let simulation = &mut self.systems.simulation;
+            let graphics = &mut self.systems.graphics;
 
-            let mut graphics = async {
-                self.graphics.frame_begin().await;
-                self.systems.graphics.render().await;
-                self.graphics.frame_end().await;
+            let mut render = async {
+                simulation_scheduler
+                    .run(Stage::Render, &tick, &mut simulation.scheduled())
+                    .await;
             };
 
-            run_parallel([&mut simulation, &mut graphics]).await;
+            let mut draw = async {
+                graphics.gfx.frame_begin().await;
+
+                graphics_scheduler
+                    .run(Stage::Draw, &tick, &mut graphics.scheduled())
+                    .await;
+
+                graphics.gfx.frame_end().await;
+            };
+
+            let mut frame = async {
+                run_parallel([&mut render, &mut draw]).await;
+            };
+            self.task_executor.execute_blocking(&mut frame);
+        }
+
+        // children are placed relative to this frame's parent render transforms, and drawn along
+        // with them next frame
+        self.distribute_events();
+
+        self.run_stage(Stage::Propagate, delta_time, frame_interp);
+        self.distribute_events();
+
+        self.profile_frame();
+    }
+
+    fn profile_frame(&mut self) {
+        let (path, frames) = match &mut self.profile {
+            Some(profile) => profile,
+            None => return,
         };
 
-        self.task_executor.execute_blocking(&mut render);
+        *frames -= 1;
+        if *frames > 0 {
+            return;
+        }
+
+        let profile = self.task_executor.stop_profiling();
+        match profile.save(path) {
+            Ok(()) => println!("saved {} task spans to {}", profile.len(), path.display()),
+            Err(err) => println!("failed to save profile to {}: {}", path.display(), err),
+        }
+        self.profile = None;
     }
 
-    fn load_level(&mut self) {
-        let static_mesh = self.graphics.create_static_mesh("suzanne");
-        self.entities
-            .push(entity::static_mesh(10, &mut self.systems, static_mesh));
-        self.entities.push(entity::camera(20, &mut self.systems));
+    fn tick(&self, delta_time: f32, frame_interp: f32) -> Tick {
+        Tick {
+            timestamp: self.timestamp,
+            delta_time,
+            frame_interp,
+        }
+    }
 
-        self.systems.simulation.camera.set_target(10);
+    fn run_stage(&mut self, stage: Stage, delta_time: f32, frame_interp: f32) {
+        let tick = self.tick(delta_time, frame_interp);
+        let mut systems = self.systems.simulation.scheduled();
+        let mut run = self.simulation_scheduler.run(stage, &tick, &mut systems);
+        self.task_executor.execute_blocking(&mut run);
     }
 
-    fn shutdown(&mut self) {
-        for entity in self.entities.drain(..) {
-            entity.destroy(&mut self.systems);
+    /// Spawns local entities. Networked entities are skipped since the server replicates them.
+    fn load_level(&mut self, level: &Level) {
+        for desc in &level.entities {
+            let networked = self
+                .archetypes
+                .archetypes()
+                .get(&desc.archetype)
+                .is_some_and(|archetype| archetype.has_component(ComponentKind::Network));
+
+            if networked {
+                continue;
+            }
+
+            let entity = self
+                .archetypes
+                .spawn(
+                    &desc.spawn_params(),
+                    &mut self.systems,
+                    &mut self.entity_allocator,
+                )
+                .expect("level was validated against the archetypes");
+
+            if let Some(target) = desc.target {
+                self.pending_links.push((target, PendingLink::CameraTarget));
+            }
+
+            if let Some(parent) = desc.parent {
+                let link = PendingLink::Child {
+                    entity_id: entity.entity_id(),
+                    local: desc.spawn_params().transform,
+                };
+                self.pending_links.push((parent, link));
+            }
+
+            self.entities.push(entity);
         }
     }
-}
 
-impl EventListener for Client {
-    fn receive_event(&mut self, entity_id: EntityId, component: &Component) {
-        self.systems.receive_event(entity_id, component);
+    /// Applies entity spawns and despawns received from the server
+    fn replicate(&mut self) {
+        for replication in self.systems.simulation.network_client.take_replication() {
+            match replication {
+                Replication::Spawn(spawn) => {
+                    let params = SpawnParams {
+                        archetype: spawn.archetype,
+                        transform: Transform {
+                            location: spawn.location,
+                            rotation: spawn.rotation,
+                            scale: spawn.scale,
+                        },
+                        velocity: spawn.velocity,
+                        network_id: Some(spawn.network_id),
+                        mesh: spawn.mesh,
+                    };
+
+                    let entity = match self.archetypes.spawn(
+                        &params,
+                        &mut self.systems,
+                        &mut self.entity_allocator,
+                    ) {
+                        Ok(entity) => entity,
+                        Err(err) => {
+                            println!("failed to spawn replicated entity: {}", err);
+                            continue;
+                        }
+                    };
+
+                    self.resolve_links(spawn.network_id, entity.entity_id());
+                    self.entities.push(entity);
+                }
+                Replication::Despawn(entity_id) => self.despawn(entity_id),
+            }
+        }
+    }
+
+    fn resolve_links(&mut self, network_id: NetworkId, entity_id: EntityId) {
+        let (resolved, pending) = std::mem::take(&mut self.pending_links)
+            .into_iter()
+            .partition(|(link_network_id, _)| *link_network_id == network_id);
+        self.pending_links = pending;
+
+        for (_, link) in resolved {
+            match link {
+                PendingLink::CameraTarget => self.systems.simulation.camera.set_target(entity_id),
+                PendingLink::Child {
+                    entity_id: child,
+                    local,
+                } => {
+                    if let Err(err) = self
+                        .systems
+                        .simulation
+                        .hierarchy
+                        .attach(child, entity_id, local)
+                    {
+                        println!("failed to attach level entity: {}", err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Destroys the entity along with everything attached to it
+    fn despawn(&mut self, entity_id: EntityId) {
+        let descendants = self.systems.simulation.hierarchy.descendants(entity_id);
+
+        for entity_id in descendants.into_iter().chain([entity_id]) {
+            if let Some(index) = self
+                .entities
+                .iter()
+                .position(|entity| entity.entity_id() == entity_id)
+            {
+                let entity = self.entities.swap_remove(index);
+                self.archetypes
+                    .destroy(entity, &mut self.systems, &mut self.entity_allocator);
+            }
+        }
+    }
+
+    fn shutdown(&mut self) {
+        for entity in self.entities.drain(..) {
+            self.archetypes
+                .destroy(entity, &mut self.systems, &mut self.entity_allocator);
+        }
     }
 }
 
@@ -161,83 +389,167 @@ pub struct Systems {
 }
 
 impl Systems {
-    pub fn new() -> Self {
+    pub fn new(graphics: Graphics) -> Self {
         Self {
             input: input::System::new(),
             simulation: SimulationSystems::new(),
-            graphics: GraphicsSystems::new(),
+            graphics: GraphicsSystems::new(graphics),
         }
     }
+
+    pub fn register_components(archetypes: &mut ArchetypeRegistry<Self>) {
+        archetypes.register(
+            ComponentKind::Camera,
+            |systems, entity_id, _, params| {
+                systems
+                    .simulation
+                    .camera
+                    .create_component(entity_id, params.transform.location);
+                systems.graphics.camera.create_component(entity_id);
+            },
+            |systems, entity_id| {
+                systems.simulation.camera.destroy_component(entity_id);
+                systems.graphics.camera.destroy_component(entity_id);
+            },
+        );
+
+        archetypes.register(
+            ComponentKind::Hierarchy,
+            |systems, entity_id, _, params| {
+                systems
+                    .simulation
+                    .hierarchy
+                    .create_component(entity_id, params.transform);
+            },
+            |systems, entity_id| systems.simulation.hierarchy.destroy_component(entity_id),
+        );
+
+        archetypes.register(
+            ComponentKind::Network,
+            |systems, entity_id, _, params| {
+                let network_id = params.network_id.unwrap();
+                systems
+                    .simulation
+                    .network_client
+                    .create_static_mesh_component(entity_id, network_id);
+            },
+            |systems, entity_id| {
+                systems
+                    .simulation
+                    .network_client
+                    .destroy_static_mesh_component(entity_id);
+            },
+        );
+
+        archetypes.register(
+            ComponentKind::Physics,
+            |systems, entity_id, _, params| {
+                systems.simulation.physics.create_component(
+                    entity_id,
+                    params.transform,
+                    params.velocity,
+                );
+            },
+            |systems, entity_id| systems.simulation.physics.destroy_component(entity_id),
+        );
+
+        archetypes.register(
+            ComponentKind::StaticMesh,
+            |systems, entity_id, component, _| {
+                if let ComponentDesc::StaticMesh { mesh } = component {
+                    let static_mesh = systems.graphics.gfx.create_static_mesh(mesh);
+                    systems
+                        .graphics
+                        .static_mesh
+                        .create_component(entity_id, static_mesh);
+                }
+            },
+            |systems, entity_id| systems.graphics.static_mesh.destroy_component(entity_id),
+        );
+    }
 }
 
-impl EventListener for Systems {
-    fn receive_event(&mut self, entity_id: EntityId, component: &Component) {
-        self.simulation.receive_event(entity_id, component);
-        self.graphics.receive_event(entity_id, component);
+impl Systems {
+    pub fn listeners(&mut self) -> [&mut dyn EventListener; 6] {
+        let [camera, hierarchy, network_client, physics] = self.simulation.listeners();
+        let [gfx_camera, static_mesh] = self.graphics.listeners();
+        [
+            camera,
+            hierarchy,
+            network_client,
+            physics,
+            gfx_camera,
+            static_mesh,
+        ]
     }
 }
 
 pub struct SimulationSystems {
     pub camera: sim_camera::System,
+    pub hierarchy: sim_hierarchy::System,
     pub network_client: sim_network_client::System,
     pub physics: sim_physics::System,
 }
 
+impl Default for SimulationSystems {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SimulationSystems {
     pub fn new() -> Self {
         Self {
             camera: sim_camera::System::new(),
+            hierarchy: sim_hierarchy::System::new(),
             network_client: sim_network_client::System::new(),
             physics: sim_physics::System::new(),
         }
     }
+}
 
-    pub async fn simulate(&mut self, timestamp: Timestamp) {
-        let mut network_client = self.network_client.simulate(timestamp);
-        let mut physics = self.physics.simulate(timestamp);
-
-        run_parallel([&mut network_client, &mut physics]).await;
-    }
-
-    pub async fn render(&mut self, delta_time: f32, frame_interp: f32) {
-        let mut camera = self.camera.render(delta_time);
-        let mut physics = self.physics.render(frame_interp);
-
-        run_parallel([&mut camera, &mut physics]).await;
+impl SimulationSystems {
+    pub fn listeners(&mut self) -> [&mut dyn EventListener; 4] {
+        [
+            &mut self.camera,
+            &mut self.hierarchy,
+            &mut self.network_client,
+            &mut self.physics,
+        ]
     }
-}
 
-impl EventListener for SimulationSystems {
-    fn receive_event(&mut self, entity_id: EntityId, component: &Component) {
-        self.camera.receive_event(entity_id, component);
-        self.network_client.receive_event(entity_id, component);
-        self.physics.receive_event(entity_id, component);
+    pub fn scheduled(&mut self) -> [&mut dyn ScheduledSystem; 4] {
+        [
+            &mut self.camera,
+            &mut self.hierarchy,
+            &mut self.network_client,
+            &mut self.physics,
+        ]
     }
 }
 
 pub struct GraphicsSystems {
+    pub gfx: Graphics,
     pub camera: gfx_camera::System,
     pub static_mesh: gfx_static_mesh::System,
 }
 
 impl GraphicsSystems {
-    pub fn new() -> Self {
+    pub fn new(gfx: Graphics) -> Self {
         Self {
+            gfx,
             camera: gfx_camera::System::new(),
             static_mesh: gfx_static_mesh::System::new(),
         }
     }
+}
 
-    pub async fn render(&mut self) {
-        // run sequentially until we get secondary command buffers up and running
-        self.camera.render().await;
-        self.static_mesh.render().await;
+impl GraphicsSystems {
+    pub fn listeners(&mut self) -> [&mut dyn EventListener; 2] {
+        [&mut self.camera, &mut self.static_mesh]
     }
-}
 
-impl EventListener for GraphicsSystems {
-    fn receive_event(&mut self, entity_id: EntityId, component: &Component) {
-        self.camera.receive_event(entity_id, component);
-        self.static_mesh.receive_event(entity_id, component);
+    pub fn scheduled(&mut self) -> [&mut dyn ScheduledSystem; 2] {
+        [&mut self.camera, &mut self.static_mesh]
     }
 }
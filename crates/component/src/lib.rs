@@ -1,21 +1,139 @@
-use nalgebra_glm::{Vec2, Vec3};
+use event::Event;
+use nalgebra_glm::{
+    quat_identity, quat_rotate_vec3, quat_slerp, quat_to_mat4, scale, translate, Mat4, Quat, Vec2,
+    Vec3,
+};
+use serde::{Deserialize, Serialize};
 use system::Timestamp;
 
-pub enum Component {
-    InputAcceleration(Vec2),
-    Location(Vec3),
-    NetInputAcceleration {
-        timestamp: Timestamp,
-        acceleration: Vec2,
-    },
-    NetStaticMeshLocation {
-        timestamp: Timestamp,
-        location: Vec3,
-    },
-    NetStaticMeshVelocity {
-        timestamp: Timestamp,
-        velocity: Vec3,
-    },
-    RenderLocation(Vec3),
-    Velocity(Vec3),
+/// Acceleration requested by the local player's input
+#[derive(Serialize, Deserialize)]
+pub struct InputAcceleration(pub Vec2);
+
+#[derive(Serialize, Deserialize)]
+pub struct Location(pub Vec3);
+
+/// Input acceleration received from a client, for the tick it was applied on
+#[derive(Serialize, Deserialize)]
+pub struct NetInputAcceleration {
+    pub timestamp: Timestamp,
+    pub acceleration: Vec2,
+}
+
+/// Authoritative state received from the server, for the tick it was simulated on
+#[derive(Serialize, Deserialize)]
+pub struct NetStaticMeshLocation {
+    pub timestamp: Timestamp,
+    pub location: Vec3,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NetStaticMeshRotation {
+    pub timestamp: Timestamp,
+    pub rotation: Quat,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NetStaticMeshVelocity {
+    pub timestamp: Timestamp,
+    pub velocity: Vec3,
+}
+
+/// Interpolated transform to render this frame
+#[derive(Serialize, Deserialize)]
+pub struct RenderTransform(pub Transform);
+
+#[derive(Serialize, Deserialize)]
+pub struct Rotation(pub Quat);
+
+/// Pushed just before the simulation steps, so that journals record when each step ran
+#[derive(Serialize, Deserialize)]
+pub struct SimulationStep(pub Timestamp);
+
+#[derive(Serialize, Deserialize)]
+pub struct Velocity(pub Vec3);
+
+impl Event for InputAcceleration {}
+impl Event for Location {}
+impl Event for NetInputAcceleration {}
+impl Event for NetStaticMeshLocation {}
+impl Event for NetStaticMeshRotation {}
+impl Event for NetStaticMeshVelocity {}
+impl Event for RenderTransform {}
+impl Event for Rotation {}
+impl Event for SimulationStep {}
+impl Event for Velocity {}
+
+/// Axis-aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn from_sphere(center: Vec3, radius: f32) -> Self {
+        Self {
+            min: center.add_scalar(-radius),
+            max: center.add_scalar(radius),
+        }
+    }
+
+    /// Smallest box containing both
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub location: Vec3,
+    /// Unit quaternion
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::from_location(Vec3::zeros())
+    }
+}
+
+impl Transform {
+    pub fn from_location(location: Vec3) -> Self {
+        Self {
+            location,
+            rotation: quat_identity(),
+            scale: Vec3::repeat(1.0),
+        }
+    }
+
+    /// Transform of a child whose transform relative to this one is `local`
+    pub fn compose(&self, local: &Self) -> Self {
+        let offset = self.scale.component_mul(&local.location);
+
+        Self {
+            location: self.location + quat_rotate_vec3(&self.rotation, &offset),
+            rotation: self.rotation * local.rotation,
+            scale: self.scale.component_mul(&local.scale),
+        }
+    }
+
+    /// Linear for location and scale, spherical for rotation
+    pub fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Self {
+            location: self.location.lerp(&other.location, t),
+            rotation: quat_slerp(&self.rotation, &other.rotation, t),
+            scale: self.scale.lerp(&other.scale, t),
+        }
+    }
+
+    /// Scales, then rotates, then translates
+    pub fn matrix(&self) -> Mat4 {
+        let matrix = translate(&Mat4::identity(), &self.location) * quat_to_mat4(&self.rotation);
+        scale(&matrix, &self.scale)
+    }
 }
@@ -0,0 +1,80 @@
+use component::Transform;
+use nalgebra_glm::{quat_angle_axis, quat_identity, quat_rotate_vec3, vec3, Vec3};
+
+fn assert_near(a: Vec3, b: Vec3) {
+    assert!((a - b).norm() < 1e-5, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn composes_scale_then_rotation_then_translation() {
+    let parent = Transform {
+        location: vec3(1.0, 2.0, 3.0),
+        rotation: quat_angle_axis(std::f32::consts::FRAC_PI_2, &Vec3::z()),
+        scale: vec3(2.0, 2.0, 2.0),
+    };
+    let local = Transform {
+        location: vec3(1.0, 0.0, 0.0),
+        rotation: quat_angle_axis(std::f32::consts::FRAC_PI_2, &Vec3::x()),
+        scale: vec3(0.5, 1.0, 3.0),
+    };
+
+    let world = parent.compose(&local);
+
+    // the child's offset is scaled to 2 along x, then turned onto y
+    assert_near(world.location, vec3(1.0, 4.0, 3.0));
+    assert_near(world.scale, vec3(1.0, 2.0, 6.0));
+    assert_near(
+        quat_rotate_vec3(&world.rotation, &Vec3::y()),
+        quat_rotate_vec3(&parent.rotation, &Vec3::z()),
+    );
+
+    // composing matches multiplying the matrices
+    let point = vec3(0.3, -0.7, 1.1);
+    let composed = world.matrix() * point.push(1.0);
+    let chained = parent.matrix() * local.matrix() * point.push(1.0);
+    assert_near(composed.xyz(), chained.xyz());
+}
+
+#[test]
+fn identity_composes_to_the_same_transform() {
+    let transform = Transform {
+        location: vec3(-4.0, 0.5, 2.0),
+        rotation: quat_angle_axis(1.0, &vec3(1.0, 1.0, 0.0).normalize()),
+        scale: vec3(1.0, 2.0, 0.5),
+    };
+
+    for composed in [
+        Transform::default().compose(&transform),
+        transform.compose(&Transform::default()),
+    ] {
+        assert_near(composed.location, transform.location);
+        assert_near(composed.scale, transform.scale);
+        assert!((composed.rotation.coords - transform.rotation.coords).norm() < 1e-5);
+    }
+}
+
+#[test]
+fn interpolates_between_endpoints() {
+    let from = Transform::from_location(vec3(0.0, 0.0, 0.0));
+    let to = Transform {
+        location: vec3(2.0, -4.0, 6.0),
+        rotation: quat_angle_axis(std::f32::consts::FRAC_PI_2, &Vec3::y()),
+        scale: vec3(3.0, 3.0, 3.0),
+    };
+
+    let start = from.interpolate(&to, 0.0);
+    assert_near(start.location, from.location);
+    assert!((start.rotation.coords - quat_identity().coords).norm() < 1e-5);
+
+    let end = from.interpolate(&to, 1.0);
+    assert_near(end.location, to.location);
+    assert_near(end.scale, to.scale);
+    assert!((end.rotation.coords - to.rotation.coords).norm() < 1e-5);
+
+    let middle = from.interpolate(&to, 0.5);
+    assert_near(middle.location, vec3(1.0, -2.0, 3.0));
+    assert_near(middle.scale, vec3(2.0, 2.0, 2.0));
+    let halfway = quat_angle_axis(std::f32::consts::FRAC_PI_4, &Vec3::y());
+    assert!((middle.rotation.coords - halfway.coords).norm() < 1e-5);
+    assert!((middle.rotation.norm() - 1.0).abs() < 1e-5);
+}
@@ -0,0 +1,171 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use data::ComponentArray;
+use entity::{EntityAllocator, EntityId};
+
+/// The HashMap-backed implementation that ComponentArray replaced, kept for comparison
+mod hash_map_array {
+    use std::collections::HashMap;
+
+    use entity::EntityId;
+
+    pub struct ComponentArray<T> {
+        data: Vec<(EntityId, T)>,
+        map: HashMap<EntityId, usize>,
+    }
+
+    impl<T> ComponentArray<T> {
+        pub fn new() -> Self {
+            Self {
+                data: Vec::new(),
+                map: HashMap::new(),
+            }
+        }
+
+        pub fn push(&mut self, entity_id: EntityId, data: T) {
+            self.map.insert(entity_id, self.data.len());
+            self.data.push((entity_id, data));
+        }
+
+        pub fn remove(&mut self, entity_id: EntityId) -> Option<T> {
+            let index = self.map.remove(&entity_id)?;
+            for entry in &mut self.map {
+                if *entry.1 > index {
+                    *entry.1 -= 1;
+                }
+            }
+            Some(self.data.remove(index).1)
+        }
+
+        pub fn get(&self, entity_id: EntityId) -> Option<&T> {
+            self.map.get(&entity_id).map(|index| &self.data[*index].1)
+        }
+    }
+}
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+fn entity_ids(count: usize) -> Vec<EntityId> {
+    let mut allocator = EntityAllocator::new();
+    (0..count).map(|_| allocator.allocate()).collect()
+}
+
+/// Deterministic shuffle so removal order doesn't favor either implementation
+fn shuffled(entity_ids: &[EntityId]) -> Vec<EntityId> {
+    let mut entity_ids = entity_ids.to_vec();
+    let mut state = 0x2545_f491_u32;
+    for i in (1..entity_ids.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        entity_ids.swap(i, state as usize % (i + 1));
+    }
+    entity_ids
+}
+
+fn bench_remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("remove_all");
+    for size in SIZES {
+        let entity_ids = entity_ids(size);
+        let removal_order = shuffled(&entity_ids);
+
+        group.bench_with_input(BenchmarkId::new("sparse_set", size), &size, |b, _| {
+            b.iter_batched(
+                || {
+                    let mut array = ComponentArray::new();
+                    for entity_id in &entity_ids {
+                        array.push(*entity_id, [0.0f32; 6]);
+                    }
+                    array
+                },
+                |mut array| {
+                    for entity_id in &removal_order {
+                        black_box(array.remove(*entity_id));
+                    }
+                },
+                criterion::BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_with_input(BenchmarkId::new("hash_map", size), &size, |b, _| {
+            b.iter_batched(
+                || {
+                    let mut array = hash_map_array::ComponentArray::new();
+                    for entity_id in &entity_ids {
+                        array.push(*entity_id, [0.0f32; 6]);
+                    }
+                    array
+                },
+                |mut array| {
+                    for entity_id in &removal_order {
+                        black_box(array.remove(*entity_id));
+                    }
+                },
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup_all");
+    for size in SIZES {
+        let entity_ids = entity_ids(size);
+        let lookup_order = shuffled(&entity_ids);
+
+        let mut array = ComponentArray::new();
+        let mut hash_map = hash_map_array::ComponentArray::new();
+        for entity_id in &entity_ids {
+            array.push(*entity_id, [0.0f32; 6]);
+            hash_map.push(*entity_id, [0.0f32; 6]);
+        }
+
+        group.bench_with_input(BenchmarkId::new("sparse_set", size), &size, |b, _| {
+            b.iter(|| {
+                for entity_id in &lookup_order {
+                    black_box(&array[*entity_id].data);
+                }
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("hash_map", size), &size, |b, _| {
+            b.iter(|| {
+                for entity_id in &lookup_order {
+                    black_box(hash_map.get(*entity_id));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_all");
+    for size in SIZES {
+        let entity_ids = entity_ids(size);
+
+        group.bench_with_input(BenchmarkId::new("sparse_set", size), &size, |b, _| {
+            b.iter(|| {
+                let mut array = ComponentArray::new();
+                for entity_id in &entity_ids {
+                    array.push(*entity_id, [0.0f32; 6]);
+                }
+                array
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("hash_map", size), &size, |b, _| {
+            b.iter(|| {
+                let mut array = hash_map_array::ComponentArray::new();
+                for entity_id in &entity_ids {
+                    array.push(*entity_id, [0.0f32; 6]);
+                }
+                array
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_insert, bench_lookup, bench_remove);
+criterion_main!(benches);
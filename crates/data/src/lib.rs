@@ -1,53 +1,95 @@
-use std::{
-    collections::HashMap,
-    ops::{Index, IndexMut},
-};
+mod query;
+
+use std::ops::{Add, Index, IndexMut};
 
 use entity::EntityId;
+use task::run_map_reduce;
+
+pub use query::{join2, join3, Column, IntoColumn, Join2, Join3, Mut, Ref};
 
 pub struct DataEntry<T> {
     pub data: T,
     pub entity_id: EntityId,
 }
 
+/// Marks a sparse slot with no data in the dense array
+const INVALID_INDEX: u32 = u32::MAX;
+
+/// Sparse set keyed by entity index. Data is kept densely packed so slices can be handed
+/// directly to the task executor, and lookups go through `sparse` without hashing.
 pub struct ComponentArray<T> {
     data: Vec<DataEntry<T>>,
-    map: HashMap<EntityId, usize>,
+    sparse: Vec<u32>,
+}
+
+impl<T> Default for ComponentArray<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T> ComponentArray<T> {
     pub fn new() -> Self {
         ComponentArray::<T> {
             data: Vec::new(),
-            map: HashMap::new(),
+            sparse: Vec::new(),
         }
     }
 
+    /// Replaces the entry left in the entity's slot, if any: the entity's own if pushed twice,
+    /// or a stale entity's that was never removed from this array
     pub fn push(&mut self, entity_id: EntityId, data: T) -> &mut T {
-        debug_assert!(!self.map.contains_key(&entity_id));
-        self.map.insert(entity_id, self.data.len());
+        debug_assert!(entity_id != EntityId::NONE);
+
+        let sparse_index = entity_id.index();
+        if sparse_index >= self.sparse.len() {
+            self.sparse.resize(sparse_index + 1, INVALID_INDEX);
+        }
+
+        let occupied = self.sparse[sparse_index];
+        if occupied != INVALID_INDEX {
+            let occupant = self.data[occupied as usize].entity_id;
+            self.remove(occupant);
+        }
+
+        self.sparse[sparse_index] = self.data.len() as u32;
         self.data.push(DataEntry::<T> { data, entity_id });
         &mut self.data.last_mut().unwrap().data
     }
 
-    pub fn remove(&mut self, entity_id: EntityId) -> T {
-        debug_assert!(self.map.contains_key(&entity_id));
-        let index = self.map[&entity_id];
-        self.map.remove(&entity_id);
-        for entry in &mut self.map {
-            if *entry.1 > index {
-                *entry.1 -= 1;
-            }
+    /// Returns None if the entity has no data in this array, e.g. the ID is stale
+    pub fn remove(&mut self, entity_id: EntityId) -> Option<T> {
+        let index = self.dense_index(entity_id)?;
+        self.sparse[entity_id.index()] = INVALID_INDEX;
+
+        let entry = self.data.swap_remove(index);
+        if let Some(moved) = self.data.get(index) {
+            self.sparse[moved.entity_id.index()] = index as u32;
         }
-        self.data.remove(index).data
+
+        Some(entry.data)
     }
 
     pub fn len(&self) -> usize {
         self.data.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     pub fn contains_entity(&self, entity_id: EntityId) -> bool {
-        self.map.contains_key(&entity_id)
+        self.dense_index(entity_id).is_some()
+    }
+
+    pub fn get(&self, entity_id: EntityId) -> Option<&DataEntry<T>> {
+        let index = self.dense_index(entity_id)?;
+        Some(&self.data[index])
+    }
+
+    pub fn get_mut(&mut self, entity_id: EntityId) -> Option<&mut DataEntry<T>> {
+        let index = self.dense_index(entity_id)?;
+        Some(&mut self.data[index])
     }
 
     pub fn as_slice(&self) -> &[DataEntry<T>] {
@@ -57,23 +99,64 @@ impl<T> ComponentArray<T> {
     pub fn as_mut_slice(&mut self) -> &mut [DataEntry<T>] {
         self.data.as_mut_slice()
     }
+
+    /// Maps every entry across the task executor and combines the results with `reduce`, which
+    /// must be associative. None if the array is empty.
+    pub async fn par_map_reduce<R, M, F>(&self, map: M, reduce: F) -> Option<R>
+    where
+        T: Sync,
+        R: Send,
+        M: Fn(EntityId, &T) -> R + Sync,
+        F: Fn(R, R) -> R + Sync,
+    {
+        run_map_reduce(
+            &self.data,
+            |entry| map(entry.entity_id, &entry.data),
+            reduce,
+        )
+        .await
+    }
+
+    /// Sums `map` over every entry across the task executor
+    pub async fn par_sum<R, M>(&self, map: M) -> R
+    where
+        T: Sync,
+        R: Send + Default + Add<Output = R>,
+        M: Fn(EntityId, &T) -> R + Sync,
+    {
+        self.par_map_reduce(map, |a, b| a + b)
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Index into the dense array, or None if the entity is absent or `entity_id` is stale
+    fn dense_index(&self, entity_id: EntityId) -> Option<usize> {
+        let index = *self.sparse.get(entity_id.index())?;
+        if index == INVALID_INDEX {
+            return None;
+        }
+
+        let index = index as usize;
+        if self.data[index].entity_id == entity_id {
+            Some(index)
+        } else {
+            None
+        }
+    }
 }
 
 impl<T> Index<EntityId> for ComponentArray<T> {
     type Output = DataEntry<T>;
 
     fn index(&self, entity_id: EntityId) -> &Self::Output {
-        debug_assert!(self.map.contains_key(&entity_id));
-        let index = self.map[&entity_id];
-        &self.data[index]
+        self.get(entity_id).expect("entity not in component array")
     }
 }
 
 impl<T> IndexMut<EntityId> for ComponentArray<T> {
     fn index_mut(&mut self, entity_id: EntityId) -> &mut Self::Output {
-        debug_assert!(self.map.contains_key(&entity_id));
-        let index = self.map[&entity_id];
-        &mut self.data[index]
+        self.get_mut(entity_id)
+            .expect("entity not in component array")
     }
 }
 
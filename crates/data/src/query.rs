@@ -0,0 +1,229 @@
+use std::marker::PhantomData;
+
+use entity::EntityId;
+use task::run_slice;
+
+use crate::{ComponentArray, DataEntry};
+
+/// A ComponentArray borrowed for a join, yielding either shared or exclusive references
+pub trait Column<'a> {
+    type Item: 'a;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entity_id(&self, dense_index: usize) -> EntityId;
+
+    fn dense_index(&self, entity_id: EntityId) -> Option<usize>;
+
+    /// # Safety
+    ///
+    /// `dense_index` must be in bounds, and must not be fetched again while an exclusive item
+    /// for it is still alive
+    unsafe fn fetch(&self, dense_index: usize) -> Self::Item;
+}
+
+pub trait IntoColumn<'a> {
+    type Column: Column<'a>;
+
+    fn into_column(self) -> Self::Column;
+}
+
+pub struct Ref<'a, T> {
+    entries: &'a [DataEntry<T>],
+    sparse: &'a [u32],
+}
+
+impl<'a, T> Column<'a> for Ref<'a, T> {
+    type Item = &'a T;
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn entity_id(&self, dense_index: usize) -> EntityId {
+        self.entries[dense_index].entity_id
+    }
+
+    fn dense_index(&self, entity_id: EntityId) -> Option<usize> {
+        let index = *self.sparse.get(entity_id.index())? as usize;
+        match self.entries.get(index) {
+            Some(entry) if entry.entity_id == entity_id => Some(index),
+            _ => None,
+        }
+    }
+
+    unsafe fn fetch(&self, dense_index: usize) -> Self::Item {
+        &self.entries.get_unchecked(dense_index).data
+    }
+}
+
+impl<'a, T> IntoColumn<'a> for &'a ComponentArray<T> {
+    type Column = Ref<'a, T>;
+
+    fn into_column(self) -> Self::Column {
+        Ref {
+            entries: &self.data,
+            sparse: &self.sparse,
+        }
+    }
+}
+
+/// Exclusive column. Entries are only ever accessed through `entries` so that the references
+/// handed out by fetch() never alias a reference to the whole array.
+pub struct Mut<'a, T> {
+    entries: *mut DataEntry<T>,
+    len: usize,
+    sparse: &'a [u32],
+    _marker: PhantomData<&'a mut [DataEntry<T>]>,
+}
+
+/// SAFETY: fetch() hands out each entry at most once, like splitting a &mut [T]
+unsafe impl<T: Send> Send for Mut<'_, T> {}
+unsafe impl<T: Send> Sync for Mut<'_, T> {}
+
+impl<'a, T> Column<'a> for Mut<'a, T> {
+    type Item = &'a mut T;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn entity_id(&self, dense_index: usize) -> EntityId {
+        assert!(dense_index < self.len);
+        // only the entity_id field is read, never the data an exclusive item may point to
+        unsafe { (*self.entries.add(dense_index)).entity_id }
+    }
+
+    fn dense_index(&self, entity_id: EntityId) -> Option<usize> {
+        let index = *self.sparse.get(entity_id.index())? as usize;
+        if index < self.len && self.entity_id(index) == entity_id {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    unsafe fn fetch(&self, dense_index: usize) -> Self::Item {
+        &mut (*self.entries.add(dense_index)).data
+    }
+}
+
+impl<'a, T> IntoColumn<'a> for &'a mut ComponentArray<T> {
+    type Column = Mut<'a, T>;
+
+    fn into_column(self) -> Self::Column {
+        Mut {
+            entries: self.data.as_mut_ptr(),
+            len: self.data.len(),
+            sparse: &self.sparse,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Dense indices of one entity in each joined column
+struct Match<const N: usize> {
+    entity_id: EntityId,
+    dense_indices: [usize; N],
+}
+
+macro_rules! join {
+    ($join: ident, $join_fn: ident, $n: expr, $($col: ident: $ty: ident: $index: ident),+) => {
+        /// Iterates the entities present in every joined array. Iteration is driven by the
+        /// shortest array, so the cost is proportional to its length.
+        pub struct $join<'a, $($ty: Column<'a>),+> {
+            $($col: $ty,)+
+            driver: usize,
+            cursor: usize,
+            _marker: PhantomData<&'a ()>,
+        }
+
+        pub fn $join_fn<'a, $($ty: IntoColumn<'a>),+>(
+            $($col: $ty),+
+        ) -> $join<'a, $($ty::Column),+> {
+            $(let $col = $col.into_column();)+
+
+            let lens = [$($col.len()),+];
+            let driver = (0..$n).min_by_key(|i| lens[*i]).unwrap();
+
+            $join {
+                $($col,)+
+                driver,
+                cursor: 0,
+                _marker: PhantomData,
+            }
+        }
+
+        impl<'a, $($ty: Column<'a>),+> $join<'a, $($ty),+> {
+            fn driver_len(&self) -> usize {
+                [$(self.$col.len()),+][self.driver]
+            }
+
+            fn driver_entity_id(&self, dense_index: usize) -> EntityId {
+                let mut column = 0;
+                $(
+                    if column == self.driver {
+                        return self.$col.entity_id(dense_index);
+                    }
+                    column += 1;
+                )+
+                unreachable!("join driver {} out of range", column)
+            }
+
+            fn next_match(&mut self) -> Option<Match<$n>> {
+                while self.cursor < self.driver_len() {
+                    let entity_id = self.driver_entity_id(self.cursor);
+                    self.cursor += 1;
+
+                    if let ($(Some($col),)+) = ($(self.$col.dense_index(entity_id),)+) {
+                        return Some(Match {
+                            entity_id,
+                            dense_indices: [$($col),+],
+                        });
+                    }
+                }
+
+                None
+            }
+
+            /// Runs `f` for every joined entity across the task executor
+            pub async fn par_for_each<F>(mut self, f: F)
+            where
+                $($ty: Sync,)+
+                F: Fn(EntityId, $($ty::Item),+) + Sync,
+            {
+                let mut matches = Vec::new();
+                while let Some(m) = self.next_match() {
+                    matches.push(m);
+                }
+
+                let columns = ($(&self.$col,)+);
+                run_slice(&matches, |m| {
+                    let ($($col,)+) = columns;
+                    let [$($index),+] = m.dense_indices;
+                    // SAFETY: each entity appears once per array, so no dense index repeats
+                    f(m.entity_id, $(unsafe { $col.fetch($index) }),+);
+                })
+                .await;
+            }
+        }
+
+        impl<'a, $($ty: Column<'a>),+> Iterator for $join<'a, $($ty),+> {
+            type Item = (EntityId, $($ty::Item),+);
+
+            fn next(&mut self) -> Option<Self::Item> {
+                let m = self.next_match()?;
+                let [$($index),+] = m.dense_indices;
+                // SAFETY: the cursor only moves forward, so no dense index is fetched twice
+                Some((m.entity_id, $(unsafe { self.$col.fetch($index) }),+))
+            }
+        }
+    };
+}
+
+join!(Join2, join2, 2, a: A: a_index, b: B: b_index);
+join!(Join3, join3, 3, a: A: a_index, b: B: b_index, c: C: c_index);
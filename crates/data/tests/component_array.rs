@@ -0,0 +1,98 @@
+use data::ComponentArray;
+use entity::EntityAllocator;
+
+#[test]
+fn stale_ids_are_rejected() {
+    let mut allocator = EntityAllocator::new();
+    let stale = allocator.allocate();
+
+    let mut values = ComponentArray::new();
+    values.push(stale, 1);
+    assert_eq!(values.remove(stale), Some(1));
+    assert_eq!(values.remove(stale), None);
+    allocator.free(stale);
+
+    // the recycled slot's data isn't reachable through the stale ID
+    let recycled = allocator.allocate();
+    assert_eq!(recycled.index(), stale.index());
+    values.push(recycled, 2);
+
+    assert!(!values.contains_entity(stale));
+    assert!(values.get(stale).is_none());
+    assert!(values.get_mut(stale).is_none());
+    assert_eq!(values.remove(stale), None);
+
+    assert_eq!(values.get(recycled).map(|entry| entry.data), Some(2));
+    assert_eq!(values.len(), 1);
+}
+
+#[test]
+fn pushing_over_a_stale_entity_replaces_it() {
+    let mut allocator = EntityAllocator::new();
+    let stale = allocator.allocate();
+    let other = allocator.allocate();
+
+    let mut values = ComponentArray::new();
+    values.push(stale, 1);
+    values.push(other, 2);
+
+    // never removed from the array before the slot was recycled
+    allocator.free(stale);
+    let recycled = allocator.allocate();
+    values.push(recycled, 3);
+
+    assert_eq!(values.len(), 2);
+    assert!(!values.contains_entity(stale));
+    let mut entries = values
+        .as_slice()
+        .iter()
+        .map(|entry| (entry.entity_id, entry.data))
+        .collect::<Vec<_>>();
+    entries.sort();
+    assert_eq!(entries, [(recycled, 3), (other, 2)]);
+
+    // as is pushing the same entity twice
+    values.push(recycled, 4);
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[recycled].data, 4);
+    assert_eq!(values.remove(recycled), Some(4));
+    assert_eq!(values.remove(recycled), None);
+}
+
+#[test]
+fn swap_remove_keeps_lookups_valid() {
+    let mut allocator = EntityAllocator::new();
+    let entity_ids = (0..5).map(|_| allocator.allocate()).collect::<Vec<_>>();
+
+    let mut values = ComponentArray::new();
+    for (i, entity_id) in entity_ids.iter().enumerate() {
+        values.push(*entity_id, i);
+    }
+
+    // the last entry moves into the removed one's place
+    assert_eq!(values.remove(entity_ids[1]), Some(1));
+    assert_eq!(values.as_slice()[1].entity_id, entity_ids[4]);
+    for i in [0, 2, 3, 4] {
+        assert_eq!(values[entity_ids[i]].data, i);
+    }
+
+    // removing the last entry moves nothing
+    assert_eq!(values.remove(entity_ids[3]), Some(3));
+    for i in [0, 2, 4] {
+        assert_eq!(values[entity_ids[i]].data, i);
+    }
+    assert_eq!(values.len(), 3);
+
+    // and a removed entity can be pushed again
+    values.push(entity_ids[1], 10);
+    values[entity_ids[4]].data = 40;
+    for (i, expected) in [(0, 0), (1, 10), (2, 2), (4, 40)] {
+        assert_eq!(values[entity_ids[i]].data, expected);
+    }
+    assert!(!values.contains_entity(entity_ids[3]));
+
+    for i in [0, 1, 2, 4] {
+        assert!(values.remove(entity_ids[i]).is_some());
+    }
+    assert!(values.is_empty());
+}
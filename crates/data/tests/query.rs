@@ -0,0 +1,169 @@
+use std::sync::Mutex;
+
+use data::{join2, join3, ComponentArray};
+use entity::{EntityAllocator, EntityId};
+use task::Executor;
+
+/// Entities 0..10. Every entity has an `a`, even ones a `b`, and multiples of three a `c`.
+fn arrays() -> (
+    Vec<EntityId>,
+    ComponentArray<u32>,
+    ComponentArray<u32>,
+    ComponentArray<u32>,
+) {
+    let mut allocator = EntityAllocator::new();
+    let entity_ids = (0..10).map(|_| allocator.allocate()).collect::<Vec<_>>();
+
+    let mut a = ComponentArray::new();
+    let mut b = ComponentArray::new();
+    let mut c = ComponentArray::new();
+    for (i, entity_id) in entity_ids.iter().enumerate() {
+        let i = i as u32;
+        a.push(*entity_id, i);
+        if i.is_multiple_of(2) {
+            b.push(*entity_id, 100 + i);
+        }
+        if i.is_multiple_of(3) {
+            c.push(*entity_id, 200 + i);
+        }
+    }
+
+    (entity_ids, a, b, c)
+}
+
+fn sorted<T: Ord>(mut values: Vec<T>) -> Vec<T> {
+    values.sort();
+    values
+}
+
+#[test]
+fn joins_entities_present_in_both_arrays() {
+    let (entity_ids, a, b, _) = arrays();
+
+    let joined = join2(&a, &b)
+        .map(|(entity_id, a, b)| (entity_id, *a, *b))
+        .collect::<Vec<_>>();
+
+    let expected = [0, 2, 4, 6, 8].map(|i| (entity_ids[i], i as u32, 100 + i as u32));
+    assert_eq!(sorted(joined), expected);
+
+    // the same whichever array drives the join
+    let reversed = join2(&b, &a)
+        .map(|(entity_id, b, a)| (entity_id, *a, *b))
+        .collect::<Vec<_>>();
+    assert_eq!(sorted(reversed), expected);
+}
+
+#[test]
+fn joins_mutably() {
+    let (entity_ids, mut a, b, _) = arrays();
+
+    for (_, a, b) in join2(&mut a, &b) {
+        *a += *b;
+    }
+
+    for (i, entity_id) in entity_ids.iter().enumerate() {
+        let i = i as u32;
+        let expected = if i.is_multiple_of(2) { i + 100 + i } else { i };
+        assert_eq!(a[*entity_id].data, expected);
+    }
+}
+
+#[test]
+fn joins_three_arrays() {
+    let (entity_ids, mut a, b, c) = arrays();
+
+    for (_, a, b, c) in join3(&mut a, &b, &c) {
+        *a = *b + *c;
+    }
+
+    let joined = join3(&a, &b, &c)
+        .map(|(entity_id, a, _, _)| (entity_id, *a))
+        .collect::<Vec<_>>();
+
+    let expected = [0, 6].map(|i| (entity_ids[i], 100 + 200 + 2 * i as u32));
+    assert_eq!(sorted(joined), expected);
+}
+
+#[test]
+fn skips_removed_and_stale_entities() {
+    let mut allocator = EntityAllocator::new();
+    let removed = allocator.allocate();
+    let kept = allocator.allocate();
+
+    let mut a = ComponentArray::new();
+    let mut b = ComponentArray::new();
+    a.push(removed, 1);
+    a.push(kept, 2);
+    b.push(removed, 10);
+    b.push(kept, 20);
+
+    // removed from one array only
+    b.remove(removed);
+    let joined = join2(&a, &b).map(|(entity_id, _, _)| entity_id);
+    assert_eq!(joined.collect::<Vec<_>>(), [kept]);
+
+    // the slot is recycled while the stale ID is still in the other array
+    allocator.free(removed);
+    let recycled = allocator.allocate();
+    assert_eq!(recycled.index(), removed.index());
+    b.push(recycled, 30);
+
+    let joined = join2(&a, &b).map(|(entity_id, _, b)| (entity_id, *b));
+    assert_eq!(joined.collect::<Vec<_>>(), [(kept, 20)]);
+    let joined = join2(&mut b, &a).map(|(entity_id, b, _)| (entity_id, *b));
+    assert_eq!(joined.collect::<Vec<_>>(), [(kept, 20)]);
+
+    assert_eq!(join2(&a, &ComponentArray::<u32>::new()).count(), 0);
+}
+
+#[test]
+fn joins_in_parallel() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    let mut allocator = EntityAllocator::new();
+    let entity_ids = (0..1000).map(|_| allocator.allocate()).collect::<Vec<_>>();
+    let mut a = ComponentArray::new();
+    let mut b = ComponentArray::new();
+    for (i, entity_id) in entity_ids.iter().enumerate() {
+        a.push(*entity_id, 0u64);
+        if !i.is_multiple_of(4) {
+            b.push(*entity_id, i as u64);
+        }
+    }
+
+    let visited = Mutex::new(Vec::new());
+    executor.execute_blocking(&mut async {
+        join2(&mut a, &b)
+            .par_for_each(|entity_id, a, b| {
+                *a = *b * 2;
+                visited.lock().unwrap().push(entity_id);
+            })
+            .await;
+    });
+
+    let visited = sorted(visited.into_inner().unwrap());
+    let expected = (0..1000usize)
+        .filter(|i| !i.is_multiple_of(4))
+        .map(|i| entity_ids[i])
+        .collect::<Vec<_>>();
+    assert_eq!(visited, expected);
+
+    for (i, entity_id) in entity_ids.iter().enumerate() {
+        let expected = if !i.is_multiple_of(4) {
+            i as u64 * 2
+        } else {
+            0
+        };
+        assert_eq!(a[*entity_id].data, expected);
+    }
+
+    let sum = Mutex::new(0);
+    executor.execute_blocking(&mut async {
+        join3(&a, &b, &a)
+            .par_for_each(|_, a, b, _| *sum.lock().unwrap() += *a - *b)
+            .await;
+    });
+    let expected = b.as_slice().iter().map(|entry| entry.data).sum::<u64>();
+    assert_eq!(sum.into_inner().unwrap(), expected);
+}
@@ -0,0 +1,40 @@
+use data::ComponentArray;
+use entity::EntityAllocator;
+use task::Executor;
+
+#[test]
+fn sums_across_the_executor() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    let mut allocator = EntityAllocator::new();
+    let mut values = ComponentArray::new();
+    let mut entity_ids = Vec::new();
+    for i in 0..1000u64 {
+        let entity_id = allocator.allocate();
+        values.push(entity_id, i);
+        entity_ids.push(entity_id);
+    }
+
+    // removed entries aren't summed
+    for entity_id in entity_ids.iter().step_by(2) {
+        values.remove(*entity_id);
+    }
+
+    let mut sum = 0;
+    let mut indices = 0;
+    let mut empty = 1;
+    executor.execute_blocking(&mut async {
+        sum = values.par_sum(|_, value| *value).await;
+        indices = values
+            .par_sum(|entity_id, _| entity_id.index() as u64)
+            .await;
+        empty = ComponentArray::<u64>::new()
+            .par_sum(|_, value| *value)
+            .await;
+    });
+
+    let expected = (0..1000).filter(|i| i % 2 == 1).sum::<u64>();
+    assert_eq!(sum, expected);
+    assert_eq!(indices, expected);
+    assert_eq!(empty, 0);
+}
@@ -1 +1,97 @@
-pub type EntityId = usize;
+use serde::{Deserialize, Serialize};
+
+/// Index into entity storage paired with the generation of that slot. An ID whose generation
+/// doesn't match the slot's current generation refers to a destroyed entity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
+
+impl EntityId {
+    /// Never allocated; used to address events that don't belong to a specific entity
+    pub const NONE: Self = Self {
+        index: u32::MAX,
+        generation: u32::MAX,
+    };
+
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+}
+
+struct Slot {
+    generation: u32,
+    alive: bool,
+}
+
+#[derive(Default)]
+pub struct EntityAllocator {
+    slots: Vec<Slot>,
+    free_indices: Vec<u32>,
+}
+
+impl EntityAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allocate(&mut self) -> EntityId {
+        if let Some(index) = self.free_indices.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.alive = true;
+
+            EntityId {
+                index,
+                generation: slot.generation,
+            }
+        } else {
+            let index = self.slots.len() as u32;
+            debug_assert!(index != u32::MAX, "entity indices exhausted");
+
+            self.slots.push(Slot {
+                generation: 0,
+                alive: true,
+            });
+
+            EntityId {
+                index,
+                generation: 0,
+            }
+        }
+    }
+
+    /// Returns false if the entity was already freed
+    pub fn free(&mut self, entity_id: EntityId) -> bool {
+        if !self.is_alive(entity_id) {
+            return false;
+        }
+
+        let slot = &mut self.slots[entity_id.index()];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.alive = false;
+        self.free_indices.push(entity_id.index);
+
+        true
+    }
+
+    pub fn is_alive(&self, entity_id: EntityId) -> bool {
+        match self.slots.get(entity_id.index()) {
+            Some(slot) => slot.alive && slot.generation == entity_id.generation,
+            None => false,
+        }
+    }
+
+    /// Number of live entities
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free_indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
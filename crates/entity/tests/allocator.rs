@@ -0,0 +1,60 @@
+use entity::{EntityAllocator, EntityId};
+
+#[test]
+fn allocates_distinct_ids() {
+    let mut allocator = EntityAllocator::new();
+    assert!(allocator.is_empty());
+
+    let entity_ids = (0..10).map(|_| allocator.allocate()).collect::<Vec<_>>();
+    for (index, entity_id) in entity_ids.iter().enumerate() {
+        assert_eq!(entity_id.index(), index);
+        assert_eq!(entity_id.generation(), 0);
+        assert!(allocator.is_alive(*entity_id));
+        assert_ne!(*entity_id, EntityId::NONE);
+    }
+    assert_eq!(allocator.len(), 10);
+    assert!(!allocator.is_alive(EntityId::NONE));
+}
+
+#[test]
+fn recycles_freed_slots_with_a_new_generation() {
+    let mut allocator = EntityAllocator::new();
+    let first = allocator.allocate();
+    let second = allocator.allocate();
+
+    assert!(allocator.free(first));
+    assert_eq!(allocator.len(), 1);
+
+    let recycled = allocator.allocate();
+    assert_eq!(recycled.index(), first.index());
+    assert_eq!(recycled.generation(), first.generation() + 1);
+    assert_ne!(recycled, first);
+    assert_eq!(allocator.len(), 2);
+
+    // fresh slots are only used once every freed one is reused
+    let fresh = allocator.allocate();
+    assert_ne!(fresh.index(), first.index());
+    assert_ne!(fresh.index(), second.index());
+
+    // each reuse bumps the generation again
+    assert!(allocator.free(recycled));
+    let again = allocator.allocate();
+    assert_eq!(again.index(), first.index());
+    assert_eq!(again.generation(), first.generation() + 2);
+}
+
+#[test]
+fn stale_ids_are_rejected() {
+    let mut allocator = EntityAllocator::new();
+    let stale = allocator.allocate();
+    assert!(allocator.free(stale));
+    assert!(!allocator.is_alive(stale));
+
+    // freeing twice fails, even once the slot belongs to another entity
+    assert!(!allocator.free(stale));
+    let recycled = allocator.allocate();
+    assert!(!allocator.is_alive(stale));
+    assert!(!allocator.free(stale));
+    assert!(allocator.is_alive(recycled));
+    assert_eq!(allocator.len(), 1);
+}
@@ -0,0 +1,394 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use bincode::Options;
+use entity::EntityId;
+use serde::{Deserialize, Serialize};
+use system::Timestamp;
+
+use crate::{bincode_options, EncodeFn, Event, EventQueue, QueuedEvent, EVENT_TYPES};
+
+const MAGIC: [u8; 4] = *b"SGEJ";
+
+/// Bump whenever Record changes layout. Payload layouts are up to each event type.
+const VERSION: u32 = 1;
+
+/// Largest record a reader accepts, so a corrupt length can't make it allocate gigabytes
+const MAX_RECORD_SIZE: u64 = 16 << 20;
+
+/// Events preallocated per batch, for the same reason. Larger batches grow as they're read.
+const MAX_BATCH_CAPACITY: u32 = 4096;
+
+/// Journals are a header followed by a stream of records. Each batch record is followed by its
+/// events, and an event type record precedes the first event of each type.
+#[derive(Serialize, Deserialize)]
+enum Record<'a> {
+    /// Names the next journal event type index
+    EventType {
+        name: Cow<'a, str>,
+    },
+    Batch {
+        timestamp: Timestamp,
+        len: u32,
+    },
+    Event {
+        event_type: u32,
+        entity_id: EntityId,
+        payload: Cow<'a, [u8]>,
+    },
+}
+
+#[derive(Debug)]
+pub enum JournalError {
+    Io {
+        path: PathBuf,
+        err: std::io::Error,
+    },
+    /// Not a journal, or a corrupt or truncated one
+    Format {
+        path: PathBuf,
+        message: String,
+    },
+    Version {
+        path: PathBuf,
+        version: u32,
+    },
+    /// A payload that doesn't match its event type, e.g. recorded by a different build
+    Payload {
+        event_type: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::Io { path, err } => write!(f, "{}: {}", path.display(), err),
+            JournalError::Format { path, message } => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+            JournalError::Version { path, version } => write!(
+                f,
+                "{}: journal version {} is not supported (expected {})",
+                path.display(),
+                version,
+                VERSION
+            ),
+            JournalError::Payload {
+                event_type,
+                message,
+            } => write!(f, "{}: {}", event_type, message),
+        }
+    }
+}
+
+impl std::error::Error for JournalError {}
+
+/// Records distributed events, see EventManager::set_journal()
+pub struct JournalWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    /// Journal index and encoder of each event type written so far, by event type
+    event_types: Vec<Option<(u32, EncodeFn)>>,
+    next_event_type: u32,
+    payload: Vec<u8>,
+}
+
+impl JournalWriter {
+    pub fn create(path: &Path) -> Result<Self, JournalError> {
+        let io_err = |err| JournalError::Io {
+            path: path.to_path_buf(),
+            err,
+        };
+
+        let mut writer = BufWriter::new(File::create(path).map_err(io_err)?);
+        writer.write_all(&MAGIC).map_err(io_err)?;
+        writer.write_all(&VERSION.to_le_bytes()).map_err(io_err)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer,
+            event_types: Vec::new(),
+            next_event_type: 0,
+            payload: Vec::new(),
+        })
+    }
+
+    /// Writes a batch and flushes it, so a crash loses at most the batch being dispatched
+    pub(crate) fn write_batch<'a, I>(
+        &mut self,
+        timestamp: Timestamp,
+        len: usize,
+        events: I,
+    ) -> Result<(), JournalError>
+    where
+        I: Iterator<Item = (&'a EventQueue, &'a QueuedEvent)>,
+    {
+        self.write_record(&Record::Batch {
+            timestamp,
+            len: len as u32,
+        })?;
+
+        for (queue, event) in events {
+            let (event_type, encode) = self.journal_event_type(event.event_type)?;
+
+            self.payload.clear();
+            let payloads = queue.payloads[event.event_type].as_deref().unwrap();
+            encode(payloads, event.index, &mut self.payload)
+                .map_err(|err| self.format_err(*err))?;
+
+            let payload = std::mem::take(&mut self.payload);
+            let result = self.write_record(&Record::Event {
+                event_type,
+                entity_id: event.entity_id,
+                payload: Cow::Borrowed(&payload),
+            });
+            self.payload = payload;
+            result?;
+        }
+
+        self.writer.flush().map_err(|err| JournalError::Io {
+            path: self.path.clone(),
+            err,
+        })
+    }
+
+    /// Writes the event type's name the first time it's seen
+    fn journal_event_type(&mut self, event_type: usize) -> Result<(u32, EncodeFn), JournalError> {
+        if let Some(Some(journal_event_type)) = self.event_types.get(event_type) {
+            return Ok(*journal_event_type);
+        }
+
+        let (name, encode) = {
+            let event_types = EVENT_TYPES.lock().unwrap();
+            let info = &event_types[event_type];
+            (info.name, info.encode)
+        };
+
+        self.write_record(&Record::EventType {
+            name: Cow::Borrowed(name),
+        })?;
+
+        if self.event_types.len() <= event_type {
+            self.event_types.resize(event_type + 1, None);
+        }
+        let journal_event_type = (self.next_event_type, encode);
+        self.event_types[event_type] = Some(journal_event_type);
+        self.next_event_type += 1;
+
+        Ok(journal_event_type)
+    }
+
+    fn write_record(&mut self, record: &Record) -> Result<(), JournalError> {
+        bincode_options()
+            .serialize_into(&mut self.writer, record)
+            .map_err(|err| self.format_err(*err))
+    }
+
+    fn format_err(&self, err: bincode::ErrorKind) -> JournalError {
+        match err {
+            bincode::ErrorKind::Io(err) => JournalError::Io {
+                path: self.path.clone(),
+                err,
+            },
+            err => JournalError::Format {
+                path: self.path.clone(),
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+struct RecordedEvent {
+    entity_id: EntityId,
+    /// Index into the journal's event type names
+    event_type: u32,
+    payload: Vec<u8>,
+}
+
+/// Events of one distribute() call, read back from a journal
+pub struct JournalBatch {
+    timestamp: Timestamp,
+    events: Vec<RecordedEvent>,
+    /// Names of the journal's event types, by journal event type index
+    event_type_names: Rc<Vec<String>>,
+}
+
+impl JournalBatch {
+    /// Timestamp set on the EventManager when the batch was distributed
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Decodes the batch's events of type `E`, in the order they were distributed
+    pub fn events<E: Event>(&self) -> Result<Vec<(EntityId, E)>, JournalError> {
+        let name = std::any::type_name::<E>();
+
+        self.events
+            .iter()
+            .filter(|event| self.event_type_names[event.event_type as usize] == name)
+            .map(|event| {
+                bincode_options()
+                    .deserialize(&event.payload)
+                    .map(|payload| (event.entity_id, payload))
+                    .map_err(|err| JournalError::Payload {
+                        event_type: name.to_string(),
+                        message: err.to_string(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Pushes the events onto `queue` in order. Events of types not registered in this process
+    /// are skipped, since nothing could have subscribed to them.
+    pub(crate) fn decode_into(&self, queue: &mut EventQueue) -> Result<(), JournalError> {
+        let event_types = {
+            let registered = EVENT_TYPES.lock().unwrap();
+            self.event_type_names
+                .iter()
+                .map(|name| {
+                    registered
+                        .iter()
+                        .position(|info| info.name == name)
+                        .map(|event_type| (event_type, registered[event_type].decode))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for event in &self.events {
+            let (event_type, decode) = match event_types[event.event_type as usize] {
+                Some(event_type) => event_type,
+                None => continue,
+            };
+
+            decode(&event.payload, event_type, event.entity_id, queue).map_err(|err| {
+                JournalError::Payload {
+                    event_type: self.event_type_names[event.event_type as usize].clone(),
+                    message: err.to_string(),
+                }
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct JournalReader {
+    path: PathBuf,
+    reader: BufReader<File>,
+    event_type_names: Rc<Vec<String>>,
+}
+
+impl JournalReader {
+    pub fn open(path: &Path) -> Result<Self, JournalError> {
+        let file = File::open(path).map_err(|err| JournalError::Io {
+            path: path.to_path_buf(),
+            err,
+        })?;
+
+        let mut reader = Self {
+            path: path.to_path_buf(),
+            reader: BufReader::new(file),
+            event_type_names: Rc::new(Vec::new()),
+        };
+
+        let mut header = [0; 8];
+        std::io::Read::read_exact(&mut reader.reader, &mut header)
+            .map_err(|_| reader.format_err("not a journal".to_string()))?;
+
+        if header[..MAGIC.len()] != MAGIC {
+            return Err(reader.format_err("not a journal".to_string()));
+        }
+
+        let version = u32::from_le_bytes(header[MAGIC.len()..].try_into().unwrap());
+        if version != VERSION {
+            return Err(JournalError::Version {
+                path: path.to_path_buf(),
+                version,
+            });
+        }
+
+        Ok(reader)
+    }
+
+    /// None at the end of the journal
+    pub fn next_batch(&mut self) -> Result<Option<JournalBatch>, JournalError> {
+        let at_end = self
+            .reader
+            .fill_buf()
+            .map_err(|err| JournalError::Io {
+                path: self.path.clone(),
+                err,
+            })?
+            .is_empty();
+        if at_end {
+            return Ok(None);
+        }
+
+        let (timestamp, len) = match self.read_record()? {
+            Record::Batch { timestamp, len } => (timestamp, len),
+            _ => return Err(self.format_err("expected a batch".to_string())),
+        };
+
+        let mut events = Vec::with_capacity(len.min(MAX_BATCH_CAPACITY) as usize);
+        while events.len() < len as usize {
+            match self.read_record()? {
+                Record::EventType { name } => {
+                    Rc::make_mut(&mut self.event_type_names).push(name.into_owned())
+                }
+                Record::Event {
+                    event_type,
+                    entity_id,
+                    payload,
+                } => {
+                    if event_type as usize >= self.event_type_names.len() {
+                        return Err(self.format_err(format!("undefined event type {}", event_type)));
+                    }
+
+                    events.push(RecordedEvent {
+                        entity_id,
+                        event_type,
+                        payload: payload.into_owned(),
+                    });
+                }
+                Record::Batch { .. } => {
+                    return Err(self.format_err("batch ended early".to_string()))
+                }
+            }
+        }
+
+        Ok(Some(JournalBatch {
+            timestamp,
+            events,
+            event_type_names: self.event_type_names.clone(),
+        }))
+    }
+
+    fn read_record(&mut self) -> Result<Record<'static>, JournalError> {
+        bincode_options()
+            .with_limit(MAX_RECORD_SIZE)
+            .deserialize_from(&mut self.reader)
+            .map_err(|err| self.format_err(err.to_string()))
+    }
+
+    fn format_err(&self, message: String) -> JournalError {
+        JournalError::Format {
+            path: self.path.clone(),
+            message,
+        }
+    }
+}
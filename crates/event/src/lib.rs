@@ -1,76 +1,672 @@
-use std::{cell::Cell, ptr};
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
 
-use component::Component;
+use bincode::Options;
+use crossbeam_queue::SegQueue;
 use entity::EntityId;
+use serde::{de::DeserializeOwned, Serialize};
+use system::Timestamp;
 
-struct Event {
-    pub component: Component,
-    pub entity_id: EntityId,
+mod journal;
+mod schedule;
+
+pub use journal::{JournalBatch, JournalError, JournalReader, JournalWriter};
+pub use schedule::{cancel_scheduled_event, schedule_event, ScheduleHandle};
+
+use schedule::{Schedule, ScheduledEvent};
+
+/// Payload of an event. Any crate can implement it for its own types; each type is registered
+/// the first time it's pushed or subscribed to. Serializable so that journals can record it.
+pub trait Event: Any + Send + Serialize + DeserializeOwned {}
+
+/// Registered event types, indexed by their dense index, i.e. by order of registration
+static EVENT_TYPES: Mutex<Vec<EventTypeInfo>> = Mutex::new(Vec::new());
+
+struct EventTypeInfo {
+    type_id: TypeId,
+    /// Stable across runs of the same build, unlike the order of registration
+    name: &'static str,
+    encode: EncodeFn,
+    decode: DecodeFn,
+}
+
+/// Serializes the payload at an index of a type's payloads
+type EncodeFn = fn(&dyn Payloads, usize, &mut Vec<u8>) -> bincode::Result<()>;
+
+/// Deserializes a payload and pushes it onto a queue as the given event type
+type DecodeFn = fn(&[u8], usize, EntityId, &mut EventQueue) -> bincode::Result<()>;
+
+fn encode<E: Event>(
+    payloads: &dyn Payloads,
+    index: usize,
+    out: &mut Vec<u8>,
+) -> bincode::Result<()> {
+    let payloads = payloads.as_any().downcast_ref::<Vec<E>>().unwrap();
+    bincode_options().serialize_into(out, &payloads[index])
+}
+
+fn decode<E: Event>(
+    bytes: &[u8],
+    event_type: usize,
+    entity_id: EntityId,
+    queue: &mut EventQueue,
+) -> bincode::Result<()> {
+    let event = bincode_options().deserialize::<E>(bytes)?;
+    // journaled batches are already in order
+    queue.push(event_type, entity_id, event, PushOrder::default());
+    Ok(())
+}
+
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+thread_local! {
+    /// Saves taking the registry lock on every push
+    static EVENT_TYPE_CACHE: RefCell<HashMap<TypeId, usize>> = RefCell::new(HashMap::new());
+}
+
+fn event_type<E: Event>() -> usize {
+    let type_id = TypeId::of::<E>();
+
+    EVENT_TYPE_CACHE.with(|cache| {
+        *cache.borrow_mut().entry(type_id).or_insert_with(|| {
+            let mut event_types = EVENT_TYPES.lock().unwrap();
+            match event_types
+                .iter()
+                .position(|registered| registered.type_id == type_id)
+            {
+                Some(event_type) => event_type,
+                None => {
+                    event_types.push(EventTypeInfo {
+                        type_id,
+                        name: std::any::type_name::<E>(),
+                        encode: encode::<E>,
+                        decode: decode::<E>,
+                    });
+                    event_types.len() - 1
+                }
+            }
+        })
+    })
+}
+
+/// Payloads of a single event type, erased so that a queue can hold any mix of types
+trait Payloads: Send {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn clear(&mut self);
+}
+
+impl<E: Event> Payloads for Vec<E> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+}
+
+struct QueuedEvent {
+    entity_id: EntityId,
+    event_type: usize,
+    /// Index into the payloads of the event type
+    index: usize,
+    order: PushOrder,
+}
+
+/// Where an event was pushed from, for sorting deterministic batches. Doesn't depend on which
+/// thread a task ran on, see task::task_sequence().
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct PushOrder {
+    /// Key of the pushing task, zero outside of tasks
+    task: u64,
+    /// Pushes made before this one by the same task, or outside of tasks by any thread
+    sequence: u64,
+}
+
+/// Shared by every thread, so pushes from several threads outside of tasks never compare equal
+static PUSHES_OUTSIDE_TASKS: AtomicU64 = AtomicU64::new(0);
+
+impl PushOrder {
+    fn current() -> Self {
+        match task::task_sequence() {
+            Some((task, sequence)) => Self {
+                task,
+                sequence: sequence as u64,
+            },
+            None => Self {
+                task: 0,
+                sequence: PUSHES_OUTSIDE_TASKS.fetch_add(1, Ordering::Relaxed),
+            },
+        }
+    }
+}
+
+/// Events in the order they were pushed. Payload storage is kept when the queue is cleared, so
+/// a queue stops allocating once it has seen its usual mix of events.
+struct EventQueue {
+    events: Vec<QueuedEvent>,
+    /// Indexed by event type, created the first time the queue holds an event of that type
+    payloads: Vec<Option<Box<dyn Payloads>>>,
+    /// Handed over to the EventManager's schedule on distribution
+    scheduled: Vec<ScheduledEvent>,
+    cancelled: Vec<ScheduleHandle>,
+}
+
+impl EventQueue {
+    const fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            payloads: Vec::new(),
+            scheduled: Vec::new(),
+            cancelled: Vec::new(),
+        }
+    }
+
+    fn push<E: Event>(
+        &mut self,
+        event_type: usize,
+        entity_id: EntityId,
+        event: E,
+        order: PushOrder,
+    ) {
+        if self.payloads.len() <= event_type {
+            self.payloads.resize_with(event_type + 1, || None);
+        }
+
+        let payloads = self.payloads[event_type]
+            .get_or_insert_with(|| Box::new(Vec::<E>::new()))
+            .as_any_mut()
+            .downcast_mut::<Vec<E>>()
+            .unwrap();
+
+        self.events.push(QueuedEvent {
+            entity_id,
+            event_type,
+            index: payloads.len(),
+            order,
+        });
+        payloads.push(event);
+    }
+
+    fn clear(&mut self) {
+        self.events.clear();
+        for payloads in self.payloads.iter_mut().flatten() {
+            payloads.clear();
+        }
+        self.scheduled.clear();
+        self.cancelled.clear();
+    }
 }
 
+/// Events pushed from a single thread. The lock is only contended while the bus is being
+/// distributed.
 struct EventSender {
-    event_queue: Vec<Event>,
+    event_queue: Mutex<EventQueue>,
 }
 
 impl EventSender {
     pub fn new() -> Self {
         EventSender {
-            event_queue: Vec::new(),
+            event_queue: Mutex::new(EventQueue::new()),
         }
     }
+}
 
-    pub fn push(&mut self, entity_id: EntityId, component: Component) {
-        self.event_queue.push(Event {
-            entity_id,
-            component,
+thread_local! {
+    static EVENT_SENDER: RefCell<Option<Arc<EventSender>>> = const { RefCell::new(None) };
+}
+
+/// Applies one push_event(), schedule_event() or cancel_scheduled_event() call made on an
+/// unregistered thread to the queue being distributed
+type FallbackPush = Box<dyn FnOnce(&mut EventQueue) + Send>;
+
+/// Events pushed from threads not registered with any bus, e.g. threads owned by a library.
+/// Lock-free, so pushing never waits for a distribution in progress. Drained by every
+/// EventManager, so only unambiguous while there is exactly one.
+static FALLBACK_QUEUE: SegQueue<FallbackPush> = SegQueue::new();
+
+/// Number of live EventManagers, to catch fallback events that would be lost or stolen
+static EVENT_MANAGERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Handle to one world's event queues. Cloning shares the same queues, so the handle can be
+/// moved into an executor's thread registration callback.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    senders: Arc<Mutex<Vec<Arc<EventSender>>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Routes push_event() calls made on the current thread to this bus, replacing any bus the
+    /// thread was previously registered with. Does nothing if it's already registered with this
+    /// one.
+    pub fn register_current_thread(&self) {
+        EVENT_SENDER.with(|current| {
+            let mut current = current.borrow_mut();
+            let mut senders = self.senders.lock().unwrap();
+
+            if let Some(sender) = current.as_ref() {
+                if senders.iter().any(|other| Arc::ptr_eq(other, sender)) {
+                    return;
+                }
+            }
+
+            let sender = Arc::new(EventSender::new());
+            senders.push(sender.clone());
+            *current = Some(sender);
         });
     }
+
+    /// Threads registered with the bus, including any that exited or registered elsewhere since
+    /// the last distribute()
+    pub fn registered_threads(&self) -> usize {
+        self.senders.lock().unwrap().len()
+    }
 }
 
-thread_local! {
-    static EVENT_SENDER: Cell<*mut EventSender> = Cell::new(ptr::null_mut())
+/// Queues an event on the bus the current thread is registered with. Safe to call from any
+/// thread: unregistered threads go through a slower fallback queue shared by all of them.
+pub fn push_event<E: Event>(entity_id: EntityId, event: E) {
+    let event_type = event_type::<E>();
+    let order = PushOrder::current();
+    with_event_queue(move |queue| queue.push(event_type, entity_id, event, order));
+}
+
+/// Runs `f` on the current thread's queue. On threads without one, `f` is queued on the fallback
+/// queue and runs when the event is distributed.
+fn with_event_queue(f: impl FnOnce(&mut EventQueue) + Send + 'static) {
+    EVENT_SENDER.with(|sender| match sender.borrow().as_ref() {
+        Some(sender) => f(&mut sender.event_queue.lock().unwrap()),
+        None => {
+            if check_fallback() {
+                FALLBACK_QUEUE.push(Box::new(f));
+            }
+        }
+    })
 }
 
-static mut EVENT_SENDERS: Vec<EventSender> = Vec::new();
+/// Returns false if no EventManager exists, in which case the event is dropped rather than
+/// queued, since nothing would ever drain it
+fn check_fallback() -> bool {
+    let managers = EVENT_MANAGERS.load(Ordering::Relaxed);
 
-/// SAFETY: must be externally synchronized
-pub unsafe fn add_event_sender() {
-    EVENT_SENDERS.push(EventSender::new());
-    EVENT_SENDER.with(|sender| sender.set(EVENT_SENDERS.last_mut().unwrap()));
+    if cfg!(debug_assertions) {
+        match managers {
+            0 => panic!(
+                "event pushed on thread {:?}, which isn't registered with an EventBus, \
+                 while no EventManager exists to receive the event",
+                std::thread::current().id()
+            ),
+            1 => {}
+            n => panic!(
+                "event pushed on thread {:?}, which isn't registered with an EventBus, \
+                 while {} EventManagers exist, so it's ambiguous which one receives the event. \
+                 Call EventBus::register_current_thread on this thread first.",
+                std::thread::current().id(),
+                n
+            ),
+        }
+    }
+
+    managers > 0
 }
 
-pub fn push_event(entity_id: EntityId, component: Component) {
-    EVENT_SENDER.with(|sender| unsafe {
-        sender
-            .get()
-            .as_mut()
-            .unwrap_unchecked()
-            .push(entity_id, component)
-    });
+/// Calls a listener's EventHandler for one payload. Both are downcast to the types the handler
+/// was subscribed with.
+type ErasedHandler = fn(&mut dyn EventListener, EntityId, &dyn Payloads, usize);
+
+fn handle<E, L>(
+    listener: &mut dyn EventListener,
+    entity_id: EntityId,
+    payloads: &dyn Payloads,
+    index: usize,
+) where
+    E: Event,
+    L: EventListener + EventHandler<E>,
+{
+    let listener = (listener as &mut dyn Any).downcast_mut::<L>().unwrap();
+    let payloads = payloads.as_any().downcast_ref::<Vec<E>>().unwrap();
+    listener.handle_event(entity_id, &payloads[index]);
 }
 
-pub struct EventManager;
+/// Event types a listener receives, collected by EventListener::subscribe()
+pub struct Subscriptions {
+    handlers: Vec<(usize, ErasedHandler)>,
+}
+
+impl Subscriptions {
+    /// Delivers events of type `E` to the listener's `EventHandler<E>` impl
+    pub fn add<E, L>(&mut self)
+    where
+        E: Event,
+        L: EventListener + EventHandler<E>,
+    {
+        self.handlers.push((event_type::<E>(), handle::<E, L>));
+    }
+}
+
+/// Position of an event in a deterministic batch
+struct SortKey {
+    order: PushOrder,
+    queue_index: u32,
+    /// Position in its queue
+    position: u32,
+}
+
+pub struct EventManager {
+    bus: EventBus,
+    /// Copy of the bus' senders, so that threads can register while events are dispatched
+    senders: Vec<Arc<EventSender>>,
+    /// Scheduled events that are due first, then swapped with each sender's queue, then the
+    /// fallback queue's events, so that handlers run without holding any lock
+    batch: Vec<EventQueue>,
+    schedule: Schedule,
+    deterministic: bool,
+    sort_keys: Vec<SortKey>,
+    journal: Option<JournalWriter>,
+    /// Decides which scheduled events are due, and is stamped on journaled batches
+    timestamp: Timestamp,
+    /// Types of the listeners the dispatch table was built for, in order
+    listener_types: Vec<TypeId>,
+    /// Indexed by event type: the index and handler of each listener subscribed to it
+    dispatch_table: Vec<Vec<(usize, ErasedHandler)>>,
+    /// Events distributed so far, by event type
+    event_counts: Vec<u64>,
+}
+
+impl Default for EventManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for EventManager {
+    fn drop(&mut self) {
+        EVENT_MANAGERS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
 
 impl EventManager {
     pub fn new() -> Self {
-        Self {}
+        EVENT_MANAGERS.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            bus: EventBus::new(),
+            senders: Vec::new(),
+            batch: Vec::new(),
+            schedule: Schedule::default(),
+            deterministic: false,
+            sort_keys: Vec::new(),
+            journal: None,
+            timestamp: Timestamp::default(),
+            listener_types: Vec::new(),
+            dispatch_table: Vec::new(),
+            event_counts: Vec::new(),
+        }
     }
 
-    pub fn distribute<F>(&mut self, mut event_handler: F)
-    where
-        F: FnMut(EntityId, &Component),
-    {
-        unsafe {
-            for event_sender in &mut EVENT_SENDERS {
-                for event in &mut event_sender.event_queue {
-                    event_handler(event.entity_id, &event.component);
+    pub fn bus(&self) -> &EventBus {
+        &self.bus
+    }
+
+    /// Number of events of type `E` distributed since the manager was created
+    pub fn event_count<E: Event>(&self) -> u64 {
+        self.event_counts
+            .get(event_type::<E>())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// By default events are delivered in the order they were pushed on each thread, and
+    /// threads in the order they registered, which varies from run to run since tasks run on
+    /// whichever worker is free. Deterministic mode sorts each batch by pushing task and push
+    /// order within the task instead, neither of which depend on scheduling, so each task's
+    /// events keep their causal order. Events pushed outside of tasks come first, in the order
+    /// they were pushed, which is only deterministic if a single thread pushes them.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Records every non-empty batch of distributed events to `journal`, until replaced or a
+    /// write fails
+    pub fn set_journal(&mut self, journal: Option<JournalWriter>) {
+        self.journal = journal;
+    }
+
+    /// Current tick. Scheduled events are delivered once it reaches theirs, and batches recorded
+    /// to the journal are stamped with it.
+    pub fn set_timestamp(&mut self, timestamp: Timestamp) {
+        self.timestamp = timestamp;
+    }
+
+    /// Delivers each queued event to the listeners subscribed to its type, in the order of
+    /// `listeners`
+    pub fn distribute(&mut self, listeners: &mut [&mut dyn EventListener]) {
+        self.update_dispatch_table(listeners);
+
+        {
+            let mut senders = self.bus.senders.lock().unwrap();
+            self.senders.clone_from(&senders);
+
+            // only referenced by the bus and self.senders once its thread exited or registered
+            // with another bus. Its last events are still distributed below.
+            senders.retain(|sender| Arc::strong_count(sender) > 2);
+        }
+        let queue_count = self.senders.len() + 2;
+        if self.batch.len() < queue_count {
+            self.batch.resize_with(queue_count, EventQueue::new);
+        }
+
+        for (sender, queue) in self.senders.iter().zip(&mut self.batch[1..]) {
+            std::mem::swap(&mut *sender.event_queue.lock().unwrap(), queue);
+        }
+        while let Some(push) = FALLBACK_QUEUE.pop() {
+            push(&mut self.batch[queue_count - 1]);
+        }
+
+        let (due, queues) = self.batch[..queue_count].split_first_mut().unwrap();
+        self.schedule.update(queues);
+        self.schedule.take_due(self.timestamp, due);
+
+        if self.deterministic {
+            self.sort_batch(queue_count);
+        }
+
+        // recorded before dispatch, so that the journal includes the batch a handler crashed on
+        self.record_batch(queue_count);
+
+        if self.deterministic {
+            for key in &self.sort_keys {
+                let queue = &self.batch[key.queue_index as usize];
+                dispatch_event(
+                    &self.dispatch_table,
+                    &mut self.event_counts,
+                    queue,
+                    &queue.events[key.position as usize],
+                    listeners,
+                );
+            }
+        } else {
+            for queue in &self.batch[..queue_count] {
+                for event in &queue.events {
+                    dispatch_event(
+                        &self.dispatch_table,
+                        &mut self.event_counts,
+                        queue,
+                        event,
+                        listeners,
+                    );
                 }
-                event_sender.event_queue.clear();
             }
         }
+
+        for queue in &mut self.batch[..queue_count] {
+            queue.clear();
+        }
     }
+
+    /// Delivers a batch read from a journal, instead of the events queued on the bus
+    pub fn dispatch_batch(
+        &mut self,
+        batch: &JournalBatch,
+        listeners: &mut [&mut dyn EventListener],
+    ) -> Result<(), JournalError> {
+        self.update_dispatch_table(listeners);
+
+        if self.batch.is_empty() {
+            self.batch.push(EventQueue::new());
+        }
+
+        let result = batch.decode_into(&mut self.batch[0]);
+        if result.is_ok() {
+            let queue = &self.batch[0];
+            for event in &queue.events {
+                dispatch_event(
+                    &self.dispatch_table,
+                    &mut self.event_counts,
+                    queue,
+                    event,
+                    listeners,
+                );
+            }
+        }
+
+        self.batch[0].clear();
+        result
+    }
+
+    fn update_dispatch_table(&mut self, listeners: &[&mut dyn EventListener]) {
+        let listener_types = listeners
+            .iter()
+            .map(|listener| (&**listener as &dyn Any).type_id());
+        if !self.listener_types.iter().copied().eq(listener_types) {
+            self.rebuild_dispatch_table(listeners);
+        }
+    }
+
+    fn record_batch(&mut self, queue_count: usize) {
+        let journal = match self.journal.as_mut() {
+            Some(journal) => journal,
+            None => return,
+        };
+
+        let queues = &self.batch[..queue_count];
+        let len = queues.iter().map(|queue| queue.events.len()).sum();
+        if len == 0 {
+            return;
+        }
+
+        let result = if self.deterministic {
+            let events = self.sort_keys.iter().map(|key| {
+                let queue = &queues[key.queue_index as usize];
+                (queue, &queue.events[key.position as usize])
+            });
+            journal.write_batch(self.timestamp, len, events)
+        } else {
+            let events = queues
+                .iter()
+                .flat_map(|queue| queue.events.iter().map(move |event| (queue, event)));
+            journal.write_batch(self.timestamp, len, events)
+        };
+
+        if let Err(err) = result {
+            println!("stopped recording event journal: {}", err);
+            self.journal = None;
+        }
+    }
+
+    fn sort_batch(&mut self, queue_count: usize) {
+        self.sort_keys.clear();
+        for (queue_index, queue) in self.batch[..queue_count].iter().enumerate() {
+            for (position, event) in queue.events.iter().enumerate() {
+                self.sort_keys.push(SortKey {
+                    order: event.order,
+                    queue_index: queue_index as u32,
+                    position: position as u32,
+                });
+            }
+        }
+
+        // push orders are unique, barring colliding task keys
+        self.sort_keys
+            .sort_unstable_by_key(|key| (key.order, key.queue_index, key.position));
+    }
+
+    fn rebuild_dispatch_table(&mut self, listeners: &[&mut dyn EventListener]) {
+        self.listener_types.clear();
+        self.dispatch_table.clear();
+
+        for (listener_index, listener) in listeners.iter().enumerate() {
+            self.listener_types
+                .push((&**listener as &dyn Any).type_id());
+
+            let mut subscriptions = Subscriptions {
+                handlers: Vec::new(),
+            };
+            listener.subscribe(&mut subscriptions);
+
+            for (event_type, handler) in subscriptions.handlers {
+                if self.dispatch_table.len() <= event_type {
+                    self.dispatch_table.resize_with(event_type + 1, Vec::new);
+                }
+                self.dispatch_table[event_type].push((listener_index, handler));
+            }
+        }
+    }
+}
+
+fn dispatch_event(
+    dispatch_table: &[Vec<(usize, ErasedHandler)>],
+    event_counts: &mut Vec<u64>,
+    queue: &EventQueue,
+    event: &QueuedEvent,
+    listeners: &mut [&mut dyn EventListener],
+) {
+    if event_counts.len() <= event.event_type {
+        event_counts.resize(event.event_type + 1, 0);
+    }
+    event_counts[event.event_type] += 1;
+
+    let handlers = match dispatch_table.get(event.event_type) {
+        Some(handlers) => handlers,
+        None => return,
+    };
+
+    let payloads = queue.payloads[event.event_type].as_deref().unwrap();
+    for (listener_index, handler) in handlers {
+        handler(
+            &mut *listeners[*listener_index],
+            event.entity_id,
+            payloads,
+            event.index,
+        );
+    }
+}
+
+/// Receives events of one type, once subscribed through EventListener::subscribe()
+pub trait EventHandler<E: Event> {
+    fn handle_event(&mut self, entity_id: EntityId, event: &E);
 }
 
-pub trait EventListener {
-    fn receive_event(&mut self, entity_id: EntityId, component: &Component);
+pub trait EventListener: Any {
+    /// Adds the event types delivered to this listener. Events of other types are never
+    /// delivered.
+    fn subscribe(&self, subscriptions: &mut Subscriptions);
 }
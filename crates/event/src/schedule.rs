@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use entity::EntityId;
+use system::Timestamp;
+
+use crate::{event_type, with_event_queue, Event, EventQueue, PushOrder};
+
+/// Identifies a scheduled event, for cancelling it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScheduleHandle(u64);
+
+static NEXT_SCHEDULE_HANDLE: AtomicU64 = AtomicU64::new(0);
+
+/// Payload of a scheduled event, boxed until it's due
+trait ScheduledPayload: Send {
+    fn push_into(
+        self: Box<Self>,
+        event_type: usize,
+        entity_id: EntityId,
+        order: PushOrder,
+        queue: &mut EventQueue,
+    );
+}
+
+impl<E: Event> ScheduledPayload for E {
+    fn push_into(
+        self: Box<Self>,
+        event_type: usize,
+        entity_id: EntityId,
+        order: PushOrder,
+        queue: &mut EventQueue,
+    ) {
+        queue.push(event_type, entity_id, *self, order);
+    }
+}
+
+pub(crate) struct ScheduledEvent {
+    handle: ScheduleHandle,
+    timestamp: Timestamp,
+    entity_id: EntityId,
+    event_type: usize,
+    /// Of the schedule_event() call, since the handle depends on scheduling
+    order: PushOrder,
+    payload: Box<dyn ScheduledPayload>,
+}
+
+/// Queues an event for the first distribute() once the EventManager's timestamp reaches
+/// `timestamp`, or the next one if it already has. Safe to call from any thread, like
+/// push_event().
+///
+/// Ticks are compared with wrapping arithmetic, so `timestamp` must be within 2^31 ticks of the
+/// current one, i.e. about a year at 60 steps per second.
+pub fn schedule_event<E: Event>(
+    timestamp: Timestamp,
+    entity_id: EntityId,
+    event: E,
+) -> ScheduleHandle {
+    let handle = ScheduleHandle(NEXT_SCHEDULE_HANDLE.fetch_add(1, Ordering::Relaxed));
+    let event_type = event_type::<E>();
+    let order = PushOrder::current();
+
+    with_event_queue(move |queue| {
+        queue.scheduled.push(ScheduledEvent {
+            handle,
+            timestamp,
+            entity_id,
+            event_type,
+            order,
+            payload: Box::new(event),
+        })
+    });
+
+    handle
+}
+
+/// Cancels a scheduled event as of the next distribute(). Does nothing if the event has
+/// already been delivered.
+pub fn cancel_scheduled_event(handle: ScheduleHandle) {
+    with_event_queue(move |queue| queue.cancelled.push(handle));
+}
+
+/// Whether an event scheduled for `timestamp` is due at `now`
+fn is_due(timestamp: Timestamp, now: Timestamp) -> bool {
+    (now - timestamp).0 as i32 >= 0
+}
+
+/// Scheduled events that haven't been delivered yet. Delivery is decided purely by comparing
+/// ticks, so if the timestamp moves backwards, e.g. when a client rewinds, delivered events
+/// aren't delivered again and pending ones wait until their tick comes around again.
+#[derive(Default)]
+pub(crate) struct Schedule {
+    pending: Vec<ScheduledEvent>,
+    cancelled: Vec<ScheduleHandle>,
+    due: Vec<ScheduledEvent>,
+}
+
+impl Schedule {
+    /// Takes the events scheduled and cancelled in each queue. Everything is scheduled before
+    /// anything is cancelled, since a cancellation may come from a different thread.
+    pub(crate) fn update(&mut self, queues: &mut [EventQueue]) {
+        for queue in queues.iter_mut() {
+            self.pending.append(&mut queue.scheduled);
+        }
+
+        self.cancelled.clear();
+        for queue in queues.iter_mut() {
+            self.cancelled.append(&mut queue.cancelled);
+        }
+
+        if !self.cancelled.is_empty() {
+            self.cancelled.sort_unstable();
+            self.pending
+                .retain(|event| self.cancelled.binary_search(&event.handle).is_err());
+        }
+    }
+
+    /// Moves the events due at `now` onto `queue`, earliest tick first and in push order within
+    /// a tick, i.e. by scheduling task rather than by which thread happened to get there first
+    pub(crate) fn take_due(&mut self, now: Timestamp, queue: &mut EventQueue) {
+        let mut index = 0;
+        while index < self.pending.len() {
+            if is_due(self.pending[index].timestamp, now) {
+                self.due.push(self.pending.swap_remove(index));
+            } else {
+                index += 1;
+            }
+        }
+
+        self.due
+            .sort_unstable_by_key(|event| ((event.timestamp - now).0 as i32, event.order));
+
+        for event in self.due.drain(..) {
+            event
+                .payload
+                .push_into(event.event_type, event.entity_id, event.order, queue);
+        }
+    }
+}
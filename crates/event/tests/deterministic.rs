@@ -0,0 +1,112 @@
+use entity::{EntityAllocator, EntityId};
+use event::{push_event, Event, EventHandler, EventListener, EventManager, Subscriptions};
+use serde::{Deserialize, Serialize};
+use task::{run_batch, run_scope, Executor};
+
+#[derive(Serialize, Deserialize)]
+struct Pushed(u32);
+
+impl Event for Pushed {}
+
+/// Named to sort before Pushed
+#[derive(Serialize, Deserialize)]
+struct Destroyed;
+
+impl Event for Destroyed {}
+
+#[derive(Default)]
+struct Listener {
+    received: Vec<u32>,
+}
+
+impl EventListener for Listener {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<Pushed, Self>();
+        subscriptions.add::<Destroyed, Self>();
+    }
+}
+
+impl EventHandler<Destroyed> for Listener {
+    fn handle_event(&mut self, _: EntityId, _: &Destroyed) {
+        self.received.push(u32::MAX);
+    }
+}
+
+impl EventHandler<Pushed> for Listener {
+    fn handle_event(&mut self, _: EntityId, Pushed(value): &Pushed) {
+        self.received.push(*value);
+    }
+}
+
+/// Order in which a deterministic EventManager delivers events that many tasks push for the
+/// same entity
+fn delivery_order(threads: usize) -> Vec<u32> {
+    let mut event_manager = EventManager::new();
+    event_manager.set_deterministic(true);
+
+    let event_bus = event_manager.bus().clone();
+    let (mut executor, _) =
+        Executor::with_threads(threads, move || event_bus.register_current_thread());
+
+    for round in 0..2 {
+        executor.execute_blocking(&mut async {
+            run_scope(|scope| {
+                for task in 0..8 {
+                    scope.spawn(async move {
+                        run_batch::<_, 4>(|index| {
+                            for sequence in 0..4 {
+                                let value = round * 1000 + task * 100 + index as u32 * 10;
+                                push_event(EntityId::NONE, Pushed(value + sequence));
+                            }
+                        })
+                        .await;
+                    });
+                }
+            })
+            .await;
+        });
+    }
+
+    let mut listener = Listener::default();
+    event_manager.distribute(&mut [&mut listener]);
+    listener.received
+}
+
+#[test]
+fn orders_batches_the_same_whichever_thread_pushed() {
+    let order = delivery_order(1);
+    assert_eq!(order.len(), 2 * 8 * 4 * 4);
+
+    // each task's events stay in the order it pushed them
+    for chunk in order.chunks(4) {
+        assert!(chunk.windows(2).all(|pair| pair[1] == pair[0] + 1));
+    }
+
+    for threads in [2, 4, 4, 8] {
+        assert_eq!(delivery_order(threads), order);
+    }
+}
+
+#[test]
+fn keeps_each_tasks_events_in_push_order_across_types() {
+    let mut event_manager = EventManager::new();
+    event_manager.set_deterministic(true);
+
+    let event_bus = event_manager.bus().clone();
+    let (mut executor, _) = Executor::with_threads(4, move || event_bus.register_current_thread());
+
+    let entity_id = EntityAllocator::new().allocate();
+    executor.execute_blocking(&mut async {
+        run_batch::<_, 4>(|index| {
+            if index == 0 {
+                push_event(entity_id, Pushed(1));
+                push_event(entity_id, Destroyed);
+            }
+        })
+        .await;
+    });
+
+    let mut listener = Listener::default();
+    event_manager.distribute(&mut [&mut listener]);
+    assert_eq!(listener.received, [1, u32::MAX]);
+}
@@ -0,0 +1,74 @@
+use std::thread;
+
+use entity::EntityId;
+use event::{push_event, Event, EventHandler, EventListener, EventManager, Subscriptions};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Pushed {
+    thread: usize,
+    sequence: usize,
+}
+
+impl Event for Pushed {}
+
+#[derive(Default)]
+struct Listener {
+    received: Vec<Vec<usize>>,
+}
+
+impl EventListener for Listener {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<Pushed, Self>();
+    }
+}
+
+impl EventHandler<Pushed> for Listener {
+    fn handle_event(&mut self, _: EntityId, event: &Pushed) {
+        self.received[event.thread].push(event.sequence);
+    }
+}
+
+// the only test in this file, since unregistered threads push to whichever EventManagers exist
+#[test]
+fn unregistered_threads_push_to_the_only_manager() {
+    const THREADS: usize = 4;
+    const EVENTS: usize = 1000;
+
+    // sorting deterministic batches keeps each thread's order too, and never finds two equal keys
+    for deterministic in [false, true] {
+        let mut event_manager = EventManager::new();
+        event_manager.set_deterministic(deterministic);
+        let mut listener = Listener {
+            received: vec![Vec::new(); THREADS],
+        };
+
+        for round in 0..2 {
+            thread::scope(|scope| {
+                for thread in 0..THREADS {
+                    scope.spawn(move || {
+                        for sequence in 0..EVENTS {
+                            push_event(EntityId::NONE, Pushed { thread, sequence });
+                        }
+                    });
+                }
+
+                // distributing while threads push takes some of their events early
+                if round == 1 {
+                    event_manager.distribute(&mut [&mut listener]);
+                }
+            });
+
+            event_manager.distribute(&mut [&mut listener]);
+        }
+
+        for received in &listener.received {
+            let expected = (0..EVENTS).chain(0..EVENTS).collect::<Vec<_>>();
+            assert_eq!(*received, expected);
+        }
+        assert_eq!(
+            event_manager.event_count::<Pushed>(),
+            2 * (THREADS * EVENTS) as u64
+        );
+    }
+}
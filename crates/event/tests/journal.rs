@@ -0,0 +1,142 @@
+use std::{fs, num::Wrapping, path::PathBuf};
+
+use entity::{EntityAllocator, EntityId};
+use event::{
+    push_event, Event, EventHandler, EventListener, EventManager, JournalReader, JournalWriter,
+    Subscriptions,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Moved {
+    location: [f32; 3],
+}
+
+impl Event for Moved {}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Renamed(String);
+
+impl Event for Renamed {}
+
+/// Subscribes to both types so that distribute() has somewhere to deliver them
+#[derive(Default)]
+struct Listener;
+
+impl EventListener for Listener {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<Moved, Self>();
+        subscriptions.add::<Renamed, Self>();
+    }
+}
+
+impl EventHandler<Moved> for Listener {
+    fn handle_event(&mut self, _: EntityId, _: &Moved) {}
+}
+
+impl EventHandler<Renamed> for Listener {
+    fn handle_event(&mut self, _: EntityId, _: &Renamed) {}
+}
+
+fn journal_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("event-journal-{}-{}.sgj", name, std::process::id()))
+}
+
+#[test]
+fn reads_back_recorded_batches() {
+    let path = journal_path("round-trip");
+    let mut entities = EntityAllocator::new();
+    let (first, second) = (entities.allocate(), entities.allocate());
+
+    {
+        let mut event_manager = EventManager::new();
+        event_manager.bus().register_current_thread();
+        event_manager.set_journal(Some(JournalWriter::create(&path).unwrap()));
+
+        event_manager.set_timestamp(Wrapping(7));
+        push_event(
+            first,
+            Moved {
+                location: [1.0, -2.5, 3.25],
+            },
+        );
+        push_event(second, Renamed("second".to_string()));
+        push_event(
+            second,
+            Moved {
+                location: [0.0, f32::MIN_POSITIVE, f32::MAX],
+            },
+        );
+        event_manager.distribute(&mut [&mut Listener]);
+
+        // empty batches aren't recorded
+        event_manager.set_timestamp(Wrapping(8));
+        event_manager.distribute(&mut [&mut Listener]);
+
+        event_manager.set_timestamp(Wrapping(9));
+        push_event(first, Renamed("first".to_string()));
+        event_manager.distribute(&mut [&mut Listener]);
+    }
+
+    let mut reader = JournalReader::open(&path).unwrap();
+
+    let batch = reader.next_batch().unwrap().unwrap();
+    assert_eq!(batch.timestamp(), Wrapping(7));
+    assert_eq!(batch.len(), 3);
+    assert_eq!(
+        batch.events::<Moved>().unwrap(),
+        vec![
+            (
+                first,
+                Moved {
+                    location: [1.0, -2.5, 3.25]
+                }
+            ),
+            (
+                second,
+                Moved {
+                    location: [0.0, f32::MIN_POSITIVE, f32::MAX]
+                }
+            ),
+        ]
+    );
+    assert_eq!(
+        batch.events::<Renamed>().unwrap(),
+        vec![(second, Renamed("second".to_string()))]
+    );
+
+    let batch = reader.next_batch().unwrap().unwrap();
+    assert_eq!(batch.timestamp(), Wrapping(9));
+    assert_eq!(batch.events::<Moved>().unwrap(), vec![]);
+    assert_eq!(
+        batch.events::<Renamed>().unwrap(),
+        vec![(first, Renamed("first".to_string()))]
+    );
+
+    assert!(reader.next_batch().unwrap().is_none());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn rejects_a_corrupt_batch_length() {
+    let path = journal_path("corrupt-length");
+
+    // a batch record claiming u32::MAX events, then nothing
+    let mut bytes = b"SGEJ".to_vec();
+    bytes.extend_from_slice(&1u32.to_le_bytes());
+    bytes.extend_from_slice(&[1, 0, 0xfc, 0xff, 0xff, 0xff, 0xff]);
+    fs::write(&path, bytes).unwrap();
+
+    let mut reader = JournalReader::open(&path).unwrap();
+    assert!(reader.next_batch().is_err());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn rejects_other_files() {
+    let path = journal_path("not-a-journal");
+    fs::write(&path, b"{\"events\": []}").unwrap();
+
+    assert!(JournalReader::open(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,202 @@
+use std::{num::Wrapping, thread, time::Duration};
+
+use entity::EntityId;
+use event::{
+    cancel_scheduled_event, schedule_event, Event, EventHandler, EventListener, EventManager,
+    ScheduleHandle, Subscriptions,
+};
+use serde::{Deserialize, Serialize};
+use system::Timestamp;
+use task::{run_scope, Executor};
+
+#[derive(Serialize, Deserialize)]
+struct Scheduled(u32);
+
+impl Event for Scheduled {}
+
+#[derive(Default)]
+struct Listener {
+    received: Vec<u32>,
+}
+
+impl EventListener for Listener {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<Scheduled, Self>();
+    }
+}
+
+impl EventHandler<Scheduled> for Listener {
+    fn handle_event(&mut self, _: EntityId, Scheduled(value): &Scheduled) {
+        self.received.push(*value);
+    }
+}
+
+fn event_manager() -> EventManager {
+    let event_manager = EventManager::new();
+    event_manager.bus().register_current_thread();
+    event_manager
+}
+
+/// Events delivered by distributing at `timestamp`
+fn distribute(event_manager: &mut EventManager, timestamp: u32) -> Vec<u32> {
+    let mut listener = Listener::default();
+    event_manager.set_timestamp(Wrapping(timestamp));
+    event_manager.distribute(&mut [&mut listener]);
+    listener.received
+}
+
+fn schedule(timestamp: u32, value: u32) -> ScheduleHandle {
+    let timestamp: Timestamp = Wrapping(timestamp);
+    schedule_event(timestamp, EntityId::NONE, Scheduled(value))
+}
+
+#[test]
+fn delivers_once_the_tick_comes() {
+    let mut event_manager = event_manager();
+
+    schedule(10, 1);
+    schedule(12, 2);
+    // already due
+    schedule(5, 3);
+
+    assert_eq!(distribute(&mut event_manager, 9), [3]);
+    assert_eq!(distribute(&mut event_manager, 10), [1]);
+    // skipped ticks don't lose events
+    assert_eq!(distribute(&mut event_manager, 20), [2]);
+    assert!(distribute(&mut event_manager, 21).is_empty());
+}
+
+#[test]
+fn delivers_across_timestamp_wraparound() {
+    let mut event_manager = event_manager();
+
+    schedule(u32::MAX, 1);
+    schedule(0, 2);
+    schedule(3, 3);
+
+    assert!(distribute(&mut event_manager, u32::MAX - 1).is_empty());
+    assert_eq!(distribute(&mut event_manager, u32::MAX), [1]);
+    assert_eq!(distribute(&mut event_manager, 0), [2]);
+    assert!(distribute(&mut event_manager, 2).is_empty());
+    assert_eq!(distribute(&mut event_manager, 3), [3]);
+}
+
+#[test]
+fn delivers_earliest_tick_first_then_in_scheduling_order() {
+    let mut event_manager = event_manager();
+
+    schedule(7, 1);
+    schedule(6, 2);
+    schedule(7, 3);
+    schedule(6, 4);
+    schedule(7, 5);
+
+    assert_eq!(distribute(&mut event_manager, 7), [2, 4, 1, 3, 5]);
+}
+
+/// Delivery order of the events scheduled for the same tick by four tasks, each scheduling two
+/// after blocking for its delay in milliseconds
+fn scheduled_by_tasks(delays: [u64; 4]) -> Vec<u32> {
+    let mut event_manager = event_manager();
+    let event_bus = event_manager.bus().clone();
+    let (mut executor, _) = Executor::with_threads(4, move || event_bus.register_current_thread());
+
+    executor.execute_blocking(&mut async {
+        run_scope(|scope| {
+            for (index, delay) in delays.into_iter().enumerate() {
+                let value = index as u32 * 10;
+                scope.spawn(async move {
+                    thread::sleep(Duration::from_millis(delay));
+                    schedule(1, value);
+                    schedule(1, value + 1);
+                });
+            }
+        })
+        .await;
+    });
+
+    distribute(&mut event_manager, 1)
+}
+
+#[test]
+fn delivers_in_task_order_whichever_task_scheduled_first() {
+    let forwards = scheduled_by_tasks([0, 5, 10, 15]);
+    let backwards = scheduled_by_tasks([15, 10, 5, 0]);
+    assert_eq!(forwards, backwards);
+
+    // each task's events keep their order
+    let position = |value| forwards.iter().position(|&x| x == value).unwrap();
+    for value in [0, 10, 20, 30] {
+        assert!(position(value) < position(value + 1), "{:?}", forwards);
+    }
+}
+
+#[test]
+fn cancels_in_the_same_batch() {
+    let mut event_manager = event_manager();
+
+    let cancelled = schedule(1, 1);
+    schedule(1, 2);
+    cancel_scheduled_event(cancelled);
+
+    assert_eq!(distribute(&mut event_manager, 1), [2]);
+}
+
+#[test]
+fn cancels_from_another_thread() {
+    let mut event_manager = event_manager();
+
+    let cancelled = schedule(1, 1);
+    schedule(1, 2);
+
+    let event_bus = event_manager.bus().clone();
+    thread::spawn(move || {
+        event_bus.register_current_thread();
+        cancel_scheduled_event(cancelled);
+    })
+    .join()
+    .unwrap();
+
+    assert!(distribute(&mut event_manager, 0).is_empty());
+    assert_eq!(distribute(&mut event_manager, 1), [2]);
+
+    // scheduled on another thread and cancelled here in the same batch
+    let event_bus = event_manager.bus().clone();
+    let cancelled = thread::spawn(move || {
+        event_bus.register_current_thread();
+        schedule(2, 3)
+    })
+    .join()
+    .unwrap();
+    cancel_scheduled_event(cancelled);
+
+    assert!(distribute(&mut event_manager, 2).is_empty());
+}
+
+#[test]
+fn cancelling_after_delivery_does_nothing() {
+    let mut event_manager = event_manager();
+
+    let delivered = schedule(1, 1);
+    assert_eq!(distribute(&mut event_manager, 1), [1]);
+
+    cancel_scheduled_event(delivered);
+    schedule(2, 2);
+    assert_eq!(distribute(&mut event_manager, 2), [2]);
+}
+
+#[test]
+fn rewinding_neither_redelivers_nor_loses_events() {
+    let mut event_manager = event_manager();
+
+    schedule(10, 1);
+    schedule(12, 2);
+
+    assert_eq!(distribute(&mut event_manager, 10), [1]);
+
+    // e.g. a client rewinding to replay corrected steps
+    assert!(distribute(&mut event_manager, 8).is_empty());
+    assert!(distribute(&mut event_manager, 10).is_empty());
+    assert!(distribute(&mut event_manager, 11).is_empty());
+    assert_eq!(distribute(&mut event_manager, 12), [2]);
+}
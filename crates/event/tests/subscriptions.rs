@@ -0,0 +1,157 @@
+use std::{cell::RefCell, rc::Rc};
+
+use entity::{EntityAllocator, EntityId};
+use event::{push_event, Event, EventHandler, EventListener, EventManager, Subscriptions};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Moved(u32);
+
+impl Event for Moved {}
+
+#[derive(Serialize, Deserialize)]
+struct Hit(u32);
+
+impl Event for Hit {}
+
+/// Nothing subscribes to this
+#[derive(Serialize, Deserialize)]
+struct Ignored;
+
+impl Event for Ignored {}
+
+/// Deliveries to every listener, in the order they happened
+type Log = Rc<RefCell<Vec<(&'static str, EntityId, u32)>>>;
+
+struct MoveListener(Log);
+
+impl EventListener for MoveListener {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<Moved, Self>();
+    }
+}
+
+impl EventHandler<Moved> for MoveListener {
+    fn handle_event(&mut self, entity_id: EntityId, Moved(value): &Moved) {
+        self.0
+            .borrow_mut()
+            .push(("move listener", entity_id, *value));
+    }
+}
+
+struct BothListener(Log);
+
+impl EventListener for BothListener {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<Moved, Self>();
+        subscriptions.add::<Hit, Self>();
+    }
+}
+
+impl EventHandler<Moved> for BothListener {
+    fn handle_event(&mut self, entity_id: EntityId, Moved(value): &Moved) {
+        self.0
+            .borrow_mut()
+            .push(("both listener", entity_id, *value));
+    }
+}
+
+impl EventHandler<Hit> for BothListener {
+    fn handle_event(&mut self, entity_id: EntityId, Hit(value): &Hit) {
+        self.0
+            .borrow_mut()
+            .push(("both listener", entity_id, 1000 + *value));
+    }
+}
+
+/// Subscribes to nothing, so is never called
+struct DeafListener;
+
+impl EventListener for DeafListener {
+    fn subscribe(&self, _: &mut Subscriptions) {}
+}
+
+fn event_manager() -> EventManager {
+    let event_manager = EventManager::new();
+    event_manager.bus().register_current_thread();
+    event_manager
+}
+
+#[test]
+fn routes_events_to_subscribers_in_listener_order() {
+    let mut event_manager = event_manager();
+    let mut entities = EntityAllocator::new();
+    let (first, second) = (entities.allocate(), entities.allocate());
+    let log = Log::default();
+
+    push_event(first, Moved(1));
+    push_event(second, Hit(2));
+    push_event(first, Ignored);
+    push_event(second, Moved(3));
+
+    let mut move_listener = MoveListener(log.clone());
+    let mut both_listener = BothListener(log.clone());
+    event_manager.distribute(&mut [&mut DeafListener, &mut both_listener, &mut move_listener]);
+
+    assert_eq!(
+        *log.borrow(),
+        vec![
+            ("both listener", first, 1),
+            ("move listener", first, 1),
+            ("both listener", second, 1002),
+            ("both listener", second, 3),
+            ("move listener", second, 3),
+        ]
+    );
+}
+
+#[test]
+fn reroutes_when_the_listeners_change() {
+    let mut event_manager = event_manager();
+    let log = Log::default();
+    let mut move_listener = MoveListener(log.clone());
+    let mut both_listener = BothListener(log.clone());
+
+    push_event(EntityId::NONE, Hit(1));
+    event_manager.distribute(&mut [&mut move_listener]);
+    assert!(log.borrow().is_empty());
+
+    push_event(EntityId::NONE, Hit(2));
+    push_event(EntityId::NONE, Moved(3));
+    event_manager.distribute(&mut [&mut move_listener, &mut both_listener]);
+    assert_eq!(
+        std::mem::take(&mut *log.borrow_mut()),
+        vec![
+            ("both listener", EntityId::NONE, 1002),
+            ("move listener", EntityId::NONE, 3),
+            ("both listener", EntityId::NONE, 3),
+        ]
+    );
+
+    push_event(EntityId::NONE, Moved(4));
+    event_manager.distribute(&mut [&mut both_listener]);
+    assert_eq!(*log.borrow(), vec![("both listener", EntityId::NONE, 4)]);
+}
+
+#[test]
+fn counts_every_distributed_event_by_type() {
+    let mut event_manager = event_manager();
+    let mut move_listener = MoveListener(Log::default());
+
+    for value in 0..3 {
+        push_event(EntityId::NONE, Moved(value));
+    }
+    push_event(EntityId::NONE, Hit(0));
+    push_event(EntityId::NONE, Ignored);
+    event_manager.distribute(&mut [&mut move_listener]);
+
+    // whether or not anything subscribed
+    assert_eq!(event_manager.event_count::<Moved>(), 3);
+    assert_eq!(event_manager.event_count::<Hit>(), 1);
+    assert_eq!(event_manager.event_count::<Ignored>(), 1);
+
+    push_event(EntityId::NONE, Moved(3));
+    event_manager.distribute(&mut [&mut move_listener]);
+    assert_eq!(event_manager.event_count::<Moved>(), 4);
+    assert_eq!(event_manager.event_count::<Hit>(), 1);
+}
@@ -0,0 +1,135 @@
+use std::marker::PhantomData;
+
+use entity::EntityId;
+use event::{push_event, Event, EventHandler, EventListener, EventManager, Subscriptions};
+use serde::{Deserialize, Serialize};
+
+/// Same layout as Damage, but a different event
+#[derive(Serialize, Deserialize)]
+struct Heal(u32);
+
+impl Event for Heal {}
+
+#[derive(Serialize, Deserialize)]
+struct Damage(u32);
+
+impl Event for Damage {}
+
+/// Owns heap data, which must survive being queued and dispatched
+#[derive(Serialize, Deserialize)]
+struct Chat {
+    from: String,
+    lines: Vec<String>,
+}
+
+impl Event for Chat {}
+
+/// Each instantiation is an event type of its own
+#[derive(Serialize, Deserialize)]
+struct Tagged<T> {
+    value: u32,
+    #[serde(skip)]
+    tag: PhantomData<T>,
+}
+
+impl<T: Send + 'static> Event for Tagged<T> {}
+
+#[derive(Serialize, Deserialize)]
+struct Red;
+
+#[derive(Serialize, Deserialize)]
+struct Blue;
+
+#[derive(Default)]
+struct Health {
+    health: i64,
+    chat: Vec<String>,
+    red: Vec<u32>,
+}
+
+impl EventListener for Health {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<Heal, Self>();
+        subscriptions.add::<Damage, Self>();
+        subscriptions.add::<Chat, Self>();
+        subscriptions.add::<Tagged<Red>, Self>();
+    }
+}
+
+impl EventHandler<Heal> for Health {
+    fn handle_event(&mut self, _: EntityId, Heal(amount): &Heal) {
+        self.health += *amount as i64;
+    }
+}
+
+impl EventHandler<Damage> for Health {
+    fn handle_event(&mut self, _: EntityId, Damage(amount): &Damage) {
+        self.health -= *amount as i64;
+    }
+}
+
+impl EventHandler<Chat> for Health {
+    fn handle_event(&mut self, _: EntityId, chat: &Chat) {
+        for line in &chat.lines {
+            self.chat.push(format!("{}: {}", chat.from, line));
+        }
+    }
+}
+
+impl EventHandler<Tagged<Red>> for Health {
+    fn handle_event(&mut self, _: EntityId, tagged: &Tagged<Red>) {
+        self.red.push(tagged.value);
+    }
+}
+
+fn tagged<T>(value: u32) -> Tagged<T> {
+    Tagged {
+        value,
+        tag: PhantomData,
+    }
+}
+
+#[test]
+fn delivers_each_payload_to_the_handler_for_its_type() {
+    let mut event_manager = EventManager::new();
+    event_manager.bus().register_current_thread();
+    let mut health = Health::default();
+
+    // several batches, so payload storage is reused between them
+    for round in 0..3 {
+        push_event(EntityId::NONE, Heal(10));
+        push_event(EntityId::NONE, Damage(3));
+        push_event(EntityId::NONE, tagged::<Red>(round));
+        push_event(EntityId::NONE, tagged::<Blue>(100 + round));
+        push_event(
+            EntityId::NONE,
+            Chat {
+                from: format!("player {}", round),
+                lines: vec!["hello".to_string(), "bye".to_string()],
+            },
+        );
+        push_event(EntityId::NONE, Damage(1));
+
+        event_manager.distribute(&mut [&mut health]);
+    }
+
+    assert_eq!(health.health, 3 * (10 - 3 - 1));
+    assert_eq!(health.red, vec![0, 1, 2]);
+    assert_eq!(
+        health.chat,
+        [0, 1, 2]
+            .iter()
+            .flat_map(|round| {
+                [
+                    format!("player {}: hello", round),
+                    format!("player {}: bye", round),
+                ]
+            })
+            .collect::<Vec<_>>()
+    );
+
+    assert_eq!(event_manager.event_count::<Heal>(), 3);
+    assert_eq!(event_manager.event_count::<Damage>(), 6);
+    assert_eq!(event_manager.event_count::<Tagged<Red>>(), 3);
+    assert_eq!(event_manager.event_count::<Tagged<Blue>>(), 3);
+}
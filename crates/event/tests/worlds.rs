@@ -0,0 +1,121 @@
+use std::thread;
+
+use entity::EntityId;
+use event::{push_event, Event, EventHandler, EventListener, EventManager, Subscriptions};
+use serde::{Deserialize, Serialize};
+use task::{run_batch, Executor};
+
+#[derive(Serialize, Deserialize)]
+struct Pushed(u32);
+
+impl Event for Pushed {}
+
+#[derive(Default)]
+struct Listener {
+    received: Vec<u32>,
+}
+
+impl EventListener for Listener {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<Pushed, Self>();
+    }
+}
+
+impl EventHandler<Pushed> for Listener {
+    fn handle_event(&mut self, _: EntityId, Pushed(value): &Pushed) {
+        self.received.push(*value);
+    }
+}
+
+struct World {
+    event_manager: EventManager,
+    executor: Executor,
+}
+
+impl World {
+    fn new() -> Self {
+        let event_manager = EventManager::new();
+        let event_bus = event_manager.bus().clone();
+        let (executor, _) = Executor::with_threads(2, move || event_bus.register_current_thread());
+
+        Self {
+            event_manager,
+            executor,
+        }
+    }
+
+    fn push(&mut self, base: u32) {
+        self.executor.execute_blocking(&mut async {
+            run_batch::<_, 16>(|index| push_event(EntityId::NONE, Pushed(base + index as u32)))
+                .await;
+        });
+    }
+
+    fn distribute(&mut self) -> Vec<u32> {
+        let mut listener = Listener::default();
+        self.event_manager.distribute(&mut [&mut listener]);
+        listener.received.sort_unstable();
+        listener.received
+    }
+}
+
+#[test]
+fn worlds_keep_their_own_events() {
+    let mut first = World::new();
+    let mut second = World::new();
+
+    first.push(100);
+    second.push(200);
+    first.push(300);
+
+    // distributing the second world first mustn't take the first world's events
+    assert_eq!(second.distribute(), (200..216).collect::<Vec<_>>());
+    assert_eq!(
+        first.distribute(),
+        (100..116).chain(300..316).collect::<Vec<_>>()
+    );
+
+    assert!(first.distribute().is_empty());
+    assert!(second.distribute().is_empty());
+}
+
+#[test]
+fn forgets_threads_once_their_last_events_are_distributed() {
+    let mut event_manager = EventManager::new();
+    let event_bus = event_manager.bus().clone();
+
+    let pushing = thread::spawn(move || {
+        event_bus.register_current_thread();
+        // registering again keeps the same queue
+        event_bus.register_current_thread();
+        push_event(EntityId::NONE, Pushed(1));
+    });
+    pushing.join().unwrap();
+    assert_eq!(event_manager.bus().registered_threads(), 1);
+
+    let mut listener = Listener::default();
+    event_manager.distribute(&mut [&mut listener]);
+    assert_eq!(listener.received, vec![1]);
+    assert_eq!(event_manager.bus().registered_threads(), 0);
+}
+
+#[test]
+fn threads_move_to_the_bus_they_registered_with_last() {
+    let mut first = EventManager::new();
+    let mut second = EventManager::new();
+
+    first.bus().register_current_thread();
+    push_event(EntityId::NONE, Pushed(1));
+    second.bus().register_current_thread();
+    push_event(EntityId::NONE, Pushed(2));
+
+    let mut listener = Listener::default();
+    first.distribute(&mut [&mut listener]);
+    assert_eq!(listener.received, vec![1]);
+    assert_eq!(first.bus().registered_threads(), 0);
+
+    let mut listener = Listener::default();
+    second.distribute(&mut [&mut listener]);
+    assert_eq!(listener.received, vec![2]);
+    assert_eq!(second.bus().registered_threads(), 1);
+}
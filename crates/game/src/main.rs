@@ -1,14 +1,94 @@
+use std::path::Path;
+
+use archetype::Archetypes;
 use client::Client;
+use level::Level;
 use server::Server;
 use winit::event_loop::EventLoop;
 
+const LEVEL: &str = "default";
+
 fn main() {
+    let archetypes = match Archetypes::load() {
+        Ok(archetypes) => archetypes,
+        Err(err) => {
+            eprintln!("failed to load archetypes: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let level = match Level::load(LEVEL, &archetypes) {
+        Ok(level) => level,
+        Err(err) => {
+            eprintln!("failed to load level: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let deterministic = std::env::args().any(|arg| arg == "--deterministic");
+    let dump_schedule = std::env::args().any(|arg| arg == "--dump-schedule");
+
     if std::env::args().any(|arg| arg == "--server") {
-        let server = Server::new();
+        let mut server = Server::new(archetypes);
+        server.set_deterministic_events(deterministic);
+
+        match arg_value("--load") {
+            Some(path) => {
+                if let Err(err) = server.restore(Path::new(&path)) {
+                    eprintln!("failed to load save: {}", err);
+                    std::process::exit(1);
+                }
+            }
+            None => server.load_level(&level),
+        }
+
+        if let Some(path) = arg_value("--checkpoint") {
+            server.set_checkpoint_path(path.into());
+        }
+
+        if let Some(path) = arg_value("--journal") {
+            if let Err(err) = server.record_journal(Path::new(&path)) {
+                eprintln!("failed to create journal: {}", err);
+                std::process::exit(1);
+            }
+        }
+
+        if let Some(path) = arg_value("--profile") {
+            server.profile(path.into());
+        }
+
+        if dump_schedule {
+            print!("{}", server.dump_schedule());
+        }
+
         server.run();
     } else {
         let event_loop = EventLoop::new();
-        let client = Client::new(&event_loop);
-        client.run(event_loop);
+        let mut client = Client::new(&event_loop, archetypes);
+        client.set_deterministic_events(deterministic);
+
+        if let Some(path) = arg_value("--journal") {
+            if let Err(err) = client.record_journal(Path::new(&path)) {
+                eprintln!("failed to create journal: {}", err);
+                std::process::exit(1);
+            }
+        }
+
+        if let Some(path) = arg_value("--profile") {
+            client.profile(path.into());
+        }
+
+        if dump_schedule {
+            print!("{}", client.dump_schedule());
+        }
+
+        client.run(event_loop, &level);
     }
 }
+
+/// Value following `name` on the command line
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    args.next()
+}
@@ -1,6 +1,6 @@
 use std::{
     collections::HashMap,
-    ptr,
+    ptr::{self, addr_of, addr_of_mut},
     sync::Arc,
     thread::{self, ThreadId},
 };
@@ -54,7 +54,7 @@ pub fn gfx_delegate<'a>() -> &'a GfxDelegate {
     let thread_id = thread::current().id();
 
     unsafe {
-        for gfx_delegate in &GFX_DELEGATES {
+        for gfx_delegate in &*addr_of!(GFX_DELEGATES) {
             if gfx_delegate.0 == thread_id {
                 return &gfx_delegate.1;
             }
@@ -80,9 +80,10 @@ pub struct Graphics {
 impl Graphics {
     pub fn new(window: Window, thread_ids: &[ThreadId]) -> Self {
         unsafe {
-            GFX_DELEGATES.clear();
+            let gfx_delegates = &mut *addr_of_mut!(GFX_DELEGATES);
+            gfx_delegates.clear();
             for thread_id in thread_ids {
-                GFX_DELEGATES.push((*thread_id, GfxDelegate(ptr::null())));
+                gfx_delegates.push((*thread_id, GfxDelegate(ptr::null())));
             }
         }
 
@@ -97,7 +98,7 @@ impl Graphics {
         self.vulkan.begin_instance_update();
 
         unsafe {
-            for gfx_delegate in &mut GFX_DELEGATES {
+            for gfx_delegate in &mut *addr_of_mut!(GFX_DELEGATES) {
                 gfx_delegate.1 .0 = &*self;
             }
         }
@@ -107,7 +108,7 @@ impl Graphics {
         self.vulkan.end_instance_update_and_render();
 
         unsafe {
-            for gfx_delegate in &mut GFX_DELEGATES {
+            for gfx_delegate in &mut *addr_of_mut!(GFX_DELEGATES) {
                 gfx_delegate.1 .0 = ptr::null();
             }
         }
@@ -1,14 +1,21 @@
-use component::Component;
+use component::RenderTransform;
 use entity::EntityId;
-use event::EventListener;
+use event::{EventHandler, EventListener, Subscriptions};
 use gfx::gfx_delegate;
 use nalgebra_glm::{ortho_rh_zo, translate, Mat4, Vec3};
+use system::{Declarations, ScheduledSystem, Stage, SystemFuture, Tick};
 
 pub struct System {
     entity_id: Option<EntityId>,
     location: Vec3,
 }
 
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl System {
     pub fn new() -> Self {
         Self {
@@ -35,12 +42,27 @@ impl System {
     }
 }
 
+impl ScheduledSystem for System {
+    fn declare(&self, declarations: &mut Declarations) {
+        // run sequentially until we get secondary command buffers up and running
+        declarations.stage(Stage::Draw).writes("gfx commands");
+    }
+
+    fn run<'a>(&'a mut self, _: Stage, _: &'a Tick) -> SystemFuture<'a> {
+        Box::pin(self.render())
+    }
+}
+
 impl EventListener for System {
-    fn receive_event(&mut self, entity_id: EntityId, component: &Component) {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<RenderTransform, Self>();
+    }
+}
+
+impl EventHandler<RenderTransform> for System {
+    fn handle_event(&mut self, entity_id: EntityId, RenderTransform(transform): &RenderTransform) {
         if self.entity_id.as_ref() == Some(&entity_id) {
-            if let Component::RenderLocation(location) = component {
-                self.location = *location;
-            }
+            self.location = transform.location;
         }
     }
 }
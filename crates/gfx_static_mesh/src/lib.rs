@@ -1,65 +1,78 @@
-use component::Component;
-use data::ComponentArray;
+use component::{RenderTransform, Transform};
+use data::{join2, ComponentArray};
 use entity::EntityId;
-use event::EventListener;
+use event::{EventHandler, EventListener, Subscriptions};
 use gfx::{gfx_delegate, StaticMesh};
-use nalgebra_glm::{translate, Mat4, Vec3};
+use system::{Declarations, ScheduledSystem, Stage, SystemFuture, Tick};
 use task::run_slice;
 
-struct StaticMeshComponent {
-    static_mesh: StaticMesh,
-    location: Vec3,
+/// Meshes and their transforms are kept apart, so that the event handler only touches
+/// transforms, and rendering joins the two
+pub struct System {
+    static_meshes: ComponentArray<StaticMesh>,
+    transforms: ComponentArray<Transform>,
 }
 
-pub struct System {
-    components: ComponentArray<StaticMeshComponent>,
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl System {
     pub fn new() -> Self {
         Self {
-            components: ComponentArray::new(),
+            static_meshes: ComponentArray::new(),
+            transforms: ComponentArray::new(),
         }
     }
 
     pub fn create_component(&mut self, entity_id: EntityId, static_mesh: StaticMesh) {
-        self.components.push(
-            entity_id,
-            StaticMeshComponent {
-                static_mesh,
-                location: Vec3::zeros(),
-            },
-        );
+        self.static_meshes.push(entity_id, static_mesh);
+        self.transforms.push(entity_id, Transform::default());
     }
 
     pub fn destroy_component(&mut self, entity_id: EntityId) {
-        self.components.remove(entity_id);
+        self.static_meshes.remove(entity_id);
+        self.transforms.remove(entity_id);
     }
 
     pub async fn render(&mut self) {
-        run_slice(self.components.as_slice(), |component| {
-            let gfx_delegate = gfx_delegate();
-            let model_matrix = translate(&Mat4::identity(), &component.data.location);
-            gfx_delegate.update_static_mesh(&component.data.static_mesh, model_matrix);
-        })
-        .await;
+        join2(&self.static_meshes, &self.transforms)
+            .par_for_each(|_, static_mesh, transform| {
+                gfx_delegate().update_static_mesh(static_mesh, transform.matrix());
+            })
+            .await;
 
-        run_slice(self.components.as_slice(), |component| {
+        run_slice(self.static_meshes.as_slice(), |component| {
             let gfx_delegate = gfx_delegate();
-            gfx_delegate.draw_instance(&component.data.static_mesh);
+            gfx_delegate.draw_instance(&component.data);
         })
         .await;
     }
 }
 
+impl ScheduledSystem for System {
+    fn declare(&self, declarations: &mut Declarations) {
+        // run sequentially until we get secondary command buffers up and running
+        declarations.stage(Stage::Draw).writes("gfx commands");
+    }
+
+    fn run<'a>(&'a mut self, _: Stage, _: &'a Tick) -> SystemFuture<'a> {
+        Box::pin(self.render())
+    }
+}
+
 impl EventListener for System {
-    fn receive_event(&mut self, entity_id: EntityId, component: &Component) {
-        if !self.components.contains_entity(entity_id) {
-            return;
-        }
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<RenderTransform, Self>();
+    }
+}
 
-        if let Component::RenderLocation(location) = component {
-            self.components[entity_id].data.location = *location;
+impl EventHandler<RenderTransform> for System {
+    fn handle_event(&mut self, entity_id: EntityId, RenderTransform(transform): &RenderTransform) {
+        if let Some(entry) = self.transforms.get_mut(entity_id) {
+            entry.data = *transform;
         }
     }
 }
@@ -0,0 +1,252 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use archetype::{Archetypes, ComponentKind, SpawnParams};
+use component::Transform;
+use nalgebra_glm::{Quat, Vec3};
+use network_utils::NetworkId;
+use serde::Deserialize;
+
+const LEVELS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../res/levels");
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Level {
+    pub entities: Vec<LevelEntity>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelEntity {
+    /// Name of an archetype in `res/archetypes.ron`
+    pub archetype: String,
+    /// Overrides the archetype's mesh
+    #[serde(default)]
+    pub mesh: Option<String>,
+    #[serde(default = "Vec3::zeros")]
+    pub location: Vec3,
+    /// Unit quaternion written as `[x, y, z, w]`
+    #[serde(default = "nalgebra_glm::quat_identity")]
+    pub rotation: Quat,
+    #[serde(default = "unit_scale")]
+    pub scale: Vec3,
+    #[serde(default = "Vec3::zeros")]
+    pub velocity: Vec3,
+    /// Identifies a networked entity on both client and server. Allocated by the server if not
+    /// given, in which case the entity can't be targeted from the level.
+    #[serde(default)]
+    pub network_id: Option<NetworkId>,
+    /// Network ID of the entity this one is attached to. Location, rotation and scale are then
+    /// relative to the parent.
+    #[serde(default)]
+    pub parent: Option<NetworkId>,
+    /// Network ID of the entity a camera follows
+    #[serde(default)]
+    pub target: Option<NetworkId>,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io {
+        path: PathBuf,
+        err: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Invalid {
+        path: PathBuf,
+        entity_index: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io { path, err } => write!(f, "{}: {}", path.display(), err),
+            LevelError::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            LevelError::Invalid {
+                path,
+                entity_index,
+                message,
+            } => write!(
+                f,
+                "{}: entity {}: {}",
+                path.display(),
+                entity_index,
+                message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+fn unit_scale() -> Vec3 {
+    Vec3::repeat(1.0)
+}
+
+impl LevelEntity {
+    pub fn spawn_params(&self) -> SpawnParams {
+        SpawnParams {
+            archetype: self.archetype.clone(),
+            transform: Transform {
+                location: self.location,
+                rotation: self.rotation.normalize(),
+                scale: self.scale,
+            },
+            velocity: self.velocity,
+            network_id: self.network_id,
+            mesh: self.mesh.clone(),
+        }
+    }
+}
+
+impl Level {
+    /// Loads `res/levels/<name>.ron`
+    pub fn load(name: &str, archetypes: &Archetypes) -> Result<Self, LevelError> {
+        let path = Path::new(LEVELS_DIR).join(name).with_extension("ron");
+        Self::load_path(&path, archetypes)
+    }
+
+    pub fn load_path(path: &Path, archetypes: &Archetypes) -> Result<Self, LevelError> {
+        let source = fs::read_to_string(path).map_err(|err| LevelError::Io {
+            path: path.to_path_buf(),
+            err,
+        })?;
+
+        Self::parse(&source, path, archetypes)
+    }
+
+    /// `path` is only used for error reporting
+    pub fn parse(source: &str, path: &Path, archetypes: &Archetypes) -> Result<Self, LevelError> {
+        let level: Level = ron::from_str(source).map_err(|err| LevelError::Parse {
+            path: path.to_path_buf(),
+            line: err.position.line,
+            column: err.position.col,
+            message: err.code.to_string(),
+        })?;
+
+        level.validate(path, archetypes)?;
+
+        Ok(level)
+    }
+
+    fn validate(&self, path: &Path, archetypes: &Archetypes) -> Result<(), LevelError> {
+        let invalid = |entity_index, message: String| LevelError::Invalid {
+            path: path.to_path_buf(),
+            entity_index,
+            message,
+        };
+
+        // archetype of the entity with each network ID
+        let mut network_ids = HashMap::new();
+        for (entity_index, entity) in self.entities.iter().enumerate() {
+            let archetype = match archetypes.get(&entity.archetype) {
+                Some(archetype) => archetype,
+                None => {
+                    return Err(invalid(
+                        entity_index,
+                        format!("unknown archetype '{}'", entity.archetype),
+                    ))
+                }
+            };
+
+            if entity.network_id.is_some() && !archetype.has_component(ComponentKind::Network) {
+                return Err(invalid(
+                    entity_index,
+                    format!("archetype '{}' is not networked", entity.archetype),
+                ));
+            }
+
+            if entity.rotation.norm() < f32::EPSILON {
+                return Err(invalid(
+                    entity_index,
+                    "rotation has zero length".to_string(),
+                ));
+            }
+
+            if entity.mesh.is_some() && !archetype.has_component(ComponentKind::StaticMesh) {
+                return Err(invalid(
+                    entity_index,
+                    format!("archetype '{}' has no mesh to override", entity.archetype),
+                ));
+            }
+
+            if let Some(network_id) = entity.network_id {
+                if network_ids
+                    .insert(network_id, entity.archetype.as_str())
+                    .is_some()
+                {
+                    return Err(invalid(
+                        entity_index,
+                        format!("duplicate network_id {}", network_id),
+                    ));
+                }
+            }
+        }
+
+        for (entity_index, entity) in self.entities.iter().enumerate() {
+            if let Some(parent) = entity.parent {
+                let parent_archetype = match network_ids.get(&parent) {
+                    Some(archetype) => archetype,
+                    None => {
+                        return Err(invalid(
+                            entity_index,
+                            format!("parent {} is not the network_id of any entity", parent),
+                        ))
+                    }
+                };
+
+                for name in [entity.archetype.as_str(), parent_archetype] {
+                    if !archetypes
+                        .get(name)
+                        .unwrap()
+                        .has_component(ComponentKind::Hierarchy)
+                    {
+                        return Err(invalid(
+                            entity_index,
+                            format!("archetype '{}' can't be part of a hierarchy", name),
+                        ));
+                    }
+                }
+
+                let archetype = archetypes.get(&entity.archetype).unwrap();
+                if archetype.has_component(ComponentKind::Network)
+                    || archetype.has_component(ComponentKind::Physics)
+                {
+                    return Err(invalid(
+                        entity_index,
+                        format!(
+                            "archetype '{}' is simulated, so it can't have a parent",
+                            entity.archetype
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(target) = entity.target {
+                if !network_ids.contains_key(&target) {
+                    return Err(invalid(
+                        entity_index,
+                        format!("target {} is not the network_id of any entity", target),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use archetype::Archetypes;
+use level::{Level, LevelError};
+
+const ARCHETYPES: &str = r#"{
+    "camera": [camera, hierarchy],
+    "player": [network, physics, static_mesh(mesh: "suzanne"), hierarchy],
+}"#;
+
+fn parse(source: &str) -> Result<Level, LevelError> {
+    let archetypes = Archetypes::parse(ARCHETYPES, Path::new("archetypes.ron")).unwrap();
+    Level::parse(source, Path::new("test.ron"), &archetypes)
+}
+
+#[test]
+fn loads_the_default_level() {
+    Level::load("default", &Archetypes::load().unwrap()).unwrap();
+}
+
+#[test]
+fn reports_where_malformed_levels_go_wrong() {
+    let source = r#"(
+    entities: [
+        (
+            archetype: "player",
+            location: [0.0, 0.0 0.0],
+        ),
+    ],
+)"#;
+
+    match parse(source) {
+        Err(LevelError::Parse {
+            path, line, column, ..
+        }) => {
+            assert_eq!(path, Path::new("test.ron"));
+            assert_eq!((line, column), (5, 33));
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("malformed level was accepted"),
+    }
+}
+
+#[test]
+fn rejects_unknown_archetypes() {
+    let source = r#"(
+    entities: [
+        (archetype: "player", network_id: Some(0)),
+        (archetype: "monster"),
+    ],
+)"#;
+
+    match parse(source) {
+        Err(LevelError::Invalid {
+            entity_index,
+            message,
+            ..
+        }) => {
+            assert_eq!(entity_index, 1);
+            assert!(message.contains("monster"), "{}", message);
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("unknown archetype was accepted"),
+    }
+}
+
+#[test]
+fn leaves_missing_network_ids_to_the_server() {
+    let level = parse(r#"(entities: [(archetype: "player")])"#).unwrap();
+
+    let params = level.entities[0].spawn_params();
+    assert_eq!(params.archetype, "player");
+    assert_eq!(params.network_id, None);
+}
+
+#[test]
+fn rejects_network_ids_on_local_archetypes() {
+    let source = r#"(entities: [(archetype: "camera", network_id: Some(3))])"#;
+
+    match parse(source) {
+        Err(LevelError::Invalid { entity_index, .. }) => assert_eq!(entity_index, 0),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("network ID on a local archetype was accepted"),
+    }
+}
@@ -1,4 +1,4 @@
-use nalgebra_glm::{Vec2, Vec3};
+use nalgebra_glm::{quat, Quat, Vec2, Vec3};
 use serde::{Deserialize, Serialize};
 use system::{Timestamp, STEPS_PER_SECOND};
 
@@ -8,6 +8,9 @@ pub const NETWORK_SNAPSHOTS_LEN: usize = STEPS_PER_SECOND;
 
 pub const PING_UPDATE_INTERVAL: u32 = STEPS_PER_SECOND as u32;
 
+/// Laminar stream carrying Spawn and Despawn packets, which must arrive in order
+pub const SPAWN_STREAM_ID: u8 = 1;
+
 pub trait TimestampOffset {
     fn sub_client_offset(&mut self, offset: Timestamp);
 }
@@ -17,6 +20,8 @@ pub enum Packet {
     EstablishConnection,
     Input(InputPacket),
     Ping(PingPacket),
+    Spawn(SpawnPacket),
+    Despawn(DespawnPacket),
     StaticMesh(StaticMeshPacket),
     Velocity(VelocityPacket),
 }
@@ -27,9 +32,9 @@ impl From<&[u8]> for Packet {
     }
 }
 
-impl Into<Vec<u8>> for Packet {
-    fn into(self) -> Vec<u8> {
-        bincode::serialize(&self).unwrap()
+impl From<Packet> for Vec<u8> {
+    fn from(packet: Packet) -> Self {
+        bincode::serialize(&packet).unwrap()
     }
 }
 
@@ -44,9 +49,37 @@ pub struct PingPacket {
     pub timestamp: Timestamp,
 }
 
-impl Into<Packet> for PingPacket {
-    fn into(self) -> Packet {
-        Packet::Ping(self)
+impl From<PingPacket> for Packet {
+    fn from(packet: PingPacket) -> Self {
+        Packet::Ping(packet)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SpawnPacket {
+    pub network_id: NetworkId,
+    pub archetype: String,
+    pub mesh: Option<String>,
+    pub location: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+    pub velocity: Vec3,
+}
+
+impl From<SpawnPacket> for Packet {
+    fn from(packet: SpawnPacket) -> Self {
+        Packet::Spawn(packet)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct DespawnPacket {
+    pub network_id: NetworkId,
+}
+
+impl From<DespawnPacket> for Packet {
+    fn from(packet: DespawnPacket) -> Self {
+        Packet::Despawn(packet)
     }
 }
 
@@ -55,12 +88,13 @@ pub struct StaticMeshPacket {
     pub timestamp: Timestamp,
     pub network_id: NetworkId,
     pub location: Vec3,
+    pub rotation: CompressedQuat,
     pub velocity: Vec3,
 }
 
-impl Into<Packet> for StaticMeshPacket {
-    fn into(self) -> Packet {
-        Packet::StaticMesh(self)
+impl From<StaticMeshPacket> for Packet {
+    fn from(packet: StaticMeshPacket) -> Self {
+        Packet::StaticMesh(packet)
     }
 }
 
@@ -83,8 +117,62 @@ impl TimestampOffset for VelocityPacket {
     }
 }
 
-impl Into<Packet> for VelocityPacket {
-    fn into(self) -> Packet {
-        Packet::Velocity(self)
+impl From<VelocityPacket> for Packet {
+    fn from(packet: VelocityPacket) -> Self {
+        Packet::Velocity(packet)
+    }
+}
+
+/// Largest magnitude of the three smallest components of a unit quaternion
+const SMALLEST_THREE_RANGE: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+const SMALLEST_THREE_BITS: u32 = 10;
+
+const SMALLEST_THREE_MAX: u32 = (1 << SMALLEST_THREE_BITS) - 1;
+
+/// Unit quaternion packed into 32 bits using "smallest three" compression. The largest component
+/// is dropped and recovered from the unit length, and the remaining three are quantized to 10
+/// bits each, which keeps the error below a quarter of a degree.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompressedQuat(u32);
+
+impl From<Quat> for CompressedQuat {
+    fn from(rotation: Quat) -> Self {
+        let coords = rotation.normalize().coords;
+
+        let largest = (0..4)
+            .max_by(|a, b| coords[*a].abs().total_cmp(&coords[*b].abs()))
+            .unwrap();
+
+        // q and -q are the same rotation, so the dropped component can always be positive
+        let sign = if coords[largest] < 0.0 { -1.0 } else { 1.0 };
+
+        let mut packed = (largest as u32) << (3 * SMALLEST_THREE_BITS);
+        for (shift, i) in (0..4).filter(|i| *i != largest).rev().enumerate() {
+            let normalized = (sign * coords[i] / SMALLEST_THREE_RANGE).clamp(-1.0, 1.0);
+            let quantized = ((normalized * 0.5 + 0.5) * SMALLEST_THREE_MAX as f32).round() as u32;
+            packed |= quantized << (shift as u32 * SMALLEST_THREE_BITS);
+        }
+
+        Self(packed)
+    }
+}
+
+impl From<CompressedQuat> for Quat {
+    fn from(compressed: CompressedQuat) -> Self {
+        let largest = (compressed.0 >> (3 * SMALLEST_THREE_BITS)) as usize;
+
+        let mut coords = [0.0; 4];
+        for (shift, i) in (0..4).filter(|i| *i != largest).rev().enumerate() {
+            let quantized =
+                (compressed.0 >> (shift as u32 * SMALLEST_THREE_BITS)) & SMALLEST_THREE_MAX;
+            let normalized = quantized as f32 / SMALLEST_THREE_MAX as f32 * 2.0 - 1.0;
+            coords[i] = normalized * SMALLEST_THREE_RANGE;
+        }
+
+        let sum_squares: f32 = coords.iter().map(|c| c * c).sum();
+        coords[largest] = (1.0 - sum_squares).max(0.0).sqrt();
+
+        quat(coords[0], coords[1], coords[2], coords[3]).normalize()
     }
 }
@@ -0,0 +1,79 @@
+use nalgebra_glm::{quat, quat_dot, Quat};
+use network_utils::CompressedQuat;
+
+/// Angle of the rotation taking `a` to `b`, in degrees
+fn angle_between(a: &Quat, b: &Quat) -> f32 {
+    let dot = quat_dot(a, b).abs().min(1.0);
+    (2.0 * dot.acos()).to_degrees()
+}
+
+fn round_trip(rotation: &Quat) -> Quat {
+    CompressedQuat::from(*rotation).into()
+}
+
+/// xorshift64, so the test needs no rand dependency and always checks the same rotations
+struct Rng(u64);
+
+impl Rng {
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniformly distributed unit quaternion
+    fn next_rotation(&mut self) -> Quat {
+        let (u1, u2, u3) = (self.next_f32(), self.next_f32(), self.next_f32());
+        let tau = std::f32::consts::TAU;
+        quat(
+            (1.0 - u1).sqrt() * (tau * u2).sin(),
+            (1.0 - u1).sqrt() * (tau * u2).cos(),
+            u1.sqrt() * (tau * u3).sin(),
+            u1.sqrt() * (tau * u3).cos(),
+        )
+    }
+}
+
+#[test]
+fn error_stays_below_a_quarter_degree() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    for _ in 0..100_000 {
+        let rotation = rng.next_rotation();
+        let error = angle_between(&rotation, &round_trip(&rotation));
+        assert!(error < 0.25, "{:?} is off by {} degrees", rotation, error);
+    }
+}
+
+#[test]
+fn drops_whichever_component_is_largest() {
+    for largest in 0..4 {
+        for sign in [1.0, -1.0] {
+            let mut coords = [0.3, -0.2, 0.1, 0.25];
+            coords[largest] = sign * 0.9;
+            let rotation = quat(coords[0], coords[1], coords[2], coords[3]).normalize();
+
+            let error = angle_between(&rotation, &round_trip(&rotation));
+            assert!(
+                error < 0.25,
+                "component {} off by {} degrees",
+                largest,
+                error
+            );
+        }
+    }
+}
+
+#[test]
+fn negated_quaternions_compress_the_same() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+    for _ in 0..1000 {
+        let rotation = rng.next_rotation();
+        assert_eq!(
+            CompressedQuat::from(rotation),
+            CompressedQuat::from(-rotation)
+        );
+    }
+}
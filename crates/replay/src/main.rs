@@ -0,0 +1,287 @@
+//! Replays an event journal recorded with `--journal` through a headless simulation, so that
+//! sessions can be reproduced under a debugger. Only physics is simulated, since networking and
+//! graphics are replaced by the recorded events.
+
+use std::{collections::HashMap, fmt, path::Path};
+
+use archetype::{
+    ArchetypeRegistry, Archetypes, ComponentKind, Entity, EntityLifecycle, SpawnError,
+};
+use component::{Location, SimulationStep};
+use entity::{EntityAllocator, EntityId};
+use event::{
+    EventHandler, EventListener, EventManager, JournalBatch, JournalError, JournalReader,
+    Subscriptions,
+};
+use nalgebra_glm::Vec3;
+use system::Timestamp;
+use task::Executor;
+
+fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: sphere-replay <journal>");
+            std::process::exit(1);
+        }
+    };
+
+    let archetypes = match Archetypes::load() {
+        Ok(archetypes) => archetypes,
+        Err(err) => {
+            eprintln!("failed to load archetypes: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut journal = match JournalReader::open(Path::new(&path)) {
+        Ok(journal) => journal,
+        Err(err) => {
+            eprintln!("failed to open journal: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut replay = Replay::new(archetypes);
+    let mut failed = false;
+
+    loop {
+        let batch = match journal.next_batch() {
+            Ok(Some(batch)) => batch,
+            Ok(None) => break,
+            Err(err) => {
+                eprintln!("stopped reading journal: {}", err);
+                break;
+            }
+        };
+
+        if let Err(err) = replay.replay_batch(&batch) {
+            eprintln!("stopped replaying at tick {}: {}", batch.timestamp(), err);
+            match err {
+                ReplayError::Journal(_) => failed = true,
+                _ => replay.diverged(batch.timestamp()),
+            }
+            break;
+        }
+    }
+
+    println!(
+        "replayed {} batches, {} steps and {} events",
+        replay.batches, replay.steps, replay.events
+    );
+
+    if let Some(timestamp) = replay.diverged_at {
+        println!("replay diverged from the recording at tick {}", timestamp);
+        std::process::exit(2);
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+enum ReplayError {
+    Journal(JournalError),
+    /// A recorded spawn that failed to replay
+    Spawn(SpawnError),
+    /// Spawns and destroys replayed in a different order than they were recorded in
+    EntityId {
+        replayed: EntityId,
+        recorded: EntityId,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Journal(err) => write!(f, "{}", err),
+            ReplayError::Spawn(err) => write!(f, "failed to spawn recorded entity: {}", err),
+            ReplayError::EntityId { replayed, recorded } => write!(
+                f,
+                "replayed entity {:?} was recorded as {:?}",
+                replayed, recorded
+            ),
+        }
+    }
+}
+
+impl From<JournalError> for ReplayError {
+    fn from(err: JournalError) -> Self {
+        ReplayError::Journal(err)
+    }
+}
+
+struct Replay {
+    event_manager: EventManager,
+    task_executor: Executor,
+    entity_allocator: EntityAllocator,
+    /// By the entity ID they were recorded with, which matches the replayed ID as long as
+    /// spawns and destroys replay in the recorded order
+    entities: HashMap<EntityId, Entity>,
+    archetypes: ArchetypeRegistry<Systems>,
+    systems: Systems,
+    replayed_locations: ReplayedLocations,
+    /// First tick at which the replayed physics disagreed with the recorded locations
+    diverged_at: Option<Timestamp>,
+    batches: usize,
+    steps: usize,
+    events: usize,
+}
+
+impl Replay {
+    fn new(archetypes: Archetypes) -> Self {
+        let mut archetypes = ArchetypeRegistry::new(archetypes);
+        Systems::register_components(&mut archetypes);
+
+        let event_manager = EventManager::new();
+        // spawns are pushed from this thread, and must not go through the fallback queue
+        event_manager.bus().register_current_thread();
+        let event_bus = event_manager.bus().clone();
+        let (task_executor, _) = Executor::new(move || event_bus.register_current_thread());
+
+        Self {
+            event_manager,
+            task_executor,
+            entity_allocator: EntityAllocator::new(),
+            entities: HashMap::new(),
+            archetypes,
+            systems: Systems::new(),
+            replayed_locations: ReplayedLocations::default(),
+            diverged_at: None,
+            batches: 0,
+            steps: 0,
+            events: 0,
+        }
+    }
+
+    /// Mirrors the order things happened in when the batch was recorded: entities were spawned
+    /// and destroyed right after the previous batch, then the simulation stepped, then the
+    /// batch was distributed.
+    fn replay_batch(&mut self, batch: &JournalBatch) -> Result<(), ReplayError> {
+        for (entity_id, lifecycle) in batch.events::<EntityLifecycle>()? {
+            match lifecycle {
+                EntityLifecycle::Spawned(params) => {
+                    let entity = self
+                        .archetypes
+                        .spawn(&params, &mut self.systems, &mut self.entity_allocator)
+                        .map_err(ReplayError::Spawn)?;
+
+                    let replayed = entity.entity_id();
+                    self.entities.insert(entity_id, entity);
+                    if replayed != entity_id {
+                        return Err(ReplayError::EntityId {
+                            replayed,
+                            recorded: entity_id,
+                        });
+                    }
+                }
+                EntityLifecycle::Destroyed => {
+                    if let Some(entity) = self.entities.remove(&entity_id) {
+                        self.archetypes.destroy(
+                            entity,
+                            &mut self.systems,
+                            &mut self.entity_allocator,
+                        );
+                    }
+                }
+            }
+        }
+
+        for (_, SimulationStep(timestamp)) in batch.events::<SimulationStep>()? {
+            let mut simulate = self.systems.physics.simulate(timestamp);
+            self.task_executor.execute_blocking(&mut simulate);
+            self.steps += 1;
+        }
+
+        // the replayed systems' own events were recorded along with everything else, so they
+        // are only checked against the recording rather than delivered
+        self.event_manager
+            .distribute(&mut [&mut self.replayed_locations]);
+        self.check_locations(batch)?;
+
+        self.event_manager
+            .dispatch_batch(batch, &mut self.systems.listeners())?;
+
+        self.batches += 1;
+        self.events += batch.len();
+
+        Ok(())
+    }
+
+    /// Locations are compared bitwise rather than within an epsilon: the replay runs the same
+    /// physics on the same inputs, so any difference at all means it diverged, and an epsilon
+    /// would only hide the divergence until it grew past it
+    fn check_locations(&mut self, batch: &JournalBatch) -> Result<(), JournalError> {
+        let recorded = batch
+            .events::<Location>()?
+            .into_iter()
+            .map(|(entity_id, Location(location))| (entity_id, location_bits(&location)))
+            .collect::<HashMap<_, _>>();
+
+        let replayed = std::mem::take(&mut self.replayed_locations.0)
+            .into_iter()
+            .map(|(entity_id, location)| (entity_id, location_bits(&location)))
+            .collect::<HashMap<_, _>>();
+
+        if recorded != replayed {
+            self.diverged(batch.timestamp());
+        }
+
+        Ok(())
+    }
+
+    fn diverged(&mut self, timestamp: Timestamp) {
+        self.diverged_at.get_or_insert(timestamp);
+    }
+}
+
+fn location_bits(location: &Vec3) -> [u32; 3] {
+    [
+        location.x.to_bits(),
+        location.y.to_bits(),
+        location.z.to_bits(),
+    ]
+}
+
+/// Latest location the replayed physics pushed for each entity during the current batch
+#[derive(Default)]
+struct ReplayedLocations(HashMap<EntityId, Vec3>);
+
+impl EventListener for ReplayedLocations {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<Location, Self>();
+    }
+}
+
+impl EventHandler<Location> for ReplayedLocations {
+    fn handle_event(&mut self, entity_id: EntityId, Location(location): &Location) {
+        self.0.insert(entity_id, *location);
+    }
+}
+
+struct Systems {
+    physics: sim_physics::System,
+}
+
+impl Systems {
+    fn new() -> Self {
+        Self {
+            physics: sim_physics::System::new(),
+        }
+    }
+
+    fn register_components(archetypes: &mut ArchetypeRegistry<Self>) {
+        archetypes.register(
+            ComponentKind::Physics,
+            |systems, entity_id, _, params| {
+                systems
+                    .physics
+                    .create_component(entity_id, params.transform, params.velocity);
+            },
+            |systems, entity_id| systems.physics.destroy_component(entity_id),
+        );
+    }
+
+    fn listeners(&mut self) -> [&mut dyn EventListener; 1] {
+        [&mut self.physics]
+    }
+}
@@ -1,46 +1,150 @@
-use std::num::Wrapping;
+mod save;
 
-use component::Component;
-use event::{self, EventListener, EventManager};
-use system::{Timestamp, TIMESTEP};
-use task::{run_parallel, Executor};
+use std::{
+    io,
+    net::ToSocketAddrs,
+    num::Wrapping,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+};
 
-use crate::entity::Entity;
+use archetype::{ArchetypeRegistry, Archetypes, ComponentKind, Entity, SpawnError, SpawnParams};
+use component::SimulationStep;
+use entity::{EntityAllocator, EntityId};
+use event::{push_event, EventListener, EventManager, JournalError, JournalWriter};
+use level::Level;
+use network_utils::SpawnPacket;
+use system::{ScheduledSystem, Scheduler, Stage, Tick, Timestamp, STEPS_PER_SECOND, TIMESTEP};
+use task::Executor;
 
-mod entity;
+pub use save::SaveError;
+
+/// Steps between checkpoint saves
+const CHECKPOINT_INTERVAL: u32 = 60 * STEPS_PER_SECOND as u32;
+
+/// Steps captured by profile()
+const PROFILE_STEPS: u32 = 5 * STEPS_PER_SECOND as u32;
+
+/// Runs on the server's thread between simulation steps, e.g. to spawn or despawn entities
+/// while the server runs
+pub type Command = Box<dyn FnOnce(&mut Server) + Send>;
 
 pub struct Server {
     event_manager: EventManager,
     task_executor: Executor,
+    scheduler: Scheduler,
     last_update: std::time::Instant,
     timestamp: Timestamp,
+    entity_allocator: EntityAllocator,
     entities: Vec<Entity>,
+    archetypes: ArchetypeRegistry<Systems>,
+    checkpoint_path: Option<PathBuf>,
+    /// Where to save the profile being captured, and the steps left to capture
+    profile: Option<(PathBuf, u32)>,
+    commands: Receiver<Command>,
+    command_sender: Sender<Command>,
+    running: bool,
     systems: Systems,
 }
 
 impl Server {
-    pub fn new() -> Self {
+    pub fn new(archetypes: Archetypes) -> Self {
+        Self::with_systems(archetypes, Systems::new())
+    }
+
+    /// Listens on `addr` instead of the address clients connect to, e.g. port 0 so that several
+    /// servers can run in one process
+    pub fn bind(archetypes: Archetypes, addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self::with_systems(archetypes, Systems::bind(addr)?))
+    }
+
+    fn with_systems(archetypes: Archetypes, mut systems: Systems) -> Self {
+        let mut archetypes = ArchetypeRegistry::new(archetypes);
+        Systems::register_components(&mut archetypes);
+
         let event_manager = EventManager::new();
-        let (task_executor, _) = Executor::new(|| unsafe { event::add_event_sender() });
+        // spawns and simulation steps are pushed from this thread, and must not go through the
+        // fallback queue, which every world in the process drains
+        event_manager.bus().register_current_thread();
+        let event_bus = event_manager.bus().clone();
+        let (task_executor, _) = Executor::new(move || event_bus.register_current_thread());
+        let (command_sender, commands) = mpsc::channel();
 
         Self {
             event_manager,
             task_executor,
+            scheduler: Scheduler::new(&systems.scheduled()),
             last_update: std::time::Instant::now(),
             timestamp: Wrapping(0),
+            entity_allocator: EntityAllocator::new(),
             entities: Vec::new(),
-            systems: Systems::new(),
+            archetypes,
+            checkpoint_path: None,
+            profile: None,
+            commands,
+            command_sender,
+            running: false,
+            systems,
         }
     }
 
+    /// Periodically saves to `path` while running
+    pub fn set_checkpoint_path(&mut self, path: PathBuf) {
+        self.checkpoint_path = Some(path);
+    }
+
+    /// See EventManager::set_deterministic()
+    pub fn set_deterministic_events(&mut self, deterministic: bool) {
+        self.event_manager.set_deterministic(deterministic);
+    }
+
+    /// Records every event to a journal at `path`, for replaying the session offline
+    pub fn record_journal(&mut self, path: &Path) -> Result<(), JournalError> {
+        let journal = JournalWriter::create(path)?;
+        self.event_manager.set_journal(Some(journal));
+        Ok(())
+    }
+
+    /// Profiles the executor's tasks over the next PROFILE_STEPS steps, then saves a Chrome
+    /// trace to `path`
+    pub fn profile(&mut self, path: PathBuf) {
+        self.task_executor.start_profiling();
+        self.profile = Some((path, PROFILE_STEPS));
+    }
+
+    /// Describes which systems run concurrently in each stage, and why the others wait
+    pub fn dump_schedule(&mut self) -> String {
+        self.scheduler.dump(&self.systems.scheduled())
+    }
+
+    /// Queues commands for run() from any thread
+    pub fn commands(&self) -> Sender<Command> {
+        self.command_sender.clone()
+    }
+
+    /// Makes run() return once the current step is done, e.g. from a command
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
     pub fn run(mut self) {
-        self.load_level();
         self.last_update = std::time::Instant::now();
+        self.running = true;
+
+        while self.running {
+            while let Ok(command) = self.commands.try_recv() {
+                command(&mut self);
+            }
 
-        loop {
             self.simulate();
             self.distribute_events();
-            std::thread::sleep(std::time::Duration::from_millis(1));
+
+            // packets are received as they arrive until the next step is due
+            let mut next_step = self
+                .systems
+                .sim_network_server
+                .poll_socket_until(self.last_update + TIMESTEP);
+            self.task_executor.execute_blocking(&mut next_step);
         }
 
         self.shutdown();
@@ -51,37 +155,135 @@ impl Server {
         while time_now.duration_since(self.last_update) > TIMESTEP {
             self.last_update += TIMESTEP;
 
-            let mut frame_task = async {
-                self.systems
-                    .sim_network_server
-                    .simulate(self.timestamp)
-                    .await;
+            push_event(EntityId::NONE, SimulationStep(self.timestamp));
+
+            {
+                let tick = Tick {
+                    timestamp: self.timestamp,
+                    delta_time: 0.0,
+                    frame_interp: 0.0,
+                };
+                let mut systems = self.systems.scheduled();
+                let mut simulate = self.scheduler.run(Stage::Simulate, &tick, &mut systems);
+                self.task_executor.execute_blocking(&mut simulate);
+            }
+
+            self.timestamp += Wrapping(1);
 
-                let mut physics = self.systems.sim_physics.simulate(self.timestamp);
+            // physics has just consumed any pending correction, so nothing is lost
+            if self.timestamp.0.is_multiple_of(CHECKPOINT_INTERVAL) {
+                self.checkpoint();
+            }
 
-                run_parallel([&mut physics]).await;
+            self.profile_step();
+        }
+    }
 
-                self.timestamp += Wrapping(1);
-            };
+    fn profile_step(&mut self) {
+        let (path, steps) = match &mut self.profile {
+            Some(profile) => profile,
+            None => return,
+        };
+
+        *steps -= 1;
+        if *steps > 0 {
+            return;
+        }
 
-            self.task_executor.execute_blocking(&mut frame_task);
+        let profile = self.task_executor.stop_profiling();
+        match profile.save(path) {
+            Ok(()) => println!("saved {} task spans to {}", profile.len(), path.display()),
+            Err(err) => println!("failed to save profile to {}: {}", path.display(), err),
+        }
+        self.profile = None;
+    }
+
+    fn checkpoint(&self) {
+        if let Some(path) = &self.checkpoint_path {
+            if let Err(err) = self.save(path) {
+                println!("checkpoint failed: {}", err);
+            }
         }
     }
 
     fn distribute_events(&mut self) {
-        self.event_manager.distribute(|entity_id, component| {
-            self.systems.receive_event(entity_id, component);
-        });
+        self.event_manager.set_timestamp(self.timestamp);
+        self.event_manager.distribute(&mut self.systems.listeners());
+    }
+
+    pub fn load_level(&mut self, level: &Level) {
+        // entities without a network ID mustn't be allocated one that a later entity specifies
+        for network_id in level.entities.iter().filter_map(|desc| desc.network_id) {
+            self.systems
+                .sim_network_server
+                .reserve_network_id(network_id);
+        }
+
+        for desc in &level.entities {
+            self.spawn(desc.spawn_params())
+                .expect("level was validated against the archetypes");
+        }
     }
 
-    fn load_level(&mut self) {
-        self.entities
-            .push(entity::static_mesh(0, &mut self.systems));
+    /// Spawns an entity, replicating it to clients if its archetype is networked. A network ID
+    /// is allocated if `params` doesn't specify one, and fails if it specifies one in use.
+    pub fn spawn(&mut self, mut params: SpawnParams) -> Result<EntityId, SpawnError> {
+        let networked = self
+            .archetypes
+            .archetypes()
+            .get(&params.archetype)
+            .is_some_and(|archetype| archetype.has_component(ComponentKind::Network));
+
+        if networked {
+            match params.network_id {
+                Some(network_id) => {
+                    if self
+                        .systems
+                        .sim_network_server
+                        .is_network_id_used(network_id)
+                    {
+                        return Err(SpawnError::DuplicateNetworkId(network_id));
+                    }
+                }
+                None => {
+                    let network_id = self.systems.sim_network_server.allocate_network_id();
+                    params.network_id = Some(network_id);
+                }
+            }
+        }
+
+        let entity =
+            self.archetypes
+                .spawn(&params, &mut self.systems, &mut self.entity_allocator)?;
+
+        let entity_id = entity.entity_id();
+        self.entities.push(entity);
+
+        Ok(entity_id)
+    }
+
+    /// Returns false if the entity doesn't exist
+    pub fn despawn(&mut self, entity_id: EntityId) -> bool {
+        let index = match self
+            .entities
+            .iter()
+            .position(|entity| entity.entity_id() == entity_id)
+        {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let entity = self.entities.swap_remove(index);
+        self.archetypes
+            .destroy(entity, &mut self.systems, &mut self.entity_allocator);
+
+        true
     }
 
     fn shutdown(&mut self) {
         for entity in self.entities.drain(..) {
-            entity.destroy(&mut self.systems);
+            self.archetypes
+                .destroy(entity, &mut self.systems, &mut self.entity_allocator);
         }
     }
 }
@@ -91,6 +293,12 @@ pub struct Systems {
     sim_physics: sim_physics::System,
 }
 
+impl Default for Systems {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Systems {
     pub fn new() -> Self {
         Self {
@@ -98,11 +306,56 @@ impl Systems {
             sim_physics: sim_physics::System::new(),
         }
     }
+
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            sim_network_server: sim_network_server::System::bind(addr)?,
+            sim_physics: sim_physics::System::new(),
+        })
+    }
+
+    pub fn register_components(archetypes: &mut ArchetypeRegistry<Self>) {
+        archetypes.register(
+            ComponentKind::Network,
+            |systems, entity_id, _, params| {
+                let spawn = SpawnPacket {
+                    network_id: params.network_id.unwrap(),
+                    archetype: params.archetype.clone(),
+                    mesh: params.mesh.clone(),
+                    location: params.transform.location,
+                    rotation: params.transform.rotation,
+                    scale: params.transform.scale,
+                    velocity: params.velocity,
+                };
+                systems
+                    .sim_network_server
+                    .create_static_mesh_component(entity_id, spawn);
+            },
+            |systems, entity_id| {
+                systems
+                    .sim_network_server
+                    .destroy_static_mesh_component(entity_id);
+            },
+        );
+
+        archetypes.register(
+            ComponentKind::Physics,
+            |systems, entity_id, _, params| {
+                systems
+                    .sim_physics
+                    .create_component(entity_id, params.transform, params.velocity);
+            },
+            |systems, entity_id| systems.sim_physics.destroy_component(entity_id),
+        );
+    }
 }
 
-impl EventListener for Systems {
-    fn receive_event(&mut self, entity_id: ::entity::EntityId, component: &Component) {
-        self.sim_network_server.receive_event(entity_id, component);
-        self.sim_physics.receive_event(entity_id, component);
+impl Systems {
+    pub fn listeners(&mut self) -> [&mut dyn EventListener; 2] {
+        [&mut self.sim_network_server, &mut self.sim_physics]
+    }
+
+    pub fn scheduled(&mut self) -> [&mut dyn ScheduledSystem; 2] {
+        [&mut self.sim_network_server, &mut self.sim_physics]
     }
 }
@@ -0,0 +1,233 @@
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use archetype::{ComponentKind, SpawnError, SpawnParams};
+use component::Transform;
+use nalgebra_glm::Vec3;
+use network_utils::NetworkId;
+use serde::{Deserialize, Serialize};
+use sim_physics::ObjectState;
+use system::Timestamp;
+
+use crate::Server;
+
+const MAGIC: [u8; 4] = *b"SGSV";
+
+/// Bump whenever SaveData or anything it contains changes layout, including
+/// NETWORK_SNAPSHOTS_LEN
+const VERSION: u32 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + std::mem::size_of::<u32>();
+
+#[derive(Serialize, Deserialize)]
+struct SaveData {
+    timestamp: Timestamp,
+    physics_timestamp: Timestamp,
+    next_network_id: NetworkId,
+    entities: Vec<SavedEntity>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedEntity {
+    archetype: String,
+    mesh: Option<String>,
+    network_id: Option<NetworkId>,
+    physics: Option<ObjectState>,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io {
+        path: PathBuf,
+        err: std::io::Error,
+    },
+    /// Not a save file, or a corrupt one
+    Format {
+        path: PathBuf,
+        message: String,
+    },
+    Version {
+        path: PathBuf,
+        version: u32,
+    },
+    Spawn {
+        path: PathBuf,
+        err: SpawnError,
+    },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io { path, err } => write!(f, "{}: {}", path.display(), err),
+            SaveError::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+            SaveError::Version { path, version } => write!(
+                f,
+                "{}: save version {} is not supported (expected {})",
+                path.display(),
+                version,
+                VERSION
+            ),
+            SaveError::Spawn { path, err } => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl Server {
+    /// Writes the full simulation state. The file is written next to `path` and renamed over
+    /// it, so an interrupted save never leaves a truncated file behind.
+    pub fn save(&self, path: &Path) -> Result<(), SaveError> {
+        let sim_physics = &self.systems.sim_physics;
+
+        let entities = self
+            .entities
+            .iter()
+            .map(|entity| SavedEntity {
+                archetype: entity.archetype().to_string(),
+                mesh: entity.mesh().map(str::to_string),
+                network_id: entity.network_id(),
+                physics: sim_physics.object_state(entity.entity_id()),
+            })
+            .collect();
+
+        let data = SaveData {
+            timestamp: self.timestamp,
+            physics_timestamp: sim_physics.current_timestamp(),
+            next_network_id: self.systems.sim_network_server.next_network_id(),
+            entities,
+        };
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, &data).map_err(|err| SaveError::Format {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        })?;
+
+        let io_err = |err| SaveError::Io {
+            path: path.to_path_buf(),
+            err,
+        };
+
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, bytes).map_err(io_err)?;
+        fs::rename(&temp_path, path).map_err(io_err)
+    }
+
+    /// Restores state written by save(). Must be called on a server with no entities, before
+    /// run(). The whole save is validated before anything is restored, so the server is left
+    /// untouched on error.
+    pub fn restore(&mut self, path: &Path) -> Result<(), SaveError> {
+        assert!(
+            self.entities.is_empty(),
+            "restoring into a populated server"
+        );
+
+        let format_err = |message: String| SaveError::Format {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let bytes = fs::read(path).map_err(|err| SaveError::Io {
+            path: path.to_path_buf(),
+            err,
+        })?;
+
+        if bytes.len() < HEADER_LEN || bytes[..MAGIC.len()] != MAGIC {
+            return Err(format_err("not a save file".to_string()));
+        }
+
+        let version = u32::from_le_bytes(bytes[MAGIC.len()..HEADER_LEN].try_into().unwrap());
+        if version != VERSION {
+            return Err(SaveError::Version {
+                path: path.to_path_buf(),
+                version,
+            });
+        }
+
+        let data: SaveData = bincode::deserialize(&bytes[HEADER_LEN..])
+            .map_err(|err| format_err(err.to_string()))?;
+
+        self.validate(&data, path)?;
+
+        self.timestamp = data.timestamp;
+        self.systems
+            .sim_physics
+            .restore_timestamp(data.physics_timestamp);
+        self.systems
+            .sim_network_server
+            .set_next_network_id(data.next_network_id);
+
+        for saved in data.entities {
+            let (transform, velocity) = saved
+                .physics
+                .as_ref()
+                .and_then(|physics| physics.transform_and_velocity(data.physics_timestamp))
+                .unwrap_or((Transform::default(), Vec3::zeros()));
+
+            let params = SpawnParams {
+                archetype: saved.archetype,
+                transform,
+                velocity,
+                network_id: saved.network_id,
+                mesh: saved.mesh,
+            };
+
+            let entity_id = self.spawn(params).expect("save was validated");
+
+            if let Some(physics) = saved.physics {
+                let restored = self
+                    .systems
+                    .sim_physics
+                    .restore_object_state(entity_id, physics);
+                assert!(restored, "save was validated");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that every saved entity can be spawned and its state restored
+    fn validate(&self, data: &SaveData, path: &Path) -> Result<(), SaveError> {
+        let spawn_err = |err| SaveError::Spawn {
+            path: path.to_path_buf(),
+            err,
+        };
+
+        let mut network_ids = HashSet::new();
+        for (entity_index, saved) in data.entities.iter().enumerate() {
+            let archetype = self
+                .archetypes
+                .archetypes()
+                .get(&saved.archetype)
+                .ok_or_else(|| spawn_err(SpawnError::UnknownArchetype(saved.archetype.clone())))?;
+
+            if archetype.has_component(ComponentKind::Network) {
+                if let Some(network_id) = saved.network_id {
+                    if !network_ids.insert(network_id) {
+                        return Err(spawn_err(SpawnError::DuplicateNetworkId(network_id)));
+                    }
+                }
+            }
+
+            if let Some(physics) = &saved.physics {
+                if !archetype.has_component(ComponentKind::Physics) || !physics.is_complete() {
+                    return Err(SaveError::Format {
+                        path: path.to_path_buf(),
+                        message: format!(
+                            "entity {}: physics state doesn't match its archetype",
+                            entity_index
+                        ),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
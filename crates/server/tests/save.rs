@@ -0,0 +1,109 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use archetype::{Archetypes, SpawnParams};
+use component::Transform;
+use nalgebra_glm::vec3;
+use server::{SaveError, Server};
+
+const ARCHETYPES: &str = r#"{
+    "player": [network, physics, static_mesh(mesh: "suzanne")],
+    "monster": [network, physics, static_mesh(mesh: "cube")],
+}"#;
+
+fn archetypes(source: &str) -> Archetypes {
+    Archetypes::parse(source, Path::new("archetypes.ron")).unwrap()
+}
+
+fn server(source: &str) -> Server {
+    Server::bind(archetypes(source), "127.0.0.1:0").unwrap()
+}
+
+fn save_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("server-save-{}-{}.sav", name, std::process::id()))
+}
+
+fn spawn(server: &mut Server, archetype: &str, index: u16) {
+    let params = SpawnParams {
+        archetype: archetype.to_string(),
+        transform: Transform::from_location(vec3(index as f32 * 3.0, 0.0, 0.0)),
+        velocity: vec3(1.0, 0.5 * index as f32, 0.0),
+        network_id: None,
+        mesh: None,
+    };
+    server.spawn(params).unwrap();
+}
+
+#[test]
+fn restores_what_was_saved() {
+    let saved = save_path("saved");
+    let resaved = save_path("resaved");
+
+    // run for a while first, so every object's snapshot ring holds a history of moves
+    let (commands_sender, commands) = mpsc::channel();
+    let running = thread::spawn(move || {
+        let mut server = server(ARCHETYPES);
+        for index in 0..4 {
+            spawn(&mut server, "player", index);
+        }
+        commands_sender.send(server.commands()).unwrap();
+        server.run();
+    });
+
+    let commands = commands.recv().unwrap();
+    thread::sleep(Duration::from_millis(300));
+    let path = saved.clone();
+    commands
+        .send(Box::new(move |server: &mut Server| {
+            server.save(&path).unwrap();
+            server.stop();
+        }))
+        .unwrap();
+    running.join().unwrap();
+
+    let mut restored = server(ARCHETYPES);
+    restored.restore(&saved).unwrap();
+    restored.save(&resaved).unwrap();
+
+    // saves hold the timestamps, next_network_id, and each entity's archetype, network ID and
+    // full snapshot ring, so equal saves mean all of those were restored as they were
+    assert_eq!(fs::read(&saved).unwrap(), fs::read(&resaved).unwrap());
+
+    fs::remove_file(&saved).unwrap();
+    fs::remove_file(&resaved).unwrap();
+}
+
+#[test]
+fn restores_nothing_from_an_invalid_save() {
+    let saved = save_path("invalid");
+    let untouched = save_path("untouched");
+    let empty = save_path("empty");
+
+    let mut original = server(ARCHETYPES);
+    spawn(&mut original, "player", 0);
+    spawn(&mut original, "player", 1);
+    spawn(&mut original, "monster", 2);
+    original.save(&saved).unwrap();
+
+    // the last entity's archetype is unknown here, so it fails after the others would spawn
+    let players_only = r#"{ "player": [network, physics, static_mesh(mesh: "suzanne")] }"#;
+    let mut restored = server(players_only);
+    match restored.restore(&saved) {
+        Err(SaveError::Spawn { .. }) => {}
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(()) => panic!("unknown archetype was restored"),
+    }
+
+    restored.save(&untouched).unwrap();
+    server(players_only).save(&empty).unwrap();
+    assert_eq!(fs::read(&untouched).unwrap(), fs::read(&empty).unwrap());
+
+    for path in [saved, untouched, empty] {
+        fs::remove_file(path).unwrap();
+    }
+}
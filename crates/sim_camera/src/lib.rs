@@ -1,7 +1,8 @@
-use component::Component;
+use component::{RenderTransform, Transform};
 use entity::EntityId;
-use event::{push_event, EventListener};
-use nalgebra_glm::{vec3, Vec3};
+use event::{push_event, EventHandler, EventListener, Subscriptions};
+use nalgebra_glm::Vec3;
+use system::{Declarations, ScheduledSystem, Stage, SystemFuture, Tick};
 
 pub struct System {
     entity_id: Option<EntityId>,
@@ -14,6 +15,12 @@ struct Target {
     location: Vec3,
 }
 
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl System {
     pub fn new() -> Self {
         Self {
@@ -23,10 +30,10 @@ impl System {
         }
     }
 
-    pub fn create_component(&mut self, entity_id: EntityId) {
+    pub fn create_component(&mut self, entity_id: EntityId, location: Vec3) {
         debug_assert!(self.entity_id.is_none());
         self.entity_id = Some(entity_id);
-        self.location = vec3(0.0, 0.0, 5.0);
+        self.location = location;
     }
 
     pub fn destroy_component(&mut self, entity_id: EntityId) {
@@ -41,26 +48,43 @@ impl System {
         })
     }
 
-    pub async fn render(&mut self, delta_time: f32) {
+    pub async fn render(&mut self, _delta_time: f32) {
         if let (Some(entity_id), Some(target)) = (self.entity_id, self.target.as_ref()) {
             self.location = target.location;
 
-            push_event(entity_id, Component::RenderLocation(self.location));
+            push_event(
+                entity_id,
+                RenderTransform(Transform::from_location(self.location)),
+            );
         }
     }
 }
 
+impl ScheduledSystem for System {
+    fn declare(&self, declarations: &mut Declarations) {
+        declarations.stage(Stage::Render).writes("camera");
+    }
+
+    fn run<'a>(&'a mut self, _: Stage, tick: &'a Tick) -> SystemFuture<'a> {
+        Box::pin(self.render(tick.delta_time))
+    }
+}
+
 impl EventListener for System {
-    fn receive_event(&mut self, entity_id: EntityId, component: &Component) {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<RenderTransform, Self>();
+    }
+}
+
+impl EventHandler<RenderTransform> for System {
+    fn handle_event(&mut self, entity_id: EntityId, RenderTransform(transform): &RenderTransform) {
         let target = match self.target.as_mut() {
             Some(target) => target,
             None => return,
         };
 
         if target.entity_id == entity_id {
-            if let Component::RenderLocation(location) = component {
-                target.location = *location;
-            }
+            target.location = transform.location;
         }
     }
 }
@@ -0,0 +1,242 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
+
+use component::{RenderTransform, Transform};
+use data::ComponentArray;
+use entity::EntityId;
+use event::{push_event, EventHandler, EventListener, Subscriptions};
+use system::{Declarations, ScheduledSystem, Stage, SystemFuture, Tick};
+
+/// An entity attached to a parent
+struct Node {
+    parent: EntityId,
+    local: Transform,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AttachError {
+    /// The entity or parent has no hierarchy component
+    NoHierarchy(EntityId),
+    /// The parent is the entity itself or one of its descendants
+    Cycle {
+        entity_id: EntityId,
+        parent: EntityId,
+    },
+}
+
+impl fmt::Display for AttachError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttachError::NoHierarchy(entity_id) => {
+                write!(f, "{:?} has no hierarchy component", entity_id)
+            }
+            AttachError::Cycle { entity_id, parent } => write!(
+                f,
+                "attaching {:?} to {:?} would create a cycle",
+                entity_id, parent
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AttachError {}
+
+/// Attaches entities to parents. Each frame, children receive a RenderTransform composed from
+/// their parent's RenderTransform and their local transform. Parents' render transforms are
+/// already interpolated, so children follow them smoothly without interpolating themselves.
+///
+/// Only entities with a hierarchy component can be attached or have children. Children are
+/// positioned exclusively by the hierarchy, so they shouldn't have physics.
+pub struct System {
+    /// Latest render transform of every entity with a hierarchy component, its spawn transform
+    /// until first rendered, so parents without physics still place their children
+    transforms: ComponentArray<Transform>,
+    nodes: ComponentArray<Node>,
+    children: HashMap<EntityId, Vec<EntityId>>,
+    /// Nodes ordered so that every parent is propagated before its children
+    order: Vec<EntityId>,
+    order_dirty: bool,
+}
+
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl System {
+    pub fn new() -> Self {
+        Self {
+            transforms: ComponentArray::new(),
+            nodes: ComponentArray::new(),
+            children: HashMap::new(),
+            order: Vec::new(),
+            order_dirty: false,
+        }
+    }
+
+    pub fn create_component(&mut self, entity_id: EntityId, transform: Transform) {
+        self.transforms.push(entity_id, transform);
+    }
+
+    /// Detaches the entity, and detaches its children from it. They stay where they last were.
+    pub fn destroy_component(&mut self, entity_id: EntityId) {
+        self.detach(entity_id);
+
+        if let Some(children) = self.children.remove(&entity_id) {
+            for child in children {
+                self.nodes.remove(child);
+            }
+            self.order_dirty = true;
+        }
+
+        self.transforms.remove(entity_id);
+    }
+
+    /// Attaches the entity to `parent`, detaching it from any previous parent. Nothing changes
+    /// on error.
+    pub fn attach(
+        &mut self,
+        entity_id: EntityId,
+        parent: EntityId,
+        local: Transform,
+    ) -> Result<(), AttachError> {
+        for entity_id in [entity_id, parent] {
+            if !self.transforms.contains_entity(entity_id) {
+                return Err(AttachError::NoHierarchy(entity_id));
+            }
+        }
+        if self.is_ancestor(entity_id, parent) {
+            return Err(AttachError::Cycle { entity_id, parent });
+        }
+
+        self.detach(entity_id);
+
+        self.nodes.push(entity_id, Node { parent, local });
+        self.children.entry(parent).or_default().push(entity_id);
+
+        self.order_dirty = true;
+        Ok(())
+    }
+
+    /// Detaches the entity from its parent. Its own children stay attached to it.
+    pub fn detach(&mut self, entity_id: EntityId) {
+        let node = match self.nodes.remove(entity_id) {
+            Some(node) => node,
+            None => return,
+        };
+
+        let children = self.children.get_mut(&node.parent).unwrap();
+        children.retain(|child| *child != entity_id);
+        if children.is_empty() {
+            self.children.remove(&node.parent);
+        }
+
+        self.order_dirty = true;
+    }
+
+    /// Everything attached to the entity, directly or not, deepest first. These should usually
+    /// be destroyed along with it.
+    pub fn descendants(&self, entity_id: EntityId) -> Vec<EntityId> {
+        let mut descendants = Vec::new();
+        self.collect_descendants(entity_id, &mut descendants);
+        descendants
+    }
+
+    pub fn set_local_transform(&mut self, entity_id: EntityId, local: Transform) {
+        if let Some(node) = self.nodes.get_mut(entity_id) {
+            node.data.local = local;
+        }
+    }
+
+    pub async fn propagate(&mut self) {
+        if self.order_dirty {
+            self.rebuild_order();
+        }
+
+        for entity_id in &self.order {
+            let node = &self.nodes[*entity_id].data;
+            let transform = self.transforms[node.parent].data.compose(&node.local);
+
+            // grandchildren are later in the order and read this during the same pass
+            self.transforms[*entity_id].data = transform;
+
+            push_event(*entity_id, RenderTransform(transform));
+        }
+    }
+
+    fn is_ancestor(&self, ancestor: EntityId, mut entity_id: EntityId) -> bool {
+        loop {
+            if entity_id == ancestor {
+                return true;
+            }
+
+            entity_id = match self.nodes.get(entity_id) {
+                Some(node) => node.data.parent,
+                None => return false,
+            };
+        }
+    }
+
+    /// Post-order, so every entity comes after its own descendants
+    fn collect_descendants(&self, entity_id: EntityId, descendants: &mut Vec<EntityId>) {
+        if let Some(children) = self.children.get(&entity_id) {
+            for child in children {
+                self.collect_descendants(*child, descendants);
+                descendants.push(*child);
+            }
+        }
+    }
+
+    /// Breadth-first from the roots, i.e. parents which aren't nodes themselves
+    fn rebuild_order(&mut self) {
+        self.order.clear();
+
+        let mut queue = self
+            .children
+            .keys()
+            .filter(|entity_id| !self.nodes.contains_entity(**entity_id))
+            .copied()
+            .collect::<VecDeque<_>>();
+
+        while let Some(entity_id) = queue.pop_front() {
+            if let Some(children) = self.children.get(&entity_id) {
+                self.order.extend_from_slice(children);
+                queue.extend(children);
+            }
+        }
+
+        self.order_dirty = false;
+    }
+}
+
+impl ScheduledSystem for System {
+    fn declare(&self, declarations: &mut Declarations) {
+        declarations.stage(Stage::Propagate).writes("hierarchy");
+    }
+
+    fn run<'a>(&'a mut self, _: Stage, _: &'a Tick) -> SystemFuture<'a> {
+        Box::pin(self.propagate())
+    }
+}
+
+impl EventListener for System {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<RenderTransform, Self>();
+    }
+}
+
+impl EventHandler<RenderTransform> for System {
+    fn handle_event(&mut self, entity_id: EntityId, RenderTransform(transform): &RenderTransform) {
+        // nodes set their own transform during propagation
+        if self.nodes.contains_entity(entity_id) {
+            return;
+        }
+
+        if let Some(component) = self.transforms.get_mut(entity_id) {
+            component.data = *transform;
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use component::{RenderTransform, Transform};
+use entity::{EntityAllocator, EntityId};
+use event::{EventHandler, EventListener, EventManager, Subscriptions};
+use nalgebra_glm::{vec3, Vec3};
+use sim_hierarchy::{AttachError, System};
+use task::Executor;
+
+#[derive(Default)]
+struct Listener {
+    received: Vec<(EntityId, Vec3)>,
+}
+
+impl EventListener for Listener {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<RenderTransform, Self>();
+    }
+}
+
+impl EventHandler<RenderTransform> for Listener {
+    fn handle_event(&mut self, entity_id: EntityId, RenderTransform(transform): &RenderTransform) {
+        self.received.push((entity_id, transform.location));
+    }
+}
+
+struct World {
+    event_manager: EventManager,
+    executor: Executor,
+    hierarchy: System,
+    allocator: EntityAllocator,
+}
+
+impl World {
+    fn new() -> Self {
+        let event_manager = EventManager::new();
+        let event_bus = event_manager.bus().clone();
+        let (executor, _) = Executor::inline(move || event_bus.register_current_thread());
+
+        Self {
+            event_manager,
+            executor,
+            hierarchy: System::new(),
+            allocator: EntityAllocator::new(),
+        }
+    }
+
+    fn spawn(&mut self, location: Vec3) -> EntityId {
+        let entity_id = self.allocator.allocate();
+        self.hierarchy
+            .create_component(entity_id, Transform::from_location(location));
+        entity_id
+    }
+
+    /// Locations pushed by one propagation, in the order they were pushed
+    fn propagate(&mut self) -> Vec<(EntityId, Vec3)> {
+        let hierarchy = &mut self.hierarchy;
+        self.executor
+            .execute_blocking(&mut async { hierarchy.propagate().await });
+
+        let mut listener = Listener::default();
+        self.event_manager.distribute(&mut [&mut listener]);
+        listener.received
+    }
+}
+
+fn offset(location: Vec3) -> Transform {
+    Transform::from_location(location)
+}
+
+#[test]
+fn propagates_parents_before_children() {
+    let mut world = World::new();
+    let root = world.spawn(vec3(1.0, 0.0, 0.0));
+    let child = world.spawn(Vec3::zeros());
+    let grandchild = world.spawn(Vec3::zeros());
+
+    // attached bottom up, so the order can't just follow attachment
+    world
+        .hierarchy
+        .attach(grandchild, child, offset(vec3(0.0, 0.0, 3.0)))
+        .unwrap();
+    world
+        .hierarchy
+        .attach(child, root, offset(vec3(0.0, 2.0, 0.0)))
+        .unwrap();
+
+    assert_eq!(
+        world.propagate(),
+        vec![
+            (child, vec3(1.0, 2.0, 0.0)),
+            (grandchild, vec3(1.0, 2.0, 3.0)),
+        ]
+    );
+
+    // children follow the parent's latest render transform
+    world
+        .hierarchy
+        .handle_event(root, &RenderTransform(offset(vec3(-1.0, 0.0, 0.0))));
+    assert_eq!(
+        world.propagate(),
+        vec![
+            (child, vec3(-1.0, 2.0, 0.0)),
+            (grandchild, vec3(-1.0, 2.0, 3.0)),
+        ]
+    );
+}
+
+#[test]
+fn rejects_cycles_and_entities_without_the_component() {
+    let mut world = World::new();
+    let root = world.spawn(Vec3::zeros());
+    let child = world.spawn(Vec3::zeros());
+    let grandchild = world.spawn(Vec3::zeros());
+    let unrelated = world.allocator.allocate();
+
+    world
+        .hierarchy
+        .attach(child, root, offset(Vec3::x()))
+        .unwrap();
+    world
+        .hierarchy
+        .attach(grandchild, child, offset(Vec3::y()))
+        .unwrap();
+    let before = world.propagate();
+
+    assert_eq!(
+        world.hierarchy.attach(root, grandchild, offset(Vec3::z())),
+        Err(AttachError::Cycle {
+            entity_id: root,
+            parent: grandchild
+        })
+    );
+    assert_eq!(
+        world.hierarchy.attach(child, child, offset(Vec3::z())),
+        Err(AttachError::Cycle {
+            entity_id: child,
+            parent: child
+        })
+    );
+    assert_eq!(
+        world.hierarchy.attach(child, unrelated, offset(Vec3::z())),
+        Err(AttachError::NoHierarchy(unrelated))
+    );
+    assert_eq!(
+        world.hierarchy.attach(unrelated, root, offset(Vec3::z())),
+        Err(AttachError::NoHierarchy(unrelated))
+    );
+
+    // nothing changed
+    assert_eq!(world.propagate(), before);
+    assert_eq!(world.hierarchy.descendants(root), vec![grandchild, child]);
+}
+
+#[test]
+fn destroying_a_parent_detaches_its_children() {
+    let mut world = World::new();
+    let root = world.spawn(Vec3::zeros());
+    let child = world.spawn(Vec3::zeros());
+    let grandchild = world.spawn(Vec3::zeros());
+
+    world
+        .hierarchy
+        .attach(child, root, offset(Vec3::x()))
+        .unwrap();
+    world
+        .hierarchy
+        .attach(grandchild, child, offset(Vec3::y()))
+        .unwrap();
+    assert_eq!(world.propagate().len(), 2);
+
+    world.hierarchy.destroy_component(child);
+
+    assert!(world.hierarchy.descendants(root).is_empty());
+    assert!(world.hierarchy.descendants(child).is_empty());
+    // the grandchild is attached to nothing now, so nothing is propagated
+    assert!(world.propagate().is_empty());
+
+    // and can be attached elsewhere
+    world
+        .hierarchy
+        .attach(grandchild, root, offset(Vec3::z()))
+        .unwrap();
+    assert_eq!(world.propagate(), vec![(grandchild, Vec3::z())]);
+}
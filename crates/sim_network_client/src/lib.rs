@@ -1,12 +1,17 @@
 use std::{collections::HashMap, net::SocketAddr};
 
-use component::Component;
+use component::{
+    InputAcceleration, NetStaticMeshLocation, NetStaticMeshRotation, NetStaticMeshVelocity,
+};
 use entity::EntityId;
-use event::{push_event, EventListener};
+use event::{push_event, EventHandler, EventListener, Subscriptions};
 use laminar::{Packet as LaminarPacket, Socket, SocketEvent};
 use nalgebra_glm::{Vec2, Vec3};
-use network_utils::{InputPacket, NetworkId, Packet, PingPacket, StaticMeshPacket, VelocityPacket};
-use system::Timestamp;
+use network_utils::{
+    DespawnPacket, InputPacket, NetworkId, Packet, PingPacket, SpawnPacket, StaticMeshPacket,
+    VelocityPacket,
+};
+use system::{Declarations, ScheduledSystem, Stage, SystemFuture, Tick, Timestamp};
 
 const SERVER_IP: &str = "127.0.0.1:12351";
 
@@ -17,6 +22,14 @@ pub struct System {
     input: Vec2,
     last_sent_input: Vec2,
     static_mesh_components: HashMap<NetworkId, StaticMeshComponent>,
+    replication: Vec<Replication>,
+}
+
+/// Entity lifetime changes sent by the server. These can't be applied from within the network
+/// system since spawning touches every system, so they're queued until `take_replication`.
+pub enum Replication {
+    Spawn(SpawnPacket),
+    Despawn(EntityId),
 }
 
 struct StaticMeshComponent {
@@ -24,6 +37,12 @@ struct StaticMeshComponent {
     location: Vec3,
 }
 
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl System {
     pub fn new() -> Self {
         let mut socket = Socket::bind_any().unwrap();
@@ -45,12 +64,14 @@ impl System {
             input: Vec2::zeros(),
             last_sent_input: Vec2::zeros(),
             static_mesh_components: HashMap::new(),
+            replication: Vec::new(),
         }
     }
 
-    pub fn create_static_mesh_component(&mut self, entity_id: EntityId) {
+    pub fn create_static_mesh_component(&mut self, entity_id: EntityId, network_id: NetworkId) {
+        debug_assert!(!self.static_mesh_components.contains_key(&network_id));
         self.static_mesh_components.insert(
-            0,
+            network_id,
             StaticMeshComponent {
                 entity_id,
                 location: Vec3::zeros(),
@@ -63,24 +84,26 @@ impl System {
             .retain(|_, static_mesh| static_mesh.entity_id != entity_id);
     }
 
+    pub fn take_replication(&mut self) -> Vec<Replication> {
+        std::mem::take(&mut self.replication)
+    }
+
     pub async fn simulate(&mut self, timestamp: Timestamp) {
-        if self.connected {
-            if self.input != self.last_sent_input {
-                self.last_sent_input = self.input;
-
-                let input = Packet::Input(InputPacket {
-                    timestamp,
-                    input: self.input,
-                });
-
-                self.socket
-                    .send(LaminarPacket::reliable_sequenced(
-                        self.server_addr,
-                        input.into(),
-                        None,
-                    ))
-                    .unwrap();
-            }
+        if self.connected && self.input != self.last_sent_input {
+            self.last_sent_input = self.input;
+
+            let input = Packet::Input(InputPacket {
+                timestamp,
+                input: self.input,
+            });
+
+            self.socket
+                .send(LaminarPacket::reliable_sequenced(
+                    self.server_addr,
+                    input.into(),
+                    None,
+                ))
+                .unwrap();
         }
 
         self.socket.manual_poll(std::time::Instant::now());
@@ -107,6 +130,8 @@ impl System {
             Packet::Input(_) => panic!(),
             Packet::StaticMesh(data) => self.handle_static_mesh_packet(data),
             Packet::Velocity(data) => self.handle_velocity_packet(data),
+            Packet::Spawn(data) => self.handle_spawn_packet(data),
+            Packet::Despawn(data) => self.handle_despawn_packet(data),
         };
     }
 
@@ -117,15 +142,16 @@ impl System {
     }
 
     fn handle_static_mesh_packet(&mut self, packet: StaticMeshPacket) {
-        let static_mesh = self
-            .static_mesh_components
-            .get_mut(&packet.network_id)
-            .unwrap();
+        // state may arrive before the entity's spawn packet
+        let static_mesh = match self.static_mesh_components.get_mut(&packet.network_id) {
+            Some(static_mesh) => static_mesh,
+            None => return,
+        };
         static_mesh.location = packet.location;
 
         push_event(
             static_mesh.entity_id,
-            Component::NetStaticMeshLocation {
+            NetStaticMeshLocation {
                 timestamp: packet.timestamp,
                 location: packet.location,
             },
@@ -133,22 +159,61 @@ impl System {
 
         push_event(
             static_mesh.entity_id,
-            Component::NetStaticMeshVelocity {
+            NetStaticMeshRotation {
+                timestamp: packet.timestamp,
+                rotation: packet.rotation.into(),
+            },
+        );
+
+        push_event(
+            static_mesh.entity_id,
+            NetStaticMeshVelocity {
                 timestamp: packet.timestamp,
                 velocity: packet.velocity,
             },
         );
     }
 
+    fn handle_spawn_packet(&mut self, packet: SpawnPacket) {
+        if self.static_mesh_components.contains_key(&packet.network_id)
+            || self.pending_spawn(packet.network_id).is_some()
+        {
+            return;
+        }
+
+        self.replication.push(Replication::Spawn(packet));
+    }
+
+    fn handle_despawn_packet(&mut self, packet: DespawnPacket) {
+        // spawned and despawned before the client applied the spawn
+        if let Some(index) = self.pending_spawn(packet.network_id) {
+            self.replication.remove(index);
+            return;
+        }
+
+        if let Some(static_mesh) = self.static_mesh_components.get(&packet.network_id) {
+            self.replication
+                .push(Replication::Despawn(static_mesh.entity_id));
+        }
+    }
+
+    /// Index of a queued spawn of `network_id`
+    fn pending_spawn(&self, network_id: NetworkId) -> Option<usize> {
+        self.replication.iter().position(|replication| {
+            matches!(replication, Replication::Spawn(spawn) if spawn.network_id == network_id)
+        })
+    }
+
     fn handle_velocity_packet(&mut self, packet: VelocityPacket) {
-        let static_mesh = self
-            .static_mesh_components
-            .get_mut(&packet.network_id)
-            .unwrap();
+        // state may arrive before the entity's spawn packet
+        let static_mesh = match self.static_mesh_components.get_mut(&packet.network_id) {
+            Some(static_mesh) => static_mesh,
+            None => return,
+        };
 
         push_event(
             static_mesh.entity_id,
-            Component::NetStaticMeshVelocity {
+            NetStaticMeshVelocity {
                 timestamp: packet.timestamp,
                 velocity: packet.velocity,
             },
@@ -156,13 +221,24 @@ impl System {
     }
 }
 
+impl ScheduledSystem for System {
+    fn declare(&self, declarations: &mut Declarations) {
+        declarations.stage(Stage::Simulate).writes("network");
+    }
+
+    fn run<'a>(&'a mut self, _: Stage, tick: &'a Tick) -> SystemFuture<'a> {
+        Box::pin(self.simulate(tick.timestamp))
+    }
+}
+
 impl EventListener for System {
-    fn receive_event(&mut self, _: EntityId, component: &Component) {
-        match component {
-            Component::InputAcceleration(acceleration) => {
-                self.input = *acceleration;
-            }
-            _ => {}
-        }
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<InputAcceleration, Self>();
+    }
+}
+
+impl EventHandler<InputAcceleration> for System {
+    fn handle_event(&mut self, _: EntityId, InputAcceleration(acceleration): &InputAcceleration) {
+        self.input = *acceleration;
     }
 }
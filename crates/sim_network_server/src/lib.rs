@@ -1,32 +1,40 @@
+mod socket;
+
 use std::{
-    collections::HashMap,
-    net::SocketAddr,
+    collections::{HashMap, HashSet},
+    io,
+    net::{SocketAddr, ToSocketAddrs},
     num::Wrapping,
-    thread::{self, JoinHandle},
+    time::Instant,
 };
 
-use component::Component;
+use component::{Location, NetInputAcceleration, Rotation, Velocity};
 use crossbeam_channel::{Receiver, Sender};
 use entity::EntityId;
-use event::{push_event, EventListener};
-use laminar::{Packet as LaminarPacket, Socket, SocketEvent};
-use nalgebra_glm::Vec3;
+use event::{push_event, EventHandler, EventListener, Subscriptions};
+use laminar::{Packet as LaminarPacket, SocketEvent};
+use nalgebra_glm::{Quat, Vec3};
 use network_utils::{
-    InputPacket, NetworkId, Packet, PingPacket, StaticMeshPacket, TimestampOffset, VelocityPacket,
-    PING_UPDATE_INTERVAL,
+    DespawnPacket, InputPacket, NetworkId, Packet, PingPacket, SpawnPacket, StaticMeshPacket,
+    TimestampOffset, VelocityPacket, PING_UPDATE_INTERVAL, SPAWN_STREAM_ID,
 };
-use system::Timestamp;
+use system::{Declarations, ScheduledSystem, Stage, SystemFuture, Tick, Timestamp};
+
+pub use socket::ReactorSocket;
 
 const SERVER: &str = "127.0.0.1:12351";
 
 const TIMESTEPS_PER_CLIENT_UPDATE: usize = 6;
 
 pub struct System {
-    _socket_thread_join: JoinHandle<()>,
+    socket: ReactorSocket,
     sender: Sender<LaminarPacket>,
     receiver: Receiver<SocketEvent>,
     clients: Vec<Client>,
     static_mesh_components: HashMap<NetworkId, StaticMeshComponent>,
+    next_network_id: NetworkId,
+    /// Network IDs promised to entities not spawned yet, e.g. later in a level
+    reserved_network_ids: HashSet<NetworkId>,
 }
 
 struct Client {
@@ -38,12 +46,28 @@ struct Client {
 
 struct StaticMeshComponent {
     entity_id: EntityId,
+    archetype: String,
+    mesh: Option<String>,
     location: Vec3,
+    rotation: Quat,
+    scale: Vec3,
     velocity: Vec3,
     velocity_updated: bool,
 }
 
 impl StaticMeshComponent {
+    fn spawn_packet(&self, network_id: NetworkId) -> SpawnPacket {
+        SpawnPacket {
+            network_id,
+            archetype: self.archetype.clone(),
+            mesh: self.mesh.clone(),
+            location: self.location,
+            rotation: self.rotation,
+            scale: self.scale,
+            velocity: self.velocity,
+        }
+    }
+
     fn update_velocity(&mut self, velocity: &Vec3) {
         if self.velocity != *velocity {
             self.velocity = *velocity;
@@ -61,43 +85,125 @@ impl StaticMeshComponent {
     }
 }
 
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl System {
     pub fn new() -> Self {
-        let mut socket = Socket::bind(SERVER).unwrap();
+        Self::bind(SERVER).unwrap()
+    }
 
-        let sender = socket.get_packet_sender();
-        let receiver = socket.get_event_receiver();
+    /// Listens on `addr` instead of the address clients connect to, e.g. port 0 in tests
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = ReactorSocket::bind(addr)?;
 
-        let _socket_thread_join = thread::spawn(move || {
-            socket.start_polling();
-        });
+        let sender = socket.packet_sender();
+        let receiver = socket.event_receiver();
 
-        Self {
-            _socket_thread_join,
+        Ok(Self {
+            socket,
             sender,
             receiver,
             clients: Vec::new(),
             static_mesh_components: HashMap::new(),
+            next_network_id: 0,
+            reserved_network_ids: HashSet::new(),
+        })
+    }
+
+    /// Returns a network ID neither used by any static mesh component nor reserved
+    pub fn allocate_network_id(&mut self) -> NetworkId {
+        while self
+            .static_mesh_components
+            .contains_key(&self.next_network_id)
+            || self.reserved_network_ids.contains(&self.next_network_id)
+        {
+            self.next_network_id = self.next_network_id.wrapping_add(1);
         }
+
+        let network_id = self.next_network_id;
+        self.next_network_id = self.next_network_id.wrapping_add(1);
+        network_id
+    }
+
+    /// Keeps allocate_network_id() from returning `network_id` until an entity is spawned with it
+    pub fn reserve_network_id(&mut self, network_id: NetworkId) {
+        self.reserved_network_ids.insert(network_id);
+    }
+
+    /// Whether a static mesh component already replicates with `network_id`
+    pub fn is_network_id_used(&self, network_id: NetworkId) -> bool {
+        self.static_mesh_components.contains_key(&network_id)
     }
 
-    pub fn create_static_mesh_component(&mut self, entity_id: EntityId) {
+    /// The network ID that allocate_network_id() tries first
+    pub fn next_network_id(&self) -> NetworkId {
+        self.next_network_id
+    }
+
+    pub fn set_next_network_id(&mut self, network_id: NetworkId) {
+        self.next_network_id = network_id;
+    }
+
+    /// Replicates the entity to all connected clients, and to clients that connect later. The
+    /// network ID must not be used already, see is_network_id_used().
+    pub fn create_static_mesh_component(&mut self, entity_id: EntityId, spawn: SpawnPacket) {
+        assert!(
+            !self.is_network_id_used(spawn.network_id),
+            "network ID {} is already used",
+            spawn.network_id
+        );
+        self.reserved_network_ids.remove(&spawn.network_id);
+
+        send_spawn_stream(&self.sender, &self.clients, spawn.clone());
+
         self.static_mesh_components.insert(
-            0,
+            spawn.network_id,
             StaticMeshComponent {
                 entity_id,
-                location: Vec3::zeros(),
-                velocity: Vec3::zeros(),
+                archetype: spawn.archetype,
+                mesh: spawn.mesh,
+                location: spawn.location,
+                rotation: spawn.rotation,
+                scale: spawn.scale,
+                velocity: spawn.velocity,
                 velocity_updated: false,
             },
         );
     }
 
     pub fn destroy_static_mesh_component(&mut self, entity_id: EntityId) {
+        let network_id = self
+            .static_mesh_components
+            .iter()
+            .find(|(_, static_mesh)| static_mesh.entity_id == entity_id)
+            .map(|(network_id, _)| *network_id);
+
+        if let Some(network_id) = network_id {
+            self.static_mesh_components.remove(&network_id);
+            send_spawn_stream(&self.sender, &self.clients, DespawnPacket { network_id });
+        }
+    }
+
+    fn static_mesh_component_mut(
+        &mut self,
+        entity_id: EntityId,
+    ) -> Option<&mut StaticMeshComponent> {
         self.static_mesh_components
-            .retain(|_, static_mesh| static_mesh.entity_id != entity_id);
+            .values_mut()
+            .find(|static_mesh| static_mesh.entity_id == entity_id)
+    }
+
+    /// Receives and sends packets as they arrive until `deadline`, e.g. the next step. Packets
+    /// queued by a step go out on the first poll.
+    pub async fn poll_socket_until(&mut self, deadline: Instant) {
+        self.socket.poll_until(deadline).await;
     }
 
+    /// Handles the packets received since the last step, see poll_socket_until()
     pub async fn simulate(&mut self, timestamp: Timestamp) {
         // recv
 
@@ -114,13 +220,17 @@ impl System {
 
         self.update_pings(timestamp);
 
-        if timestamp.0 % TIMESTEPS_PER_CLIENT_UPDATE as u32 == 0 {
+        if timestamp
+            .0
+            .is_multiple_of(TIMESTEPS_PER_CLIENT_UPDATE as u32)
+        {
             // full update
             for (network_id, static_mesh) in &mut self.static_mesh_components {
                 let packet = StaticMeshPacket {
                     timestamp,
                     network_id: *network_id,
                     location: static_mesh.location,
+                    rotation: static_mesh.rotation.into(),
                     velocity: static_mesh.velocity,
                 };
                 static_mesh.velocity_updated = false;
@@ -148,10 +258,12 @@ impl System {
         let payload = packet.payload();
         match Packet::from(payload) {
             Packet::EstablishConnection => self.handle_establish_connection(packet.addr()),
-            Packet::Input(data) => self.handle_input_packet(data, packet.addr(), timestamp),
+            Packet::Input(data) => self.handle_input_packet(data, packet.addr()),
             Packet::Ping(data) => self.handle_ping(data, packet.addr(), timestamp),
-            Packet::StaticMesh(_) => panic!(),
-            Packet::Velocity(_) => panic!(),
+            // only the server sends these, a client sending one is ignored rather than trusted
+            Packet::Spawn(_) | Packet::Despawn(_) | Packet::StaticMesh(_) | Packet::Velocity(_) => {
+                println!("ignoring unexpected packet from {}", packet.addr());
+            }
         };
     }
 
@@ -168,12 +280,20 @@ impl System {
     fn handle_connect(&mut self, addr: SocketAddr) {
         println!("connection established");
 
-        self.clients.push(Client {
+        let client = Client {
             addr,
             ping: Wrapping(0),
             last_ping_request: Wrapping(0),
             timestamp_offset: Wrapping(0),
-        });
+        };
+
+        // bring the new client up to date with every entity spawned before it joined
+        for (network_id, static_mesh) in &self.static_mesh_components {
+            let spawn = static_mesh.spawn_packet(*network_id);
+            send_spawn_stream(&self.sender, std::slice::from_ref(&client), spawn);
+        }
+
+        self.clients.push(client);
     }
 
     fn update_pings(&mut self, timestamp: Timestamp) {
@@ -202,17 +322,23 @@ impl System {
         client.timestamp_offset = timestamp - packet.timestamp + Wrapping(client.ping.0 / 2);
     }
 
-    fn handle_input_packet(&mut self, packet: InputPacket, addr: SocketAddr, timestamp: Timestamp) {
+    fn handle_input_packet(&mut self, packet: InputPacket, addr: SocketAddr) {
         // hack: only allow first client to send input
         let client = match self.clients.first() {
             Some(client) => client,
             _ => return,
         };
 
+        // hack: first client controls the first static mesh
+        let entity_id = match self.static_mesh_components.get(&0) {
+            Some(static_mesh) => static_mesh.entity_id,
+            _ => return,
+        };
+
         if client.addr == addr {
             push_event(
-                0,
-                Component::NetInputAcceleration {
+                entity_id,
+                NetInputAcceleration {
                     timestamp: packet.timestamp + client.timestamp_offset,
                     acceleration: packet.input,
                 },
@@ -223,6 +349,22 @@ impl System {
     }
 }
 
+/// Sends reliably and in order relative to every other packet on the spawn stream
+fn send_spawn_stream<P>(sender: &Sender<LaminarPacket>, clients: &[Client], data: P)
+where
+    P: Clone + Into<Packet>,
+{
+    for client in clients {
+        sender
+            .send(LaminarPacket::reliable_ordered(
+                client.addr,
+                data.clone().into().into(),
+                Some(SPAWN_STREAM_ID),
+            ))
+            .unwrap();
+    }
+}
+
 fn send_to_clients<P>(sender: &Sender<LaminarPacket>, clients: &[Client], data: P)
 where
     P: Copy + TimestampOffset + Into<Packet>,
@@ -241,24 +383,44 @@ where
     }
 }
 
+impl ScheduledSystem for System {
+    fn declare(&self, declarations: &mut Declarations) {
+        declarations.stage(Stage::Simulate).writes("network");
+    }
+
+    fn run<'a>(&'a mut self, _: Stage, tick: &'a Tick) -> SystemFuture<'a> {
+        Box::pin(self.simulate(tick.timestamp))
+    }
+}
+
 impl EventListener for System {
-    fn receive_event(&mut self, entity_id: EntityId, component: &Component) {
-        match component {
-            Component::Location(location) => {
-                self.static_mesh_components
-                    .values_mut()
-                    .find(|static_mesh| static_mesh.entity_id == entity_id)
-                    .unwrap()
-                    .location = *location;
-            }
-            Component::Velocity(velocity) => {
-                self.static_mesh_components
-                    .values_mut()
-                    .find(|static_mesh| static_mesh.entity_id == entity_id)
-                    .unwrap()
-                    .update_velocity(velocity);
-            }
-            _ => {}
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<Location, Self>();
+        subscriptions.add::<Rotation, Self>();
+        subscriptions.add::<Velocity, Self>();
+    }
+}
+
+impl EventHandler<Location> for System {
+    fn handle_event(&mut self, entity_id: EntityId, Location(location): &Location) {
+        if let Some(static_mesh) = self.static_mesh_component_mut(entity_id) {
+            static_mesh.location = *location;
+        }
+    }
+}
+
+impl EventHandler<Rotation> for System {
+    fn handle_event(&mut self, entity_id: EntityId, Rotation(rotation): &Rotation) {
+        if let Some(static_mesh) = self.static_mesh_component_mut(entity_id) {
+            static_mesh.rotation = *rotation;
+        }
+    }
+}
+
+impl EventHandler<Velocity> for System {
+    fn handle_event(&mut self, entity_id: EntityId, Velocity(velocity): &Velocity) {
+        if let Some(static_mesh) = self.static_mesh_component_mut(entity_id) {
+            static_mesh.update_velocity(velocity);
         }
     }
 }
@@ -0,0 +1,110 @@
+use std::{
+    future::{self, Future},
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    pin::pin,
+    task::Poll,
+    time::Instant,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use laminar::{
+    Config, ConnectionManager, DatagramSocket, Packet as LaminarPacket, SocketEvent,
+    VirtualConnection,
+};
+
+/// A laminar connection manager over a non-blocking UDP socket, polled from async code. Waiting
+/// for datagrams is left to the task reactor instead of a thread of its own.
+pub struct ReactorSocket {
+    connections: ConnectionManager<Udp, VirtualConnection>,
+}
+
+impl ReactorSocket {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            connections: ConnectionManager::new(Udp(socket), Config::default()),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.connections.socket().0.local_addr()
+    }
+
+    /// Packets sent through this go out on the next poll
+    pub fn packet_sender(&self) -> Sender<LaminarPacket> {
+        self.connections.event_sender().clone()
+    }
+
+    /// Packets, connects and disconnects received by polls
+    pub fn event_receiver(&self) -> Receiver<SocketEvent> {
+        self.connections.event_receiver().clone()
+    }
+
+    /// Receives every datagram that has arrived, sends queued packets, and resends or times out
+    /// connections as due, without waiting
+    pub fn poll(&mut self) {
+        self.connections.manual_poll(Instant::now());
+    }
+
+    /// Polls whenever a datagram arrives until `deadline`, so packets are received and
+    /// acknowledged as they come in rather than at the caller's pace
+    pub async fn poll_until(&mut self, deadline: Instant) {
+        loop {
+            self.poll();
+            if Instant::now() >= deadline {
+                return;
+            }
+
+            readable_until(&self.connections.socket().0, deadline).await;
+        }
+    }
+}
+
+/// Completes once `socket` has a datagram to read or `deadline` has passed
+#[cfg(target_os = "linux")]
+async fn readable_until(socket: &UdpSocket, deadline: Instant) {
+    let mut readable = pin!(task::readable(socket));
+    let mut timeout = pin!(task::sleep_until(deadline));
+
+    future::poll_fn(|cx| {
+        // an error also wakes the caller, whose next poll reports it
+        if readable.as_mut().poll(cx).is_ready() || timeout.as_mut().poll(cx).is_ready() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// The reactor only has timers elsewhere, so check for datagrams every millisecond
+#[cfg(not(target_os = "linux"))]
+async fn readable_until(_: &UdpSocket, deadline: Instant) {
+    let interval = std::time::Duration::from_millis(1);
+    task::sleep_until(deadline.min(Instant::now() + interval)).await
+}
+
+#[derive(Debug)]
+struct Udp(UdpSocket);
+
+impl DatagramSocket for Udp {
+    fn send_packet(&mut self, addr: &SocketAddr, payload: &[u8]) -> io::Result<usize> {
+        self.0.send_to(payload, addr)
+    }
+
+    fn receive_packet<'a>(&mut self, buffer: &'a mut [u8]) -> io::Result<(&'a [u8], SocketAddr)> {
+        let (len, addr) = self.0.recv_from(buffer)?;
+        Ok((&buffer[..len], addr))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    fn is_blocking_mode(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,50 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use laminar::{Packet, SocketEvent};
+use sim_network_server::ReactorSocket;
+use task::{run_parallel, sleep, Executor};
+
+#[test]
+fn receives_while_waiting_for_the_deadline() {
+    let (mut executor, _) = Executor::with_threads(2, || {});
+
+    let mut sender = ReactorSocket::bind("127.0.0.1:0").unwrap();
+    let mut receiver = ReactorSocket::bind("127.0.0.1:0").unwrap();
+    let receiver_addr = receiver.local_addr().unwrap();
+    let events = receiver.event_receiver();
+
+    let start = Instant::now();
+    let deadline = start + Duration::from_millis(500);
+    let received = Mutex::new(None);
+
+    executor.execute_blocking(&mut async {
+        let mut receive = receiver.poll_until(deadline);
+        let mut send = async {
+            sleep(Duration::from_millis(10)).await;
+            let packet = Packet::reliable_unordered(receiver_addr, b"hello".to_vec());
+            sender.packet_sender().send(packet).unwrap();
+            sender.poll();
+        };
+        // checks what the receiving socket has polled so far
+        let mut watch = async {
+            while Instant::now() < deadline {
+                if let Ok(SocketEvent::Packet(packet)) = events.try_recv() {
+                    *received.lock().unwrap() = Some((packet.payload().to_vec(), start.elapsed()));
+                    break;
+                }
+                sleep(Duration::from_millis(1)).await;
+            }
+        };
+        run_parallel([&mut receive, &mut send, &mut watch]).await;
+    });
+
+    assert!(Instant::now() >= deadline);
+
+    let (payload, elapsed) = received.into_inner().unwrap().expect("packet not received");
+    assert_eq!(payload, b"hello");
+    // well before the deadline, rather than only once it was reached
+    assert!(elapsed < Duration::from_millis(250), "{:?}", elapsed);
+}
@@ -1,18 +1,60 @@
 use std::num::Wrapping;
 
-use component::Component;
+use component::{
+    Aabb, InputAcceleration, Location, NetInputAcceleration, NetStaticMeshLocation,
+    NetStaticMeshRotation, NetStaticMeshVelocity, RenderTransform, Rotation, Transform, Velocity,
+};
 use data::ComponentArray;
 use entity::EntityId;
-use event::{push_event, EventListener};
-use nalgebra_glm::{vec2_to_vec3, Vec3};
+use event::{push_event, EventHandler, EventListener, Subscriptions};
+use nalgebra_glm::{quat_angle, quat_angle_axis, quat_conjugate, vec2_to_vec3, Quat, Vec3};
 use network_utils::NETWORK_SNAPSHOTS_LEN;
-use system::{Timestamp, TIMESTEP_F32};
+use serde::{Deserialize, Serialize};
+use system::{Declarations, ScheduledSystem, Stage, SystemFuture, Tick, Timestamp, TIMESTEP_F32};
 use task::{run_slice, run_slice_mut};
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 struct Object {
-    location: Vec3,
+    transform: Transform,
     velocity: Vec3,
+    angular_velocity: Vec3,
+}
+
+/// Angular velocity of a sphere rolling without slipping on the XY plane. Objects are unit
+/// spheres, so the radius is the scale.
+fn rolling_angular_velocity(velocity: &Vec3, scale: &Vec3) -> Vec3 {
+    Vec3::z().cross(velocity) / scale.x
+}
+
+fn integrate_rotation(rotation: &Quat, angular_velocity: &Vec3, delta_time: f32) -> Quat {
+    let angle = angular_velocity.norm() * delta_time;
+    if angle <= f32::EPSILON {
+        return *rotation;
+    }
+
+    (quat_angle_axis(angle, angular_velocity) * rotation).normalize()
+}
+
+/// An object's full snapshot history, for saving
+#[derive(Serialize, Deserialize)]
+pub struct ObjectState {
+    snapshots: Vec<Object>,
+}
+
+impl ObjectState {
+    /// Returns None if there's no snapshot for the timestamp
+    pub fn transform_and_velocity(&self, timestamp: Timestamp) -> Option<(Transform, Vec3)> {
+        let object = self
+            .snapshots
+            .get(timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN)?;
+        Some((object.transform, object.velocity))
+    }
+
+    /// False if the history was saved with a different NETWORK_SNAPSHOTS_LEN, in which case
+    /// restore_object_state() rejects it
+    pub fn is_complete(&self) -> bool {
+        self.snapshots.len() == NETWORK_SNAPSHOTS_LEN
+    }
 }
 
 pub struct System {
@@ -21,6 +63,12 @@ pub struct System {
     correct_from_timestamp: Option<Timestamp>,
 }
 
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl System {
     pub fn new() -> Self {
         System {
@@ -30,15 +78,54 @@ impl System {
         }
     }
 
-    pub fn create_component(&mut self, entity_id: EntityId) {
+    pub fn create_component(&mut self, entity_id: EntityId, transform: Transform, velocity: Vec3) {
+        let object = Object {
+            transform,
+            velocity,
+            angular_velocity: rolling_angular_velocity(&velocity, &transform.scale),
+        };
         self.objects
-            .push(entity_id, [Object::default(); NETWORK_SNAPSHOTS_LEN]);
+            .push(entity_id, [object; NETWORK_SNAPSHOTS_LEN]);
     }
 
     pub fn destroy_component(&mut self, entity_id: EntityId) {
         self.objects.remove(entity_id);
     }
 
+    pub fn current_timestamp(&self) -> Timestamp {
+        self.current_timestamp
+    }
+
+    /// Only valid before the next simulate() call on a system with no pending corrections
+    pub fn restore_timestamp(&mut self, timestamp: Timestamp) {
+        self.current_timestamp = timestamp;
+        self.correct_from_timestamp = None;
+    }
+
+    pub fn object_state(&self, entity_id: EntityId) -> Option<ObjectState> {
+        let object = self.objects.get(entity_id)?;
+        Some(ObjectState {
+            snapshots: object.data.to_vec(),
+        })
+    }
+
+    /// Replaces the snapshot history of an existing component. Returns false if the component
+    /// doesn't exist or the history was saved with a different NETWORK_SNAPSHOTS_LEN.
+    pub fn restore_object_state(&mut self, entity_id: EntityId, state: ObjectState) -> bool {
+        let snapshots = match state.snapshots.try_into() {
+            Ok(snapshots) => snapshots,
+            Err(_) => return false,
+        };
+
+        match self.objects.get_mut(entity_id) {
+            Some(object) => {
+                object.data = snapshots;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub async fn simulate(&mut self, timestamp: Timestamp) {
         if let Some(correct_from_timestamp) = self.correct_from_timestamp.take() {
             self.current_timestamp = correct_from_timestamp;
@@ -59,19 +146,34 @@ impl System {
         let snapshot_index = self.current_timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
 
         run_slice_mut(self.objects.as_mut_slice(), |object| {
-            let prev_location = object.data[prev_snapshot_index].location;
-            let prev_velocity = object.data[prev_snapshot_index].velocity;
-            object.data[snapshot_index].velocity = prev_velocity;
-            object.data[snapshot_index].location = prev_location + prev_velocity * TIMESTEP_F32;
+            let prev = object.data[prev_snapshot_index];
+            let next = &mut object.data[snapshot_index];
+
+            next.velocity = prev.velocity;
+            next.angular_velocity = rolling_angular_velocity(&prev.velocity, &prev.transform.scale);
+            next.transform = Transform {
+                location: prev.transform.location + prev.velocity * TIMESTEP_F32,
+                rotation: integrate_rotation(
+                    &prev.transform.rotation,
+                    &prev.angular_velocity,
+                    TIMESTEP_F32,
+                ),
+                scale: prev.transform.scale,
+            };
 
             push_event(
                 object.entity_id,
-                Component::Location(object.data[snapshot_index].location),
+                Location(object.data[snapshot_index].transform.location),
             );
 
             push_event(
                 object.entity_id,
-                Component::Velocity(object.data[snapshot_index].velocity),
+                Rotation(object.data[snapshot_index].transform.rotation),
+            );
+
+            push_event(
+                object.entity_id,
+                Velocity(object.data[snapshot_index].velocity),
             );
         })
         .await;
@@ -83,69 +185,133 @@ impl System {
         let snapshot_index = self.current_timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
 
         run_slice(self.objects.as_slice(), |object| {
-            let prev_location = &object.data[prev_snapshot_index].location;
-            let location = &object.data[snapshot_index].location;
+            let prev_transform = &object.data[prev_snapshot_index].transform;
+            let transform = &object.data[snapshot_index].transform;
 
-            let interp_location = (1.0 - frame_interp) * prev_location + frame_interp * location;
+            let interp_transform = prev_transform.interpolate(transform, frame_interp);
 
-            push_event(object.entity_id, Component::RenderLocation(interp_location));
+            push_event(object.entity_id, RenderTransform(interp_transform));
         })
         .await;
     }
+
+    /// Box around every object at the latest step, None without objects
+    pub async fn bounds(&self) -> Option<Aabb> {
+        let snapshot_index = self.current_timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
+
+        self.objects
+            .par_map_reduce(
+                |_, snapshots| {
+                    let transform = &snapshots[snapshot_index].transform;
+                    Aabb::from_sphere(transform.location, transform.scale.x)
+                },
+                |a, b| a.union(&b),
+            )
+            .await
+    }
+
+    /// Whether a correction for `timestamp` is still within the snapshot history
+    fn in_history(&self, timestamp: Timestamp) -> bool {
+        ((self.current_timestamp - timestamp).0 as usize) < NETWORK_SNAPSHOTS_LEN
+    }
+}
+
+impl ScheduledSystem for System {
+    fn declare(&self, declarations: &mut Declarations) {
+        declarations.stage(Stage::Simulate).writes("physics");
+        declarations.stage(Stage::Render).reads("physics");
+    }
+
+    fn run<'a>(&'a mut self, stage: Stage, tick: &'a Tick) -> SystemFuture<'a> {
+        match stage {
+            Stage::Simulate => Box::pin(self.simulate(tick.timestamp)),
+            Stage::Render => Box::pin(self.render(tick.frame_interp)),
+            stage => unreachable!("physics doesn't run in {:?}", stage),
+        }
+    }
 }
 
 impl EventListener for System {
-    fn receive_event(&mut self, entity_id: EntityId, component: &Component) {
-        if entity_id > 0 && !self.objects.contains_entity(entity_id) {
+    fn subscribe(&self, subscriptions: &mut Subscriptions) {
+        subscriptions.add::<InputAcceleration, Self>();
+        subscriptions.add::<NetInputAcceleration, Self>();
+        subscriptions.add::<NetStaticMeshLocation, Self>();
+        subscriptions.add::<NetStaticMeshRotation, Self>();
+        subscriptions.add::<NetStaticMeshVelocity, Self>();
+    }
+}
+
+impl EventHandler<InputAcceleration> for System {
+    fn handle_event(&mut self, _: EntityId, InputAcceleration(acceleration): &InputAcceleration) {
+        let timestamp_index = self.current_timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
+        for component in &mut self.objects {
+            component.data[timestamp_index].velocity = vec2_to_vec3(acceleration);
+        }
+    }
+}
+
+impl EventHandler<NetInputAcceleration> for System {
+    fn handle_event(&mut self, entity_id: EntityId, event: &NetInputAcceleration) {
+        if let Some(object) = self.objects.get_mut(entity_id) {
+            let timestamp_index = event.timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
+            object.data[timestamp_index].velocity = vec2_to_vec3(&event.acceleration);
+            self.correct_from_timestamp = Some(event.timestamp);
+        }
+    }
+}
+
+impl EventHandler<NetStaticMeshLocation> for System {
+    fn handle_event(&mut self, entity_id: EntityId, event: &NetStaticMeshLocation) {
+        if !self.in_history(event.timestamp) {
             return;
         }
 
-        match component {
-            Component::InputAcceleration(acceleration) => {
-                let timestamp_index = self.current_timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
-                for component in &mut self.objects {
-                    component.data[timestamp_index].velocity = vec2_to_vec3(acceleration);
-                }
+        if let Some(object) = self.objects.get_mut(entity_id) {
+            let timestamp_index = event.timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
+            let client_location = &mut object.data[timestamp_index].transform.location;
+            let err = (*client_location - event.location).norm();
+            if err > 0.1 {
+                *client_location = event.location;
+                // todo: falls apart if multiple corrections at different timestamps
+                self.correct_from_timestamp = Some(event.timestamp);
             }
-            Component::NetInputAcceleration {
-                timestamp,
-                acceleration,
-            } => {
-                let timestamp_index = timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
-                self.objects[entity_id].data[timestamp_index].velocity = vec2_to_vec3(acceleration);
-                self.correct_from_timestamp = Some(*timestamp);
-            }
-            Component::NetStaticMeshLocation {
-                timestamp,
-                location,
-            } => {
-                if ((self.current_timestamp - timestamp).0 as usize) < NETWORK_SNAPSHOTS_LEN {
-                    let timestamp_index = timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
-                    let client_location = &self.objects[entity_id].data[timestamp_index].location;
-                    let err = (client_location - *location).norm();
-                    if err > 0.1 {
-                        self.objects[entity_id].data[timestamp_index].location = *location;
-                        // todo: falls apart if multiple corrections at different timestamps
-                        self.correct_from_timestamp = Some(*timestamp);
-                    }
-                }
+        }
+    }
+}
+
+impl EventHandler<NetStaticMeshRotation> for System {
+    fn handle_event(&mut self, entity_id: EntityId, event: &NetStaticMeshRotation) {
+        if !self.in_history(event.timestamp) {
+            return;
+        }
+
+        if let Some(object) = self.objects.get_mut(entity_id) {
+            let timestamp_index = event.timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
+            let client_rotation = &mut object.data[timestamp_index].transform.rotation;
+            let err = quat_angle(&(quat_conjugate(client_rotation) * event.rotation));
+            if err > 0.1 {
+                *client_rotation = event.rotation;
+                // todo: falls apart if multiple corrections at different timestamps
+                self.correct_from_timestamp = Some(event.timestamp);
             }
-            Component::NetStaticMeshVelocity {
-                timestamp,
-                velocity,
-            } => {
-                if ((self.current_timestamp - timestamp).0 as usize) < NETWORK_SNAPSHOTS_LEN {
-                    let timestamp_index = timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
-                    let object_velocity =
-                        &mut self.objects[entity_id].data[timestamp_index].velocity;
-                    if object_velocity != velocity {
-                        *object_velocity = *velocity;
-                        // todo: falls apart if multiple corrections at different timestamps
-                        self.correct_from_timestamp = Some(*timestamp);
-                    }
-                }
+        }
+    }
+}
+
+impl EventHandler<NetStaticMeshVelocity> for System {
+    fn handle_event(&mut self, entity_id: EntityId, event: &NetStaticMeshVelocity) {
+        if !self.in_history(event.timestamp) {
+            return;
+        }
+
+        if let Some(object) = self.objects.get_mut(entity_id) {
+            let timestamp_index = event.timestamp.0 as usize % NETWORK_SNAPSHOTS_LEN;
+            let object_velocity = &mut object.data[timestamp_index].velocity;
+            if *object_velocity != event.velocity {
+                *object_velocity = event.velocity;
+                // todo: falls apart if multiple corrections at different timestamps
+                self.correct_from_timestamp = Some(event.timestamp);
             }
-            _ => {}
         }
     }
 }
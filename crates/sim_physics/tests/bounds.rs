@@ -0,0 +1,53 @@
+use component::{Aabb, Transform};
+use entity::EntityAllocator;
+use nalgebra_glm::{vec3, Vec3};
+use sim_physics::System;
+use task::Executor;
+
+fn sphere(location: Vec3, radius: f32) -> Transform {
+    Transform {
+        location,
+        scale: vec3(radius, radius, radius),
+        ..Transform::default()
+    }
+}
+
+#[test]
+fn bounds_contain_every_object() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    let mut allocator = EntityAllocator::new();
+    let mut physics = System::new();
+
+    let mut bounds = Some(Aabb::from_sphere(Vec3::zeros(), 1.0));
+    executor.execute_blocking(&mut async { bounds = physics.bounds().await });
+    assert_eq!(bounds, None);
+
+    let near = allocator.allocate();
+    let far = allocator.allocate();
+    physics.create_component(near, sphere(vec3(1.0, 2.0, 3.0), 1.0), Vec3::zeros());
+    physics.create_component(far, sphere(vec3(-10.0, 0.0, 5.0), 2.0), Vec3::zeros());
+    for _ in 0..100 {
+        let transform = sphere(vec3(0.0, 0.0, 0.0), 0.5);
+        physics.create_component(allocator.allocate(), transform, Vec3::zeros());
+    }
+
+    executor.execute_blocking(&mut async { bounds = physics.bounds().await });
+    assert_eq!(
+        bounds,
+        Some(Aabb {
+            min: vec3(-12.0, -2.0, -0.5),
+            max: vec3(2.0, 3.0, 7.0),
+        })
+    );
+
+    physics.destroy_component(far);
+    executor.execute_blocking(&mut async { bounds = physics.bounds().await });
+    assert_eq!(
+        bounds,
+        Some(Aabb {
+            min: vec3(-0.5, -0.5, -0.5),
+            max: vec3(2.0, 3.0, 4.0),
+        })
+    );
+}
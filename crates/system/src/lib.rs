@@ -1,5 +1,11 @@
+mod schedule;
+
 use std::{num::Wrapping, time::Duration};
 
+pub use schedule::{
+    Declarations, Resource, ScheduledSystem, Scheduler, Stage, StageAccess, SystemFuture, Tick,
+};
+
 pub const STEPS_PER_SECOND: usize = 60;
 
 pub const TIMESTEP: Duration = Duration::from_micros(16_667);
@@ -0,0 +1,247 @@
+use std::{fmt::Write, future::Future, pin::Pin};
+
+use task::run_scope;
+
+use crate::Timestamp;
+
+/// Points in the frame at which systems run. Events are distributed between stages, so each
+/// stage sees the events pushed during the previous ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Once per simulation step
+    Simulate,
+    /// Once per frame, after simulating
+    Render,
+    /// Once per frame, once render transforms are distributed
+    Propagate,
+    /// Once per frame, between the renderer's frame_begin() and frame_end()
+    Draw,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [
+        Stage::Simulate,
+        Stage::Render,
+        Stage::Propagate,
+        Stage::Draw,
+    ];
+}
+
+/// Parameters shared by every system in a stage
+#[derive(Clone, Copy)]
+pub struct Tick {
+    pub timestamp: Timestamp,
+    /// Seconds since the previous frame, zero when simulating
+    pub delta_time: f32,
+    /// How far the frame is between the previous simulation step and the next one
+    pub frame_interp: f32,
+}
+
+/// Names state which more than one system may touch, e.g. a component kind or the renderer's
+/// command buffer
+pub type Resource = &'static str;
+
+pub type SystemFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// A system run by the Scheduler
+pub trait ScheduledSystem: Send {
+    /// Declares the stages the system runs in, and the resources it uses in each
+    fn declare(&self, declarations: &mut Declarations);
+
+    /// Only called for stages the system declared
+    fn run<'a>(&'a mut self, stage: Stage, tick: &'a Tick) -> SystemFuture<'a>;
+
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// What one system uses in one stage
+#[derive(Default)]
+pub struct StageAccess {
+    reads: Vec<Resource>,
+    writes: Vec<Resource>,
+}
+
+impl StageAccess {
+    pub fn reads(&mut self, resource: Resource) -> &mut Self {
+        self.reads.push(resource);
+        self
+    }
+
+    pub fn writes(&mut self, resource: Resource) -> &mut Self {
+        self.writes.push(resource);
+        self
+    }
+
+    /// The first resource one of the two writes while the other uses it
+    fn conflict(&self, other: &Self) -> Option<Resource> {
+        let writes_used = |a: &Self, b: &Self| {
+            a.writes
+                .iter()
+                .find(|resource| b.reads.contains(resource) || b.writes.contains(resource))
+                .copied()
+        };
+
+        writes_used(self, other).or_else(|| writes_used(other, self))
+    }
+}
+
+/// Filled in by ScheduledSystem::declare()
+#[derive(Default)]
+pub struct Declarations {
+    stages: Vec<(Stage, StageAccess)>,
+}
+
+impl Declarations {
+    /// Declares that the system runs in `stage`. Calling it again for the same stage adds to the
+    /// same access.
+    pub fn stage(&mut self, stage: Stage) -> &mut StageAccess {
+        let index = match self.stages.iter().position(|(s, _)| *s == stage) {
+            Some(index) => index,
+            None => {
+                self.stages.push((stage, StageAccess::default()));
+                self.stages.len() - 1
+            }
+        };
+
+        &mut self.stages[index].1
+    }
+
+    fn access(&self, stage: Stage) -> Option<&StageAccess> {
+        self.stages
+            .iter()
+            .find(|(s, _)| *s == stage)
+            .map(|(_, access)| access)
+    }
+}
+
+/// A system's place in one stage's schedule
+struct Slot {
+    system: usize,
+    wave: usize,
+    /// Earlier systems this one waits for, and the resource they conflict on
+    after: Vec<(usize, Resource)>,
+}
+
+#[derive(Default)]
+struct StageSchedule {
+    /// In system order
+    slots: Vec<Slot>,
+    waves: usize,
+}
+
+/// Runs each stage's systems on the task executor. Systems whose accesses conflict run in the
+/// order they're passed in, and each runs in the first wave after every earlier system it
+/// conflicts with. The systems within a wave run concurrently.
+///
+/// Declarations are gathered once, when the scheduler is created, so systems must declare the
+/// same stages and resources for as long as they're scheduled.
+pub struct Scheduler {
+    /// By stage
+    stages: Vec<StageSchedule>,
+    system_count: usize,
+}
+
+impl Scheduler {
+    /// Builds every stage's schedule. run() and dump() must be passed the same systems in the
+    /// same order.
+    pub fn new(systems: &[&mut dyn ScheduledSystem]) -> Self {
+        let declarations = systems
+            .iter()
+            .map(|system| {
+                let mut declarations = Declarations::default();
+                system.declare(&mut declarations);
+                declarations
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            stages: Stage::ALL
+                .iter()
+                .map(|stage| Self::build(*stage, &declarations))
+                .collect(),
+            system_count: systems.len(),
+        }
+    }
+
+    pub async fn run(&self, stage: Stage, tick: &Tick, systems: &mut [&mut dyn ScheduledSystem]) {
+        debug_assert_eq!(
+            systems.len(),
+            self.system_count,
+            "systems changed since new()"
+        );
+        let schedule = &self.stages[stage as usize];
+
+        for wave in 0..schedule.waves {
+            run_scope(|scope| {
+                let mut slots = schedule.slots.iter().peekable();
+                for (index, system) in systems.iter_mut().enumerate() {
+                    if slots
+                        .next_if(|slot| slot.system == index)
+                        .is_some_and(|slot| slot.wave == wave)
+                    {
+                        scope.spawn_boxed(system.name(), system.run(stage, tick));
+                    }
+                }
+            })
+            .await;
+        }
+    }
+
+    /// Describes every stage's schedule, for debugging
+    pub fn dump(&self, systems: &[&mut dyn ScheduledSystem]) -> String {
+        let mut dump = String::new();
+
+        for stage in Stage::ALL {
+            let schedule = &self.stages[stage as usize];
+            writeln!(dump, "{:?}", stage).unwrap();
+
+            for wave in 0..schedule.waves {
+                writeln!(dump, "  wave {}", wave).unwrap();
+
+                for slot in schedule.slots.iter().filter(|slot| slot.wave == wave) {
+                    write!(dump, "    {}", systems[slot.system].name()).unwrap();
+                    for (i, (system, resource)) in slot.after.iter().enumerate() {
+                        let separator = if i == 0 { " after " } else { ", " };
+                        let name = systems[*system].name();
+                        write!(dump, "{}{} ({})", separator, name, resource).unwrap();
+                    }
+                    writeln!(dump).unwrap();
+                }
+            }
+        }
+
+        dump
+    }
+
+    fn build(stage: Stage, declarations: &[Declarations]) -> StageSchedule {
+        let mut schedule = StageSchedule::default();
+
+        for (system, system_declarations) in declarations.iter().enumerate() {
+            let access = match system_declarations.access(stage) {
+                Some(access) => access,
+                None => continue,
+            };
+
+            let mut slot = Slot {
+                system,
+                wave: 0,
+                after: Vec::new(),
+            };
+
+            for earlier in &schedule.slots {
+                let earlier_access = declarations[earlier.system].access(stage).unwrap();
+                if let Some(resource) = access.conflict(earlier_access) {
+                    slot.wave = slot.wave.max(earlier.wave + 1);
+                    slot.after.push((earlier.system, resource));
+                }
+            }
+
+            schedule.waves = schedule.waves.max(slot.wave + 1);
+            schedule.slots.push(slot);
+        }
+
+        schedule
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    num::Wrapping,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use system::{Declarations, ScheduledSystem, Scheduler, Stage, SystemFuture, Tick};
+use task::{sleep, Executor};
+
+/// Log of (system, stage, started) shared by the test systems
+type Log = Arc<Mutex<Vec<(&'static str, Stage, bool)>>>;
+
+struct TestSystem {
+    name: &'static str,
+    /// (stage, reads, writes)
+    accesses: Vec<(Stage, &'static [&'static str], &'static [&'static str])>,
+    log: Log,
+}
+
+impl TestSystem {
+    fn new(name: &'static str, log: &Log) -> Self {
+        Self {
+            name,
+            accesses: Vec::new(),
+            log: log.clone(),
+        }
+    }
+
+    fn access(
+        mut self,
+        stage: Stage,
+        reads: &'static [&'static str],
+        writes: &'static [&'static str],
+    ) -> Self {
+        self.accesses.push((stage, reads, writes));
+        self
+    }
+}
+
+impl ScheduledSystem for TestSystem {
+    fn declare(&self, declarations: &mut Declarations) {
+        for (stage, reads, writes) in &self.accesses {
+            let access = declarations.stage(*stage);
+            for resource in *reads {
+                access.reads(resource);
+            }
+            for resource in *writes {
+                access.writes(resource);
+            }
+        }
+    }
+
+    fn run<'a>(&'a mut self, stage: Stage, _: &'a Tick) -> SystemFuture<'a> {
+        Box::pin(async move {
+            self.log.lock().unwrap().push((self.name, stage, true));
+            // long enough for systems in the same wave to overlap
+            sleep(Duration::from_millis(20)).await;
+            self.log.lock().unwrap().push((self.name, stage, false));
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+fn systems(log: &Log) -> Vec<TestSystem> {
+    vec![
+        TestSystem::new("physics", log)
+            .access(Stage::Simulate, &[], &["physics"])
+            .access(Stage::Render, &["physics"], &[]),
+        TestSystem::new("network", log).access(Stage::Simulate, &["physics"], &["network"]),
+        TestSystem::new("camera", log).access(Stage::Render, &["physics"], &["camera"]),
+        TestSystem::new("meshes", log).access(Stage::Draw, &["camera"], &["gfx commands"]),
+        TestSystem::new("ui", log).access(Stage::Draw, &[], &["gfx commands"]),
+        TestSystem::new("audio", log).access(Stage::Simulate, &[], &["audio"]),
+    ]
+}
+
+#[test]
+fn dumps_waves_and_their_conflicts() {
+    let log = Log::default();
+    let mut systems = systems(&log);
+    let systems = systems
+        .iter_mut()
+        .map(|system| system as &mut dyn ScheduledSystem)
+        .collect::<Vec<_>>();
+
+    let scheduler = Scheduler::new(&systems);
+
+    assert_eq!(
+        scheduler.dump(&systems),
+        "Simulate
+  wave 0
+    physics
+    audio
+  wave 1
+    network after physics (physics)
+Render
+  wave 0
+    physics
+    camera
+Propagate
+Draw
+  wave 0
+    meshes
+  wave 1
+    ui after meshes (gfx commands)
+"
+    );
+}
+
+#[test]
+fn runs_waves_in_order_and_each_wave_concurrently() {
+    let log = Log::default();
+    let mut systems = systems(&log);
+    let mut systems = systems
+        .iter_mut()
+        .map(|system| system as &mut dyn ScheduledSystem)
+        .collect::<Vec<_>>();
+
+    let scheduler = Scheduler::new(&systems);
+    let (mut executor, _) = Executor::with_threads(4, || {});
+    let tick = Tick {
+        timestamp: Wrapping(0),
+        delta_time: 0.0,
+        frame_interp: 0.0,
+    };
+
+    for stage in Stage::ALL {
+        executor.execute_blocking(&mut scheduler.run(stage, &tick, &mut systems));
+    }
+
+    let log = log.lock().unwrap();
+    let position = |name, stage, started| {
+        log.iter()
+            .position(|entry| *entry == (name, stage, started))
+            .unwrap_or_else(|| panic!("{} never ran in {:?}", name, stage))
+    };
+
+    // stages run one after the other
+    assert!(position("audio", Stage::Simulate, false) < position("camera", Stage::Render, true));
+    assert!(position("camera", Stage::Render, false) < position("meshes", Stage::Draw, true));
+
+    // conflicting systems wait for the earlier wave to finish
+    assert!(
+        position("physics", Stage::Simulate, false) < position("network", Stage::Simulate, true)
+    );
+    assert!(position("meshes", Stage::Draw, false) < position("ui", Stage::Draw, true));
+
+    // systems in the same wave start before either finishes
+    assert!(position("audio", Stage::Simulate, true) < position("physics", Stage::Simulate, false));
+    assert!(position("physics", Stage::Simulate, true) < position("audio", Stage::Simulate, false));
+    assert!(position("camera", Stage::Render, true) < position("physics", Stage::Render, false));
+
+    // each system ran once per declared stage, and in no others
+    assert_eq!(log.len(), 2 * 7);
+}
@@ -0,0 +1,81 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use task::{run_slice_mut, Executor};
+
+/// Matches sim_physics' per-entity snapshot history: a second of 60 steps, each a transform
+/// plus linear and angular velocity
+const SNAPSHOTS_LEN: usize = 60;
+
+#[derive(Clone, Copy, Default)]
+struct Snapshot {
+    location: [f32; 3],
+    rotation: [f32; 4],
+    scale: [f32; 3],
+    velocity: [f32; 3],
+    angular_velocity: [f32; 3],
+}
+
+/// Integrates the next snapshot from the previous one, roughly the work of a physics step
+fn step(executor: &mut Executor, objects: &mut [[Snapshot; SNAPSHOTS_LEN]], step: usize) {
+    let prev_index = step % SNAPSHOTS_LEN;
+    let next_index = (step + 1) % SNAPSHOTS_LEN;
+
+    let mut simulate = async {
+        run_slice_mut(objects, |snapshots| {
+            let prev = snapshots[prev_index];
+            let next = &mut snapshots[next_index];
+
+            *next = prev;
+            for axis in 0..3 {
+                next.location[axis] += prev.velocity[axis] / 60.0;
+                next.velocity[axis] *= 0.99;
+            }
+            next.angular_velocity = [
+                -prev.velocity[1] / prev.scale[0],
+                prev.velocity[0] / prev.scale[0],
+                0.0,
+            ];
+
+            let norm = prev.rotation.iter().map(|x| x * x).sum::<f32>().sqrt();
+            for (next, prev) in next.rotation.iter_mut().zip(prev.rotation) {
+                *next = prev / norm;
+            }
+        })
+        .await;
+    };
+
+    executor.execute_blocking(&mut simulate);
+}
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+/// No contention, and a typical desktop core count
+const THREADS: [usize; 2] = [1, 4];
+
+fn bench_run_slice_mut(c: &mut Criterion) {
+    let mut group = c.benchmark_group("run_slice_mut");
+    for threads in THREADS {
+        let (mut executor, _) = Executor::with_threads(threads, || {});
+
+        for size in SIZES {
+            let mut objects = vec![[Snapshot::default(); SNAPSHOTS_LEN]; size];
+            for (i, snapshots) in objects.iter_mut().enumerate() {
+                snapshots[0].rotation = [0.0, 0.0, 0.0, 1.0];
+                snapshots[0].scale = [1.0; 3];
+                snapshots[0].velocity = [i as f32, 1.0, 0.0];
+            }
+
+            let id = BenchmarkId::new(format!("{}_threads", threads), size);
+            let mut steps = 0;
+            group.bench_with_input(id, &size, |b, _| {
+                b.iter(|| {
+                    step(&mut executor, &mut objects, steps);
+                    steps += 1;
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_run_slice_mut);
+criterion_main!(benches);
@@ -1,33 +1,251 @@
 use std::{
-    future::Future,
+    any::Any,
+    cell::Cell,
+    fmt,
+    future::{self, Future},
+    iter,
     mem::MaybeUninit,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
     pin::Pin,
     ptr,
     sync::{
-        atomic::{AtomicPtr, Ordering},
-        mpsc::{sync_channel, Receiver, SyncSender},
-        Arc, Condvar, Mutex,
+        atomic::{self, AtomicBool, AtomicPtr, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, PoisonError,
     },
     task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
     thread::{self, JoinHandle, ThreadId},
 };
 
-use lazy_static::lazy_static;
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+use profile::Profiler;
+use reactor::Reactor;
 use spin::Mutex as SpinMutex;
 
-struct SyncChannel {
-    sender: SyncSender<ExecutorMessage>,
-    receiver: Mutex<Receiver<ExecutorMessage>>,
+pub use profile::Profile;
+#[cfg(target_os = "linux")]
+pub use reactor::{readable, writable};
+pub use reactor::{sleep, sleep_until};
+
+mod profile;
+mod reactor;
+
+/// State of one executor, shared by its threads
+struct Shared {
+    /// Tasks queued from outside of the executor's threads
+    injector: Injector<TaskPtr>,
+    /// One per thread, for stealing from its local queue
+    stealers: Vec<Stealer<TaskPtr>>,
+    /// Threads waiting on sleep_cvar
+    sleeping: AtomicUsize,
+    sleep_mutex: Mutex<()>,
+    sleep_cvar: Condvar,
+    shutdown: AtomicBool,
+    main_task: AtomicPtr<Task>,
+    main_task_done: Mutex<bool>,
+    main_task_cvar: Condvar,
+    profiler: Profiler,
+    reactor: Arc<Reactor>,
+    /// See Executor::inline()
+    inline: bool,
+    /// Tasks queued from outside of tasks, e.g. by execute_blocking()
+    roots: AtomicU64,
+    /// First panic outside of any task, e.g. in the reactor, re-raised by execute_blocking()
+    thread_panic: Mutex<Option<TaskPanic>>,
 }
 
-lazy_static! {
-    static ref SYNC_CHANNEL: SyncChannel = {
-        let (sender, receiver) = sync_channel(50);
-        SyncChannel {
-            sender,
-            receiver: Mutex::new(receiver),
+impl Shared {
+    fn new(stealers: Vec<Stealer<TaskPtr>>, inline: bool) -> Self {
+        let num_threads = stealers.len();
+
+        Self {
+            injector: Injector::new(),
+            stealers,
+            sleeping: AtomicUsize::new(0),
+            sleep_mutex: Mutex::new(()),
+            sleep_cvar: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+            main_task: AtomicPtr::new(ptr::null_mut()),
+            main_task_done: Mutex::new(false),
+            main_task_cvar: Condvar::new(),
+            profiler: Profiler::new(num_threads),
+            reactor: Arc::new(Reactor::new().expect("failed to create reactor")),
+            inline,
+            roots: AtomicU64::new(0),
+            thread_panic: Mutex::new(None),
         }
-    };
+    }
+
+    /// Keeps the first panic outside of any task, for execute_blocking() to re-raise
+    fn record_panic(&self, thread: &'static str, payload: Box<dyn Any + Send>) {
+        let mut thread_panic = self.thread_panic.lock().unwrap();
+        thread_panic.get_or_insert(TaskPanic {
+            task: thread,
+            payload,
+        });
+    }
+
+    /// One turn of the reactor. A panic is recorded rather than unwinding, since tasks waiting on
+    /// timers would never be woken without the reactor, and execute_blocking() would never return.
+    fn turn_reactor(&self) {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| self.reactor.turn())) {
+            self.record_panic("task reactor", payload);
+        }
+    }
+
+    /// Wakes a sleeping thread, if any, after a task was queued
+    fn notify(&self) {
+        if self.inline {
+            // the calling thread may be waiting on the reactor for a task woken elsewhere
+            self.reactor.wake();
+            return;
+        }
+
+        // pairs with the increment in sleep(), so either we see the sleeper or it sees the task
+        atomic::fence(Ordering::SeqCst);
+        if self.sleeping.load(Ordering::SeqCst) > 0 {
+            let _guard = self.sleep_mutex.lock().unwrap();
+            self.sleep_cvar.notify_one();
+        }
+    }
+
+    fn has_tasks(&self) -> bool {
+        !self.injector.is_empty() || self.stealers.iter().any(|stealer| !stealer.is_empty())
+    }
+
+    /// Blocks until a task may be available. Returns false once the executor is shutting down.
+    fn sleep(&self) -> bool {
+        let guard = self.sleep_mutex.lock().unwrap();
+        self.sleeping.fetch_add(1, Ordering::SeqCst);
+        atomic::fence(Ordering::SeqCst);
+
+        if !self.shutdown.load(Ordering::SeqCst) && !self.has_tasks() {
+            drop(self.sleep_cvar.wait(guard).unwrap());
+        }
+
+        self.sleeping.fetch_sub(1, Ordering::SeqCst);
+        !self.shutdown.load(Ordering::SeqCst)
+    }
+}
+
+/// An executor thread's own queue. Tasks spawned or woken on the thread go here, and other
+/// threads steal from it when they run out of work.
+struct WorkerContext {
+    shared: Arc<Shared>,
+    local: Worker<TaskPtr>,
+    /// Position among the executor's threads
+    index: usize,
+    /// Tasks being run by help_until() further up the stack
+    help_depth: Cell<usize>,
+    /// Task being polled, null between tasks
+    current_task: Cell<*const Task>,
+}
+
+impl WorkerContext {
+    /// Only wakes another thread once there's a backlog. A lone task will run on this thread as
+    /// soon as the current one yields, and waking a thread just to steal it costs more than
+    /// running it.
+    fn push(&self, task: TaskPtr) {
+        self.local.push(task);
+        if self.local.len() > 1 && !self.shared.inline {
+            self.shared.notify();
+        }
+    }
+
+    fn find_task(&self) -> Option<TaskPtr> {
+        self.local.pop().or_else(|| {
+            iter::repeat_with(|| {
+                self.shared
+                    .injector
+                    .steal_batch_and_pop(&self.local)
+                    .or_else(|| self.shared.stealers.iter().map(Stealer::steal).collect())
+            })
+            .find(|steal| !steal.is_retry())
+            .and_then(Steal::success)
+        })
+    }
+
+    fn run_task(&self, task_ptr: TaskPtr) {
+        let task = task_ptr.inner;
+
+        // SAFETY: name is never written after the task is first queued
+        let name = unsafe { ptr::addr_of!((*task).name).read() };
+        let parent = self.current_task.replace(task);
+        let start = self.shared.profiler.start();
+
+        // SAFETY: the task is only ever polled by the thread which dequeued it
+        let done = unsafe { Task::poll_future(task) };
+
+        if let Some(start) = start {
+            self.shared.profiler.record(self.index, name, start);
+        }
+        self.current_task.set(parent);
+
+        if done {
+            // the task may have been freed, only compare the pointer
+            if task == self.shared.main_task.load(Ordering::Acquire) {
+                let mut done = self.shared.main_task_done.lock().unwrap();
+                *done = true;
+                self.shared.main_task_cvar.notify_one();
+            }
+        }
+    }
+
+    /// Name of the task being polled, inherited by the tasks it spawns with run_parallel()
+    fn current_task_name(&self) -> &'static str {
+        let task = self.current_task.get();
+        if task.is_null() {
+            return "";
+        }
+        // SAFETY: the task is alive while it's being polled, and its name is never written
+        unsafe { ptr::addr_of!((*task).name).read() }
+    }
+
+    /// Runs tasks on this thread until `done` returns true, waiting on the reactor whenever none
+    /// are queued. For inline executors, whose only thread is the caller's.
+    fn run_until(&self, done: impl Fn() -> bool) {
+        while !done() {
+            match self.find_task() {
+                Some(task) => self.run_task(task),
+                None => self.shared.turn_reactor(),
+            }
+        }
+    }
+
+    /// Runs tasks from the local queue until `done` returns true or the queue is empty, so a
+    /// task awaiting its own children can usually run them itself instead of suspending
+    fn help_until(&self, done: impl Fn() -> bool) {
+        // each level of helping nests a poll on the stack, deeply nested tasks suspend instead
+        let depth = self.help_depth.get();
+        if depth >= MAX_HELP_DEPTH {
+            return;
+        }
+
+        self.help_depth.set(depth + 1);
+        while !done() {
+            match self.local.pop() {
+                Some(task) => self.run_task(task),
+                None => break,
+            }
+        }
+        self.help_depth.set(depth);
+    }
+}
+
+/// Deepest nesting of tasks run by help_until(), bounding its stack use
+const MAX_HELP_DEPTH: usize = 32;
+
+thread_local! {
+    /// Context of the executor thread, null on other threads
+    static CURRENT_WORKER: Cell<*const WorkerContext> = const { Cell::new(ptr::null()) };
+}
+
+/// Tasks spawned from within a task run on the same executor
+fn current_worker() -> &'static WorkerContext {
+    let worker = CURRENT_WORKER.with(Cell::get);
+    assert!(!worker.is_null(), "task spawned outside of an executor");
+    // SAFETY: set for the whole life of the executor thread
+    unsafe { &*worker }
 }
 
 struct TaskPtr {
@@ -37,70 +255,285 @@ struct TaskPtr {
 /// SAFETY: TaskPtr is only dereferenced by the executor
 unsafe impl Send for TaskPtr {}
 
+/// Not queued, waiting to be woken
+const IDLE: u8 = 0;
+/// Queued, or about to be
+const SCHEDULED: u8 = 1;
+const RUNNING: u8 = 2;
+/// Woken while running, requeued once the poll returns
+const NOTIFIED: u8 = 3;
+const DONE: u8 = 4;
+
+/// A panic caught while polling a task, re-raised where the task is joined. execute_blocking()
+/// panics with this as the payload, so callers can downcast it to find which task panicked.
+pub struct TaskPanic {
+    /// Name of the task which originally panicked, before propagating through its parents, or
+    /// of the executor thread for panics outside of tasks
+    task: &'static str,
+    payload: Box<dyn Any + Send>,
+}
+
+impl TaskPanic {
+    /// Panics again at the joining task or execute_blocking() call site
+    fn resume(self) -> ! {
+        panic::resume_unwind(Box::new(self))
+    }
+
+    pub fn task(&self) -> &'static str {
+        self.task
+    }
+
+    /// What the task originally panicked with
+    pub fn payload(&self) -> &(dyn Any + Send) {
+        &*self.payload
+    }
+
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        self.payload
+    }
+
+    /// The payload, if the task panicked with a message
+    pub fn message(&self) -> Option<&str> {
+        match self.payload.downcast_ref::<&str>() {
+            Some(message) => Some(message),
+            None => self.payload.downcast_ref::<String>().map(String::as_str),
+        }
+    }
+}
+
+impl fmt::Debug for TaskPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskPanic")
+            .field("task", &self.task)
+            .field("message", &self.message())
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for TaskPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.message() {
+            Some(message) => write!(f, "task {} panicked: {}", self.task, message),
+            None => write!(f, "task {} panicked", self.task),
+        }
+    }
+}
+
 struct Task {
     future: Pin<&'static mut dyn Future<Output = ()>>,
+    /// Type of the future or closure being run, for panic messages and profiles
+    name: &'static str,
     join_handle: *const TaskJoinHandle,
+    /// Executor running the task, set once before it's first queued
+    shared: *const Shared,
+    /// Ensures the task is queued at most once, so two threads never poll it at the same time
+    state: AtomicU8,
+    /// See task_sequence(). Set once before the task is first queued.
+    key: u64,
+    /// Tasks queued by this one so far, each deriving its key from its position
+    spawned: Cell<u32>,
+    /// task_sequence() calls made by this task so far
+    sequence: Cell<u32>,
 }
 
-/// SAFETY: join_handle is only accessed via a mutex
+/// SAFETY: join_handle is only accessed via a mutex, and shared is Sync and outlives the task
 unsafe impl Send for Task {}
 
 impl Task {
-    fn new(future: Pin<&mut dyn Future<Output = ()>>) -> Self {
+    fn new(future: Pin<&mut dyn Future<Output = ()>>, name: &'static str) -> Self {
         // SAFETY: run_*() functions always join futures before returning
-        let future =
-            unsafe { std::mem::transmute::<_, Pin<&'static mut dyn Future<Output = ()>>>(future) };
+        let future = unsafe {
+            std::mem::transmute::<
+                Pin<&mut dyn Future<Output = ()>>,
+                Pin<&'static mut dyn Future<Output = ()>>,
+            >(future)
+        };
 
         Self {
             future,
+            name,
             join_handle: ptr::null(),
+            shared: ptr::null(),
+            state: AtomicU8::new(IDLE),
+            key: 0,
+            spawned: Cell::new(0),
+            sequence: Cell::new(0),
         }
     }
 
-    fn run(mut self: Pin<&mut Self>, join_handle: &Pin<&TaskJoinHandle>) {
-        self.join_handle = &**join_handle;
-
-        let message = ExecutorMessage::Task(TaskPtr { inner: &mut *self });
-
-        SYNC_CHANNEL
-            .sender
-            .try_send(message)
-            .expect("executor channel full");
+    /// Queues the task on the current executor thread
+    fn run(self: Pin<&mut Self>, join_handle: &Pin<&TaskJoinHandle>) {
+        let worker = current_worker();
+        let task = self.prepare(&worker.shared, join_handle);
+        worker.push(task);
     }
 
-    fn poll_future(&mut self) -> bool {
-        let waker = RawWaker::new(self as *mut Task as *mut (), &VTABLE);
-        let waker = unsafe { Waker::from_raw(waker) };
+    /// Queues the task on `shared`'s executor from any thread
+    fn run_on(self: Pin<&mut Self>, shared: &Shared, join_handle: &Pin<&TaskJoinHandle>) {
+        let task = self.prepare(shared, join_handle);
+        shared.injector.push(task);
+        shared.notify();
+    }
 
-        match self.future.as_mut().poll(&mut Context::from_waker(&waker)) {
-            Poll::Ready(_) => {
-                // SAFETY: technically breaking the rules by creating a reference while we could have
-                // a &mut TaskJoinHandle, but we're only accessing Sync members of TaskJoinHandle
-                let mut join_handle = unsafe { self.join_handle.as_ref().unwrap().inner.lock() };
+    fn prepare(
+        mut self: Pin<&mut Self>,
+        shared: &Shared,
+        join_handle: &Pin<&TaskJoinHandle>,
+    ) -> TaskPtr {
+        self.join_handle = &**join_handle;
+        self.shared = shared;
+        self.state = AtomicU8::new(SCHEDULED);
+        self.key = spawn_key(shared);
 
-                join_handle.done = true;
+        TaskPtr { inner: &mut *self }
+    }
 
-                if let Some(waker) = join_handle.waker.take() {
-                    waker.wake();
+    /// Returns true once the future completes or panics, after which the task may be freed at any
+    /// time
+    ///
+    /// SAFETY: must only be called by the thread which dequeued the task. Wakers may access the
+    /// task's state concurrently, so this never creates a reference to the whole task.
+    unsafe fn poll_future(task: *mut Task) -> bool {
+        let state = &*ptr::addr_of!((*task).state);
+        state.store(RUNNING, Ordering::Relaxed);
+
+        let waker = RawWaker::new(task as *mut (), &VTABLE);
+        let waker = Waker::from_raw(waker);
+
+        let future = &mut *ptr::addr_of_mut!((*task).future);
+        let poll = panic::catch_unwind(AssertUnwindSafe(|| {
+            future.as_mut().poll(&mut Context::from_waker(&waker))
+        }));
+
+        let panic = match poll {
+            Ok(Poll::Ready(())) => None,
+            Ok(Poll::Pending) => {
+                if state
+                    .compare_exchange(RUNNING, IDLE, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+                {
+                    // woken while running
+                    state.store(SCHEDULED, Ordering::Relaxed);
+                    Task::queue(task);
                 }
 
-                true
+                return false;
             }
-            Poll::Pending => false,
+            // a child's panic, re-raised by join_all(), keeps the child's name
+            Err(payload) => Some(match payload.downcast::<TaskPanic>() {
+                Ok(panic) => *panic,
+                Err(payload) => TaskPanic {
+                    task: ptr::addr_of!((*task).name).read(),
+                    payload,
+                },
+            }),
+        };
+
+        state.store(DONE, Ordering::Release);
+
+        // SAFETY: technically breaking the rules by creating a reference while we could have
+        // a &mut TaskJoinHandle, but we're only accessing Sync members of TaskJoinHandle
+        let mut join_handle = (*task).join_handle.as_ref().unwrap().inner.lock();
+
+        join_handle.done = true;
+        join_handle.panic = panic;
+
+        if let Some(waker) = join_handle.waker.take() {
+            waker.wake();
+        }
+
+        true
+    }
+
+    /// Queues locally when woken on one of the task's executor threads, otherwise on the
+    /// executor's injector
+    ///
+    /// SAFETY: the task's executor must still be alive
+    unsafe fn queue(task: *mut Task) {
+        let shared = ptr::addr_of!((*task).shared).read();
+        let worker = CURRENT_WORKER.with(Cell::get);
+
+        if !worker.is_null() && ptr::eq(Arc::as_ptr(&(*worker).shared), shared) {
+            (*worker).push(TaskPtr { inner: task });
+        } else {
+            (*shared).injector.push(TaskPtr { inner: task });
+            (*shared).notify();
         }
     }
 }
 
+/// Key of a task being queued, derived from the task queueing it and how many it queued before,
+/// or from the number of tasks queued from outside of tasks
+fn spawn_key(shared: &Shared) -> u64 {
+    let worker = CURRENT_WORKER.with(Cell::get);
+    let parent = match unsafe { worker.as_ref() } {
+        Some(worker) if ptr::eq(Arc::as_ptr(&worker.shared), shared) => worker.current_task.get(),
+        _ => ptr::null(),
+    };
+
+    let (parent_key, index) = if parent.is_null() {
+        (0, shared.roots.fetch_add(1, Ordering::Relaxed))
+    } else {
+        // SAFETY: the parent is being polled on this thread, and its key is never written
+        let (key, spawned) = unsafe { ((*parent).key, &*ptr::addr_of!((*parent).spawned)) };
+        spawned.set(spawned.get() + 1);
+        (key, spawned.get() as u64 - 1)
+    };
+
+    // splitmix64, so that keys spread evenly whatever the shape of the task tree
+    let mut key = parent_key ^ index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    key = (key ^ (key >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    key = (key ^ (key >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    key ^ (key >> 31)
+}
+
+/// Identifies the task being polled on this thread by the path of spawns leading to it, and
+/// counts the calls made from it. Both are the same from run to run as long as every task
+/// queues its children in the same order and the same calls are made from outside of tasks,
+/// wherever the tasks happen to run. Returns the task's key and the number of times it called
+/// this before, or None outside of tasks.
+pub fn task_sequence() -> Option<(u64, u32)> {
+    let worker = CURRENT_WORKER.with(Cell::get);
+    // SAFETY: the worker outlives its thread's tasks
+    let task = unsafe { worker.as_ref()? }.current_task.get();
+    if task.is_null() {
+        return None;
+    }
+
+    // SAFETY: the task is alive while it's polled. Its future is borrowed meanwhile, so only
+    // the fields needed are referenced.
+    let (key, sequence) = unsafe { ((*task).key, &*ptr::addr_of!((*task).sequence)) };
+    let next = sequence.get();
+    sequence.set(next + 1);
+    Some((key, next))
+}
+
 fn task_clone(s: *mut Task) -> RawWaker {
     RawWaker::new(s as *const (), &VTABLE)
 }
 
 fn task_wake(task: *mut Task) {
-    let message = ExecutorMessage::Task(TaskPtr { inner: task });
-    SYNC_CHANNEL
-        .sender
-        .try_send(message)
-        .expect("executor channel full");
+    // SAFETY: the executor outlives every task it runs, and wakers only touch the task's state
+    // until it's requeued
+    let state = unsafe { &*ptr::addr_of!((*task).state) };
+
+    let mut current = state.load(Ordering::Acquire);
+    loop {
+        let next = match current {
+            IDLE => SCHEDULED,
+            RUNNING => NOTIFIED,
+            _ => return,
+        };
+
+        match state.compare_exchange_weak(current, next, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => break,
+            Err(actual) => current = actual,
+        }
+    }
+
+    if current == IDLE {
+        unsafe { Task::queue(task) };
+    }
 }
 
 const VTABLE: RawWakerVTable = {
@@ -119,6 +552,7 @@ struct TaskJoinHandle {
 struct TaskJoinHandleInner {
     done: bool,
     waker: Option<Waker>,
+    panic: Option<TaskPanic>,
 }
 
 impl TaskJoinHandle {
@@ -126,22 +560,23 @@ impl TaskJoinHandle {
         let inner = TaskJoinHandleInner {
             done: false,
             waker: None,
+            panic: None,
         };
 
         TaskJoinHandle {
             inner: SpinMutex::new(inner),
         }
     }
-}
 
-impl Future for TaskJoinHandle {
-    type Output = ();
+    fn is_done(&self) -> bool {
+        self.inner.lock().done
+    }
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    fn poll_join(&self, cx: &mut Context<'_>) -> Poll<Result<(), TaskPanic>> {
         let mut inner = self.inner.lock();
 
         if inner.done {
-            Poll::Ready(())
+            Poll::Ready(inner.panic.take().map_or(Ok(()), Err))
         } else {
             inner.waker = Some(cx.waker().clone());
             Poll::Pending
@@ -149,119 +584,279 @@ impl Future for TaskJoinHandle {
     }
 }
 
+/// Runs tasks on a pool of threads. Each thread has its own queue, and steals from the others
+/// once its queue runs dry. A further thread waits on timers and I/O for the tasks.
 pub struct Executor {
+    shared: Arc<Shared>,
     thread_join_handles: Vec<JoinHandle<()>>,
-    main_task: Arc<AtomicPtr<Task>>,
-    task_cvar: Arc<Condvar>,
-    task_cvar_mutex: Arc<Mutex<bool>>,
-}
-
-enum ExecutorMessage {
-    Task(TaskPtr),
-    Join,
+    /// Queue of the thread calling execute_blocking(), for inline executors
+    inline: Option<Box<WorkerContext>>,
 }
 
 impl Executor {
+    /// Starts one thread per available core. `register_thread` is called on each thread before
+    /// it runs any task.
     pub fn new<F>(register_thread: F) -> (Self, Vec<ThreadId>)
     where
         F: Fn() + Send + 'static,
     {
-        const NUM_THREADS: usize = 4;
+        let num_threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self::with_threads(num_threads, register_thread)
+    }
+
+    /// Starts `num_threads` threads. Executors are independent, each runs only the tasks spawned
+    /// on it. If `register_thread` panics on any thread, the threads are stopped and the panic is
+    /// re-raised here.
+    pub fn with_threads<F>(num_threads: usize, register_thread: F) -> (Self, Vec<ThreadId>)
+    where
+        F: Fn() + Send + 'static,
+    {
+        assert!(num_threads > 0, "executor needs at least one thread");
 
-        let main_task = Arc::new(AtomicPtr::new(ptr::null_mut()));
+        let workers = (0..num_threads)
+            .map(|_| Worker::new_fifo())
+            .collect::<Vec<_>>();
 
-        let task_cvar = Arc::new(Condvar::new());
-        let task_cvar_mutex = Arc::new(Mutex::new(false));
+        let stealers = workers.iter().map(Worker::stealer).collect();
+        let shared = Arc::new(Shared::new(stealers, false));
 
-        let mut thread_join_handles = Vec::with_capacity(NUM_THREADS);
+        let mut thread_join_handles = Vec::with_capacity(num_threads);
 
-        let thread_ids = Mutex::new(Vec::new());
-        // SAFETY: we only ever reference this while thread_ids is still in scope
-        let thread_ids_ref =
-            unsafe { std::mem::transmute::<_, &'static Mutex<Vec<ThreadId>>>(&thread_ids) };
+        // each thread's ID once registered, or the panic registering it
+        let registered = Mutex::new(Vec::new());
+        // SAFETY: we only ever reference this while registered is still in scope
+        let registered_ref = unsafe {
+            std::mem::transmute::<
+                &Mutex<Vec<thread::Result<ThreadId>>>,
+                &'static Mutex<Vec<thread::Result<ThreadId>>>,
+            >(&registered)
+        };
 
         let register_thread = Arc::new(Mutex::new(register_thread));
 
-        for _ in 0..NUM_THREADS {
+        for (index, local) in workers.into_iter().enumerate() {
             let local_register_thread = register_thread.clone();
-            let main_task = main_task.clone();
-            let task_cvar = task_cvar.clone();
-            let task_cvar_mutex = task_cvar_mutex.clone();
-            thread_join_handles.push(thread::spawn(move || {
-                thread_ids_ref.lock().unwrap().push(thread::current().id());
-
-                local_register_thread.lock().unwrap()();
-                drop(local_register_thread);
-
-                loop {
-                    match SYNC_CHANNEL.receiver.lock().unwrap().recv().unwrap() {
-                        ExecutorMessage::Task(task_ptr) => {
-                            // SAFETY: we only ever create a Task reference here
-                            let task = unsafe { task_ptr.inner.as_mut().unwrap() };
-                            if task.poll_future()
-                                && task_ptr.inner == main_task.load(Ordering::Acquire)
-                            {
-                                let mut task_guard = task_cvar_mutex.lock().unwrap();
-                                *task_guard = true;
-                                task_cvar.notify_one();
+            let shared = shared.clone();
+            let thread = thread::Builder::new().name(format!("task worker {}", index));
+            thread_join_handles.push(
+                thread
+                    .spawn(move || {
+                        let worker = WorkerContext {
+                            shared,
+                            local,
+                            index,
+                            help_depth: Cell::new(0),
+                            current_task: Cell::new(ptr::null()),
+                        };
+                        CURRENT_WORKER.with(|current| current.set(&worker));
+
+                        let register = panic::catch_unwind(AssertUnwindSafe(|| {
+                            // poisoned if it panicked on another thread, which is re-raised
+                            local_register_thread
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner)(
+                            );
+                        }));
+                        drop(local_register_thread);
+
+                        let registered = register.is_ok();
+                        registered_ref
+                            .lock()
+                            .unwrap()
+                            .push(register.map(|()| thread::current().id()));
+
+                        if registered {
+                            loop {
+                                match worker.find_task() {
+                                    Some(task) => worker.run_task(task),
+                                    None => {
+                                        if !worker.shared.sleep() {
+                                            break;
+                                        }
+                                    }
+                                }
                             }
                         }
-                        ExecutorMessage::Join => break,
-                    }
-                }
-            }));
+
+                        CURRENT_WORKER.with(|current| current.set(ptr::null()));
+                    })
+                    .unwrap(),
+            );
         }
 
-        while thread_ids.lock().unwrap().len() < NUM_THREADS {
+        while registered.lock().unwrap().len() < num_threads {
             std::thread::sleep(std::time::Duration::from_millis(1));
         }
 
+        let mut thread_ids = Vec::with_capacity(num_threads);
+        let mut register_panic = None;
+        for result in registered.into_inner().unwrap() {
+            match result {
+                Ok(thread_id) => thread_ids.push(thread_id),
+                Err(payload) => register_panic = register_panic.or(Some(payload)),
+            }
+        }
+
+        // an executor missing threads would run slower, or never at all with a single thread
+        if let Some(payload) = register_panic {
+            shutdown_threads(&shared, thread_join_handles);
+            panic::resume_unwind(payload);
+        }
+
+        let reactor_shared = shared.clone();
+        let thread = thread::Builder::new().name("task reactor".to_string());
+        thread_join_handles.push(
+            thread
+                .spawn(move || {
+                    let shared = reactor_shared;
+                    while !shared.shutdown.load(Ordering::SeqCst) {
+                        shared.turn_reactor();
+                    }
+                })
+                .unwrap(),
+        );
+
         let executor = Self {
+            shared,
             thread_join_handles,
-            main_task,
-            task_cvar,
-            task_cvar_mutex,
+            inline: None,
+        };
+
+        (executor, thread_ids)
+    }
+
+    /// Starts no threads. Every task runs on the thread calling execute_blocking(), one at a time
+    /// in the order they're queued, so each run of the same futures polls them in the same order.
+    /// `register_thread` is called on the current thread, which should be the one calling
+    /// execute_blocking().
+    pub fn inline<F: FnOnce()>(register_thread: F) -> (Self, Vec<ThreadId>) {
+        let local = Worker::new_fifo();
+        let shared = Arc::new(Shared::new(vec![local.stealer()], true));
+
+        register_thread();
+
+        let worker = WorkerContext {
+            shared: shared.clone(),
+            local,
+            index: 0,
+            help_depth: Cell::new(0),
+            current_task: Cell::new(ptr::null()),
+        };
+
+        let executor = Self {
+            shared,
+            thread_join_handles: Vec::new(),
+            inline: Some(Box::new(worker)),
         };
 
-        (executor, thread_ids.into_inner().unwrap())
+        (executor, vec![thread::current().id()])
     }
 
-    pub fn execute_blocking(&mut self, future: &mut (dyn Future<Output = ()> + Send)) {
+    /// Runs `future` to completion. If it or any task it spawned panics, the panic is re-raised
+    /// here once every task has finished, as a TaskPanic holding the original payload. So is a
+    /// panic in the reactor, which keeps running after one.
+    #[track_caller]
+    pub fn execute_blocking<F: Future<Output = ()> + Send>(&mut self, future: &mut F) {
         // guaranteed not to move in the scope of this function
-        let future = unsafe { Pin::new_unchecked(future) };
+        let future = unsafe { Pin::new_unchecked(future as &mut dyn Future<Output = ()>) };
 
-        let mut task = Task::new(future);
+        let name = std::any::type_name::<F>();
+        let start = self.shared.profiler.start();
+
+        let mut task = Task::new(future, name);
         let mut task = unsafe { Pin::new_unchecked(&mut task) };
 
         let join_handle = TaskJoinHandle::new();
         let join_handle = unsafe { Pin::new_unchecked(&join_handle) };
 
-        self.main_task.store(&mut *task, Ordering::Release);
+        match &self.inline {
+            Some(worker) => {
+                let caller = CURRENT_WORKER.with(|current| current.replace(&**worker));
+
+                task.run(&join_handle);
+                worker.run_until(|| join_handle.is_done());
+
+                CURRENT_WORKER.with(|current| current.set(caller));
+            }
+            None => {
+                self.shared.main_task.store(&mut *task, Ordering::Release);
+
+                task.run_on(&self.shared, &join_handle);
+
+                let mut done = self
+                    .shared
+                    .main_task_cvar
+                    .wait_while(self.shared.main_task_done.lock().unwrap(), |done| !*done)
+                    .unwrap();
+
+                *done = false;
+            }
+        }
+
+        if let Some(start) = start {
+            let thread = self.shared.profiler.blocking_thread();
+            self.shared.profiler.record(thread, name, start);
+        }
 
-        task.run(&join_handle);
+        let panic = join_handle.inner.lock().panic.take();
+        let panic = panic.or_else(|| self.shared.thread_panic.lock().unwrap().take());
+        if let Some(panic) = panic {
+            panic.resume();
+        }
+    }
+}
 
-        let mut task_guard = self
-            .task_cvar
-            .wait_while(self.task_cvar_mutex.lock().unwrap(), |done| !*done)
-            .unwrap();
+impl Executor {
+    /// Records when and on which thread every task is polled, until stop_profiling(). Costs an
+    /// atomic load per poll while stopped.
+    pub fn start_profiling(&self) {
+        self.shared.profiler.enable();
+    }
 
-        *task_guard = false;
+    pub fn stop_profiling(&self) -> Profile {
+        self.shared.profiler.disable()
     }
 }
 
 impl Drop for Executor {
     fn drop(&mut self) {
-        for _ in &self.thread_join_handles {
-            SYNC_CHANNEL
-                .sender
-                .try_send(ExecutorMessage::Join)
-                .expect("executor channel full");
-        }
+        shutdown_threads(&self.shared, self.thread_join_handles.drain(..));
+    }
+}
+
+/// Wakes the executor's threads to exit, and waits until they have
+fn shutdown_threads(shared: &Shared, threads: impl IntoIterator<Item = JoinHandle<()>>) {
+    shared.shutdown.store(true, Ordering::SeqCst);
+    shared.reactor.wake();
+    {
+        let _guard = shared.sleep_mutex.lock().unwrap();
+        shared.sleep_cvar.notify_all();
+    }
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+/// Awaits every join handle, first running whichever of their tasks are still queued locally.
+/// Re-raises the first panic among the tasks.
+async fn join_all<'a, I>(join_handles: I)
+where
+    I: Iterator<Item = &'a TaskJoinHandle> + Clone,
+{
+    current_worker().help_until(|| join_handles.clone().all(TaskJoinHandle::is_done));
 
-        for thread in self.thread_join_handles.drain(..) {
-            thread.join().unwrap();
+    let mut panic = None;
+    for join_handle in join_handles {
+        if let Err(child_panic) = future::poll_fn(|cx| join_handle.poll_join(cx)).await {
+            panic.get_or_insert(child_panic);
         }
     }
+
+    // only once every task is done, as they borrow from the caller
+    if let Some(panic) = panic {
+        panic.resume();
+    }
 }
 
 macro_rules! pin_array_mut {
@@ -279,6 +874,7 @@ macro_rules! pin_array_mut {
 }
 
 pub async fn run_batch<F: Fn(usize) + Sync, const N: usize>(f: F) {
+    let name = std::any::type_name::<F>();
     let f = &f;
     let f_async = |index: usize| async move { f(index) };
 
@@ -294,7 +890,7 @@ pub async fn run_batch<F: Fn(usize) + Sync, const N: usize>(f: F) {
     let mut tasks = unsafe {
         let mut tasks: [MaybeUninit<_>; N] = MaybeUninit::uninit().assume_init();
         for (i, future) in futures.into_iter().enumerate() {
-            tasks[i].write(Task::new(future));
+            tasks[i].write(Task::new(future, name));
         }
         tasks.map(|a| a.assume_init())
     };
@@ -302,8 +898,8 @@ pub async fn run_batch<F: Fn(usize) + Sync, const N: usize>(f: F) {
 
     let mut join_handles = unsafe {
         let mut join_handles: [MaybeUninit<_>; N] = MaybeUninit::uninit().assume_init();
-        for i in 0..N {
-            join_handles[i].write(TaskJoinHandle::new());
+        for join_handle in join_handles.iter_mut() {
+            join_handle.write(TaskJoinHandle::new());
         }
         join_handles.map(|a| a.assume_init())
     };
@@ -313,18 +909,22 @@ pub async fn run_batch<F: Fn(usize) + Sync, const N: usize>(f: F) {
         task.run(&join_handles[i].as_ref());
     }
 
-    for join_handle in join_handles {
-        join_handle.await;
-    }
+    join_all(
+        join_handles
+            .iter()
+            .map(|join_handle| join_handle.as_ref().get_ref()),
+    )
+    .await;
 }
 
 pub async fn run_parallel<const N: usize>(futures: [&mut (dyn Future<Output = ()> + Send); N]) {
+    let name = current_worker().current_task_name();
     let futures = unsafe { futures.map(|a| Pin::new_unchecked(a)) };
 
     let mut tasks = unsafe {
         let mut tasks: [MaybeUninit<_>; N] = MaybeUninit::uninit().assume_init();
         for (i, future) in futures.into_iter().enumerate() {
-            tasks[i].write(Task::new(future));
+            tasks[i].write(Task::new(future, name));
         }
         tasks.map(|a| a.assume_init())
     };
@@ -332,8 +932,8 @@ pub async fn run_parallel<const N: usize>(futures: [&mut (dyn Future<Output = ()
 
     let mut join_handles = unsafe {
         let mut join_handles: [MaybeUninit<_>; N] = MaybeUninit::uninit().assume_init();
-        for i in 0..N {
-            join_handles[i].write(TaskJoinHandle::new());
+        for join_handle in join_handles.iter_mut() {
+            join_handle.write(TaskJoinHandle::new());
         }
         join_handles.map(|a| a.assume_init())
     };
@@ -343,151 +943,198 @@ pub async fn run_parallel<const N: usize>(futures: [&mut (dyn Future<Output = ()
         task.run(&join_handles[i].as_ref());
     }
 
-    for join_handle in join_handles {
-        join_handle.await;
-    }
+    join_all(
+        join_handles
+            .iter()
+            .map(|join_handle| join_handle.as_ref().get_ref()),
+    )
+    .await;
 }
 
-struct PtrWrapper<T: Send>(*const T);
+/// Runs each future as a task and joins them
+async fn run_all<F>(name: &'static str, futures: impl Iterator<Item = F>)
+where
+    F: Future<Output = ()> + Send,
+{
+    let mut futures = futures.collect::<Vec<_>>();
+    let join_handles = futures
+        .iter()
+        .map(|_| TaskJoinHandle::new())
+        .collect::<Vec<_>>();
+
+    // SAFETY: neither Vec is touched again until every task is joined
+    let mut tasks = futures
+        .iter_mut()
+        .map(|future| Task::new(unsafe { Pin::new_unchecked(future) }, name))
+        .collect::<Vec<_>>();
+
+    for (task, join_handle) in tasks.iter_mut().zip(&join_handles) {
+        unsafe { Pin::new_unchecked(task).run(&Pin::new_unchecked(join_handle)) };
+    }
+
+    join_all(join_handles.iter()).await;
+}
 
-unsafe impl<T: Send> Send for PtrWrapper<T> {}
+/// Spawns tasks borrowing from the caller of run_scope()
+pub struct Scope<'a> {
+    futures: Vec<Pin<Box<dyn Future<Output = ()> + Send + 'a>>>,
+    tasks: Vec<Pin<Box<Task>>>,
+    join_handles: Vec<Pin<Box<TaskJoinHandle>>>,
+}
 
-impl<T: Send> Copy for PtrWrapper<T> {}
+impl<'a> Scope<'a> {
+    /// Starts running `future` straight away. It's joined when run_scope() returns.
+    pub fn spawn<F: Future<Output = ()> + Send + 'a>(&mut self, future: F) {
+        self.spawn_named(std::any::type_name::<F>(), future);
+    }
 
-impl<T: Send> Clone for PtrWrapper<T> {
-    fn clone(&self) -> Self {
-        Self(self.0)
+    /// Like spawn(), naming the task in panic messages and profiles instead of the future's type,
+    /// e.g. for boxed futures
+    pub fn spawn_named<F>(&mut self, name: &'static str, future: F)
+    where
+        F: Future<Output = ()> + Send + 'a,
+    {
+        self.spawn_boxed(name, Box::pin(future));
     }
-}
 
-struct MutPtrWrapper<T: Send>(*mut T);
+    /// Like spawn_named(), for futures that are already boxed, so they aren't boxed again
+    pub fn spawn_boxed(
+        &mut self,
+        name: &'static str,
+        mut future: Pin<Box<dyn Future<Output = ()> + Send + 'a>>,
+    ) {
+        let mut task = Box::pin(Task::new(future.as_mut(), name));
+        let join_handle = Box::pin(TaskJoinHandle::new());
 
-unsafe impl<T: Send> Send for MutPtrWrapper<T> {}
+        task.as_mut().run(&join_handle.as_ref());
 
-impl<T: Send> Copy for MutPtrWrapper<T> {}
+        self.futures.push(future);
+        self.tasks.push(task);
+        self.join_handles.push(join_handle);
+    }
 
-impl<T: Send> Clone for MutPtrWrapper<T> {
-    fn clone(&self) -> Self {
-        Self(self.0)
+    fn is_done(&self) -> bool {
+        self.join_handles
+            .iter()
+            .all(|join_handle| join_handle.is_done())
     }
 }
 
-pub async fn run_slice<T: Send, F: Fn(&T) + Sync>(slice: &[T], f: F) {
-    const CONCURRENCY: usize = 8;
-    const STRIDE: usize = 8;
+impl Drop for Scope<'_> {
+    /// Only waits if run_scope()'s future is dropped before it joined the tasks, e.g. when a
+    /// select cancels it. The tasks are still running the futures freed below, and borrowing from
+    /// the caller. Their panics are discarded, as there's nowhere left to raise them.
+    fn drop(&mut self) {
+        if !self.is_done() {
+            block_until(|| self.is_done());
+        }
+    }
+}
 
-    let f = &f;
-    let slice_ptr = PtrWrapper(slice.as_ptr());
-    let slice_len = slice.len();
-
-    let f_async = |offset: usize, slice_ptr: PtrWrapper<T>| {
-        if offset < slice_len {
-            let len = usize::min(STRIDE, slice_len - offset);
-            for i in 0..len {
-                f(unsafe { &*slice_ptr.0.add(offset + i) });
+/// Blocks the current thread until `done` returns true. An executor thread runs other tasks
+/// meanwhile, both so it isn't wasted and so that the tasks being waited on can't be stuck in
+/// its queue.
+fn block_until(done: impl Fn() -> bool) {
+    let worker = CURRENT_WORKER.with(Cell::get);
+    // SAFETY: set for the whole life of the executor thread
+    match unsafe { worker.as_ref() } {
+        Some(worker) if worker.shared.inline => worker.run_until(done),
+        Some(worker) => {
+            while !done() {
+                match worker.find_task() {
+                    Some(task) => worker.run_task(task),
+                    None => thread::yield_now(),
+                }
             }
         }
-    };
-
-    let mut offset = 0;
-    while offset < slice_len {
-        let mut futures = unsafe {
-            let mut futures: [MaybeUninit<_>; CONCURRENCY] = MaybeUninit::uninit().assume_init();
-            for future in futures.iter_mut() {
-                future.write(async move {
-                    f_async(offset, slice_ptr);
-                });
-                offset += STRIDE;
+        None => {
+            while !done() {
+                thread::yield_now();
             }
-            futures.map(|a| a.assume_init())
-        };
-        pin_array_mut!(futures, CONCURRENCY);
+        }
+    }
+}
 
-        let mut tasks = unsafe {
-            let mut tasks: [MaybeUninit<_>; CONCURRENCY] = MaybeUninit::uninit().assume_init();
-            for (i, future) in futures.into_iter().enumerate() {
-                tasks[i].write(Task::new(future));
-            }
-            tasks.map(|a| a.assume_init())
-        };
-        pin_array_mut!(tasks, CONCURRENCY);
+/// Calls `spawn_tasks`, which may spawn any number of tasks, then joins them all. Unlike
+/// run_parallel(), the number of tasks needn't be known up front. Dropping the future early
+/// blocks until the tasks it spawned finish, as they borrow from the caller.
+pub async fn run_scope<'a, F: FnOnce(&mut Scope<'a>)>(spawn_tasks: F) {
+    let mut scope = Scope {
+        futures: Vec::new(),
+        tasks: Vec::new(),
+        join_handles: Vec::new(),
+    };
 
-        let mut join_handles = unsafe {
-            let mut join_handles: [MaybeUninit<_>; CONCURRENCY] =
-                MaybeUninit::uninit().assume_init();
-            for i in 0..CONCURRENCY {
-                join_handles[i].write(TaskJoinHandle::new());
-            }
-            join_handles.map(|a| a.assume_init())
-        };
-        pin_array_mut!(join_handles, CONCURRENCY);
+    // tasks already spawned borrow from the caller, so they're joined before any panic unwinds
+    let spawned = panic::catch_unwind(AssertUnwindSafe(|| spawn_tasks(&mut scope)));
 
-        for (i, task) in tasks.into_iter().enumerate() {
-            task.run(&join_handles[i].as_ref());
-        }
+    join_all(
+        scope
+            .join_handles
+            .iter()
+            .map(|join_handle| join_handle.as_ref().get_ref()),
+    )
+    .await;
 
-        for join_handle in join_handles {
-            join_handle.await;
-        }
+    if let Err(payload) = spawned {
+        panic::resume_unwind(payload);
     }
 }
 
-pub async fn run_slice_mut<T: Send, F: Fn(&mut T) + Sync>(slice: &mut [T], f: F) {
-    const CONCURRENCY: usize = 8;
-    const STRIDE: usize = 8;
+/// Slices are split into this many tasks per executor thread, so threads that finish their
+/// share early can steal from the others
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// Chunks any shorter aren't worth a task of their own
+const MIN_CHUNK_LEN: usize = 8;
 
+fn chunk_len(len: usize) -> usize {
+    let chunks = current_worker().shared.stealers.len() * CHUNKS_PER_THREAD;
+    len.div_ceil(chunks).max(MIN_CHUNK_LEN)
+}
+
+pub async fn run_slice<T: Sync, F: Fn(&T) + Sync>(slice: &[T], f: F) {
     let f = &f;
-    let slice_ptr = MutPtrWrapper(slice.as_mut_ptr());
-    let slice_len = slice.len();
-
-    let f_async = |offset: usize, slice_ptr: MutPtrWrapper<T>| {
-        if offset < slice_len {
-            let len = usize::min(STRIDE, slice_len - offset);
-            for i in 0..len {
-                f(unsafe { &mut *slice_ptr.0.add(offset + i) });
-            }
-        }
-    };
+    let chunks = slice.chunks(chunk_len(slice.len()));
 
-    let mut offset = 0;
-    while offset < slice_len {
-        let mut futures = unsafe {
-            let mut futures: [MaybeUninit<_>; CONCURRENCY] = MaybeUninit::uninit().assume_init();
-            for future in futures.iter_mut() {
-                future.write(async move {
-                    f_async(offset, slice_ptr);
-                });
-                offset += STRIDE;
-            }
-            futures.map(|a| a.assume_init())
-        };
-        pin_array_mut!(futures, CONCURRENCY);
+    run_all(
+        std::any::type_name::<F>(),
+        chunks.map(|chunk| async move { chunk.iter().for_each(f) }),
+    )
+    .await;
+}
 
-        let mut tasks = unsafe {
-            let mut tasks: [MaybeUninit<_>; CONCURRENCY] = MaybeUninit::uninit().assume_init();
-            for (i, future) in futures.into_iter().enumerate() {
-                tasks[i].write(Task::new(future));
-            }
-            tasks.map(|a| a.assume_init())
-        };
-        pin_array_mut!(tasks, CONCURRENCY);
+pub async fn run_slice_mut<T: Send, F: Fn(&mut T) + Sync>(slice: &mut [T], f: F) {
+    let f = &f;
+    let chunks = slice.chunks_mut(chunk_len(slice.len()));
 
-        let mut join_handles = unsafe {
-            let mut join_handles: [MaybeUninit<_>; CONCURRENCY] =
-                MaybeUninit::uninit().assume_init();
-            for i in 0..CONCURRENCY {
-                join_handles[i].write(TaskJoinHandle::new());
-            }
-            join_handles.map(|a| a.assume_init())
-        };
-        pin_array_mut!(join_handles, CONCURRENCY);
+    run_all(
+        std::any::type_name::<F>(),
+        chunks.map(|chunk| async move { chunk.iter_mut().for_each(f) }),
+    )
+    .await;
+}
 
-        for (i, task) in tasks.into_iter().enumerate() {
-            task.run(&join_handles[i].as_ref());
-        }
+/// Maps every element across the executor and combines the results with `reduce` in slice
+/// order, so `reduce` must be associative but needn't be commutative. None for an empty slice.
+pub async fn run_map_reduce<T, R, M, F>(slice: &[T], map: M, reduce: F) -> Option<R>
+where
+    T: Sync,
+    R: Send,
+    M: Fn(&T) -> R + Sync,
+    F: Fn(R, R) -> R + Sync,
+{
+    let (map, reduce) = (&map, &reduce);
+    let chunks = slice.chunks(chunk_len(slice.len()));
+    let mut results = chunks.clone().map(|_| None).collect::<Vec<Option<R>>>();
+
+    run_all(
+        std::any::type_name::<M>(),
+        chunks.zip(&mut results).map(|(chunk, result)| async move {
+            *result = chunk.iter().map(map).reduce(reduce);
+        }),
+    )
+    .await;
 
-        for join_handle in join_handles {
-            join_handle.await;
-        }
-    }
+    results.into_iter().flatten().reduce(reduce)
 }
@@ -0,0 +1,172 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+/// One poll of a task, or one execute_blocking() call
+struct Span {
+    name: &'static str,
+    start: Instant,
+    end: Instant,
+}
+
+/// Records spans while enabled. Each thread only ever locks its own buffer, so recording never
+/// contends.
+pub(crate) struct Profiler {
+    enabled: AtomicBool,
+    /// One per executor thread, then one for the thread calling execute_blocking()
+    threads: Vec<Mutex<Vec<Span>>>,
+}
+
+impl Profiler {
+    pub(crate) fn new(num_threads: usize) -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            threads: (0..=num_threads).map(|_| Mutex::new(Vec::new())).collect(),
+        }
+    }
+
+    /// Index of the buffer for the thread calling execute_blocking()
+    pub(crate) fn blocking_thread(&self) -> usize {
+        self.threads.len() - 1
+    }
+
+    /// None while disabled, which costs a single load
+    pub(crate) fn start(&self) -> Option<Instant> {
+        self.enabled.load(Ordering::Relaxed).then(Instant::now)
+    }
+
+    pub(crate) fn record(&self, thread: usize, name: &'static str, start: Instant) {
+        let end = Instant::now();
+        self.threads[thread]
+            .lock()
+            .unwrap()
+            .push(Span { name, start, end });
+    }
+
+    pub(crate) fn enable(&self) {
+        for thread in &self.threads {
+            thread.lock().unwrap().clear();
+        }
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn disable(&self) -> Profile {
+        self.enabled.store(false, Ordering::Relaxed);
+
+        let blocking_thread = self.blocking_thread();
+        let threads = self
+            .threads
+            .iter()
+            .enumerate()
+            .map(|(index, thread)| {
+                let name = if index == blocking_thread {
+                    "execute_blocking".to_string()
+                } else {
+                    format!("task worker {}", index)
+                };
+                (name, std::mem::take(&mut *thread.lock().unwrap()))
+            })
+            .collect();
+
+        Profile { threads }
+    }
+}
+
+/// Task timings recorded between Executor::start_profiling() and stop_profiling()
+pub struct Profile {
+    /// Name of each thread, and its spans in the order they ended
+    threads: Vec<(String, Vec<Span>)>,
+}
+
+impl Profile {
+    /// Number of spans recorded
+    pub fn len(&self) -> usize {
+        self.threads.iter().map(|(_, spans)| spans.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_chrome_trace(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Writes the profile as Chrome trace event JSON, which Perfetto and chrome://tracing open.
+    /// Times are in microseconds from the first span.
+    pub fn write_chrome_trace<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let epoch = self
+            .threads
+            .iter()
+            .flat_map(|(_, spans)| spans.iter().map(|span| span.start))
+            .min();
+
+        write!(writer, "{{\"traceEvents\":[")?;
+        let mut separator = "";
+
+        for (tid, (thread_name, spans)) in self.threads.iter().enumerate() {
+            write!(
+                writer,
+                "{}\n{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+                separator, tid, thread_name
+            )?;
+            separator = ",";
+
+            for span in spans {
+                // there's a first span whenever there are any spans
+                let epoch = epoch.unwrap();
+                let ts = span.start.duration_since(epoch).as_secs_f64() * 1e6;
+                let dur = span.end.duration_since(span.start).as_secs_f64() * 1e6;
+
+                write!(
+                    writer,
+                    ",\n{{\"name\":\"{}\",\"cat\":\"task\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":{}}}",
+                    escape(&display_name(span.name)),
+                    ts,
+                    dur,
+                    tid
+                )?;
+            }
+        }
+
+        writeln!(writer, "\n]}}")
+    }
+}
+
+/// Task names are type names. Generic arguments and closures are dropped, so the tasks a
+/// function spawns share its name, e.g. "sim_physics::System::simulate_step".
+fn display_name(type_name: &str) -> String {
+    let mut name = String::with_capacity(type_name.len());
+    let mut depth = 0;
+    let mut previous = ' ';
+
+    for c in type_name.chars() {
+        match c {
+            '<' => depth += 1,
+            // not the arrow of an fn type
+            '>' if previous != '-' => depth -= 1,
+            c if depth == 0 => name.push(c),
+            _ => {}
+        }
+        previous = c;
+    }
+
+    while let Some(stripped) = name.strip_suffix("::{{closure}}") {
+        name.truncate(stripped.len());
+    }
+
+    name
+}
+
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
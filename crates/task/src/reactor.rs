@@ -0,0 +1,535 @@
+use std::{
+    future::Future,
+    io,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use spin::Mutex as SpinMutex;
+
+use crate::current_worker;
+
+/// Timers due within the same tick fire together
+const TICK: Duration = Duration::from_millis(1);
+
+/// Timers further than this many ticks ahead share slots with nearer ones, and are skipped until
+/// their round comes
+const WHEEL_SLOTS: usize = 256;
+
+/// Where timers and I/O readiness wake their tasks. Each executor runs one on its own thread,
+/// blocking until the next timer is due or an fd it waits on becomes ready. Inline executors
+/// run it on the calling thread whenever no task is queued.
+pub(crate) struct Reactor {
+    poller: sys::Poller,
+    wheel: Mutex<TimerWheel>,
+}
+
+impl Reactor {
+    pub(crate) fn new() -> io::Result<Self> {
+        Ok(Self {
+            poller: sys::Poller::new()?,
+            wheel: Mutex::new(TimerWheel::new(Instant::now())),
+        })
+    }
+
+    /// Waits until the next timer is due, an fd is ready or wake() is called, then wakes the
+    /// tasks waiting on them
+    pub(crate) fn turn(&self) {
+        let mut ready = Vec::new();
+        let timeout = self.wheel.lock().unwrap().next_timeout(Instant::now());
+
+        let waited = self.poller.wait(timeout, &mut ready);
+
+        self.wheel
+            .lock()
+            .unwrap()
+            .advance(Instant::now(), &mut ready);
+
+        // outside the lock, a woken task may immediately register another timer. A waker
+        // panicking mustn't keep the other tasks from being woken.
+        let mut panic = None;
+        for waiter in &ready {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| waiter.fire())) {
+                panic.get_or_insert(payload);
+            }
+        }
+
+        // timers keep firing even if the poller breaks, so their tasks can still finish
+        waited.expect("reactor failed to wait");
+        if let Some(payload) = panic {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Interrupts turn(), e.g. for a timer due before the one it's waiting for
+    pub(crate) fn wake(&self) {
+        self.poller.notify();
+    }
+
+    fn add_timer(&self, deadline: Instant, timer: &Arc<Waiter>) {
+        let mut wheel = self.wheel.lock().unwrap();
+        if wheel.insert(deadline, timer.clone()) {
+            drop(wheel);
+            self.wake();
+        }
+    }
+}
+
+/// A task waiting on a timer or an fd
+struct Waiter {
+    ready: AtomicBool,
+    waker: SpinMutex<Option<Waker>>,
+}
+
+impl Waiter {
+    fn new(waker: &Waker) -> Arc<Self> {
+        Arc::new(Self {
+            ready: AtomicBool::new(false),
+            waker: SpinMutex::new(Some(waker.clone())),
+        })
+    }
+
+    /// Wakes the task while holding the lock. Task wakers don't keep their task alive, so the
+    /// future waiting must not be dropped, and its task freed, until wake() returns.
+    fn fire(&self) {
+        let mut waker = self.waker.lock();
+        self.ready.store(true, Ordering::Release);
+        if let Some(waker) = waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Stores the waker unless already fired. Returns whether it fired.
+    fn poll(&self, cx: &Context<'_>) -> bool {
+        let mut waker = self.waker.lock();
+        if self.ready.load(Ordering::Acquire) {
+            return true;
+        }
+        *waker = Some(cx.waker().clone());
+        false
+    }
+
+    /// Called when the future waiting is dropped, waiting for a wake in progress
+    fn cancel(&self) {
+        self.waker.lock().take();
+    }
+}
+
+struct TimerSlot {
+    tick: u64,
+    timer: Arc<Waiter>,
+}
+
+/// Hashed timer wheel. Each slot holds the timers due at any tick congruent to it, modulo the
+/// number of slots.
+struct TimerWheel {
+    start: Instant,
+    /// Every tick before this one has fired
+    current_tick: u64,
+    slots: Vec<Vec<TimerSlot>>,
+    len: usize,
+    /// Tick the reactor will next wake at by itself, None while it waits indefinitely
+    wake_tick: Option<u64>,
+}
+
+impl TimerWheel {
+    fn new(start: Instant) -> Self {
+        Self {
+            start,
+            current_tick: 0,
+            slots: (0..WHEEL_SLOTS).map(|_| Vec::new()).collect(),
+            len: 0,
+            wake_tick: None,
+        }
+    }
+
+    /// Returns true if the reactor must be woken to fire the timer on time
+    fn insert(&mut self, deadline: Instant, timer: Arc<Waiter>) -> bool {
+        // round up, timers never fire early
+        let elapsed = deadline.saturating_duration_since(self.start);
+        let tick = (elapsed.as_nanos().div_ceil(TICK.as_nanos()) as u64).max(self.current_tick);
+
+        self.slots[tick as usize % WHEEL_SLOTS].push(TimerSlot { tick, timer });
+        self.len += 1;
+
+        let wake = self.wake_tick.is_none_or(|wake_tick| tick < wake_tick);
+        if wake {
+            self.wake_tick = Some(tick);
+        }
+        wake
+    }
+
+    /// Collects every timer due by `now`
+    fn advance(&mut self, now: Instant, ready: &mut Vec<Arc<Waiter>>) {
+        let now_tick =
+            (now.saturating_duration_since(self.start).as_nanos() / TICK.as_nanos()) as u64;
+        if now_tick < self.current_tick {
+            return;
+        }
+
+        // each slot needs visiting at most once, however many ticks passed
+        let ticks = (now_tick - self.current_tick + 1).min(WHEEL_SLOTS as u64);
+        for tick in self.current_tick..self.current_tick + ticks {
+            let slot = &mut self.slots[tick as usize % WHEEL_SLOTS];
+            let len = slot.len();
+
+            slot.retain(|timer| {
+                if timer.tick > now_tick {
+                    return true;
+                }
+
+                ready.push(timer.timer.clone());
+                false
+            });
+
+            self.len -= len - slot.len();
+        }
+
+        self.current_tick = now_tick + 1;
+    }
+
+    /// How long until the next slot holding a timer. Timers in later rounds may cut the wait
+    /// short without firing.
+    fn next_timeout(&mut self, now: Instant) -> Option<Duration> {
+        self.wake_tick = if self.len == 0 {
+            None
+        } else {
+            (self.current_tick..self.current_tick + WHEEL_SLOTS as u64)
+                .find(|tick| !self.slots[*tick as usize % WHEEL_SLOTS].is_empty())
+        };
+
+        self.wake_tick.map(|tick| {
+            let at = self.start + Duration::from_nanos(tick * TICK.as_nanos() as u64);
+            at.saturating_duration_since(now)
+        })
+    }
+}
+
+struct Sleep {
+    deadline: Instant,
+    timer: Option<Arc<Waiter>>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match &self.timer {
+            Some(timer) => {
+                if timer.poll(cx) {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            }
+            None => {
+                if Instant::now() >= self.deadline {
+                    return Poll::Ready(());
+                }
+
+                let timer = Waiter::new(cx.waker());
+                current_worker()
+                    .shared
+                    .reactor
+                    .add_timer(self.deadline, &timer);
+                self.timer = Some(timer);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        // the wheel drops its reference once the deadline passes
+        if let Some(timer) = &self.timer {
+            timer.cancel();
+        }
+    }
+}
+
+/// Completes once `deadline` has passed, to within a millisecond or so. Must be awaited on an
+/// executor thread.
+pub async fn sleep_until(deadline: Instant) {
+    Sleep {
+        deadline,
+        timer: None,
+    }
+    .await
+}
+
+pub async fn sleep(duration: Duration) {
+    sleep_until(Instant::now() + duration).await
+}
+
+#[cfg(target_os = "linux")]
+pub use sys::{readable, writable};
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::{
+        collections::HashMap,
+        io,
+        os::unix::io::{AsRawFd, RawFd},
+        pin::Pin,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    use super::{Reactor, Waiter};
+    use crate::current_worker;
+
+    /// epoll key of the eventfd interrupting wait()
+    const NOTIFY_KEY: u64 = 0;
+
+    fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    pub(super) struct Poller {
+        epoll: RawFd,
+        event_fd: RawFd,
+        /// Tasks waiting on an fd, by epoll key
+        waiters: Mutex<HashMap<u64, Arc<Waiter>>>,
+        next_key: AtomicU64,
+    }
+
+    impl Poller {
+        pub(super) fn new() -> io::Result<Self> {
+            let epoll = check(unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) })?;
+            let event_fd =
+                match check(unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) }) {
+                    Ok(event_fd) => event_fd,
+                    Err(err) => {
+                        unsafe { libc::close(epoll) };
+                        return Err(err);
+                    }
+                };
+
+            let poller = Self {
+                epoll,
+                event_fd,
+                waiters: Mutex::new(HashMap::new()),
+                next_key: AtomicU64::new(NOTIFY_KEY + 1),
+            };
+
+            // level triggered, so it stays ready until wait() drains it
+            poller.ctl(
+                libc::EPOLL_CTL_ADD,
+                event_fd,
+                libc::EPOLLIN as u32,
+                NOTIFY_KEY,
+            )?;
+
+            Ok(poller)
+        }
+
+        fn ctl(&self, op: libc::c_int, fd: RawFd, events: u32, key: u64) -> io::Result<()> {
+            let mut event = libc::epoll_event { events, u64: key };
+            check(unsafe { libc::epoll_ctl(self.epoll, op, fd, &mut event) }).map(drop)
+        }
+
+        /// Blocks until notified, an fd is ready or `timeout` passes, collecting the waiters of
+        /// ready fds
+        pub(super) fn wait(
+            &self,
+            timeout: Option<Duration>,
+            ready: &mut Vec<Arc<Waiter>>,
+        ) -> io::Result<()> {
+            // round up, so timers aren't polled for just before they're due
+            let timeout = timeout.map_or(-1, |timeout| {
+                timeout.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as libc::c_int
+            });
+
+            let mut events = [libc::epoll_event { events: 0, u64: 0 }; 64];
+            let len = match check(unsafe {
+                libc::epoll_wait(self.epoll, events.as_mut_ptr(), events.len() as _, timeout)
+            }) {
+                Ok(len) => len as usize,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => return Ok(()),
+                Err(err) => return Err(err),
+            };
+
+            let waiters = self.waiters.lock().unwrap();
+            for event in &events[..len] {
+                let key = event.u64;
+                if key == NOTIFY_KEY {
+                    let mut value = 0u64;
+                    unsafe { libc::read(self.event_fd, &mut value as *mut u64 as *mut _, 8) };
+                } else if let Some(waiter) = waiters.get(&key) {
+                    ready.push(waiter.clone());
+                }
+            }
+
+            Ok(())
+        }
+
+        pub(super) fn notify(&self) {
+            let value = 1u64;
+            unsafe { libc::write(self.event_fd, &value as *const u64 as *const _, 8) };
+        }
+    }
+
+    impl Drop for Poller {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.event_fd);
+                libc::close(self.epoll);
+            }
+        }
+    }
+
+    /// Registered with epoll for one wakeup. Deregistered when dropped, so the fd must outlive
+    /// it.
+    struct Readiness {
+        fd: RawFd,
+        events: u32,
+        registration: Option<Registration>,
+    }
+
+    struct Registration {
+        /// Kept, since the future may be dropped off the executor's threads
+        reactor: Arc<Reactor>,
+        key: u64,
+        waiter: Arc<Waiter>,
+    }
+
+    impl std::future::Future for Readiness {
+        type Output = io::Result<()>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            if let Some(Registration { waiter, .. }) = &self.registration {
+                return if waiter.poll(cx) {
+                    Poll::Ready(Ok(()))
+                } else {
+                    Poll::Pending
+                };
+            }
+
+            let reactor = current_worker().shared.reactor.clone();
+            let poller = &reactor.poller;
+            let key = poller.next_key.fetch_add(1, Ordering::Relaxed);
+            let waiter = Waiter::new(cx.waker());
+
+            poller.waiters.lock().unwrap().insert(key, waiter.clone());
+
+            // one shot, the fd is reported once then stays registered but disabled
+            let events = self.events | libc::EPOLLONESHOT as u32;
+            if let Err(err) = poller.ctl(libc::EPOLL_CTL_ADD, self.fd, events, key) {
+                poller.waiters.lock().unwrap().remove(&key);
+                return Poll::Ready(Err(err));
+            }
+
+            self.registration = Some(Registration {
+                reactor,
+                key,
+                waiter,
+            });
+            Poll::Pending
+        }
+    }
+
+    impl Drop for Readiness {
+        fn drop(&mut self) {
+            if let Some(registration) = self.registration.take() {
+                let Registration {
+                    reactor,
+                    key,
+                    waiter,
+                } = registration;
+                let _ = reactor.poller.ctl(libc::EPOLL_CTL_DEL, self.fd, 0, key);
+                reactor.poller.waiters.lock().unwrap().remove(&key);
+                waiter.cancel();
+            }
+        }
+    }
+
+    /// Completes once `fd` has data to read, or has hung up or failed. Only one task may wait on
+    /// an fd at a time.
+    pub async fn readable(fd: &impl AsRawFd) -> io::Result<()> {
+        Readiness {
+            fd: fd.as_raw_fd(),
+            events: libc::EPOLLIN as u32,
+            registration: None,
+        }
+        .await
+    }
+
+    /// Completes once `fd` has room to write, or has hung up or failed. Only one task may wait
+    /// on an fd at a time.
+    pub async fn writable(fd: &impl AsRawFd) -> io::Result<()> {
+        Readiness {
+            fd: fd.as_raw_fd(),
+            events: libc::EPOLLOUT as u32,
+            registration: None,
+        }
+        .await
+    }
+}
+
+/// Timers only, waiting on a condition variable
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::{
+        io,
+        sync::{Arc, Condvar, Mutex},
+        time::Duration,
+    };
+
+    use super::Waiter;
+
+    pub(super) struct Poller {
+        notified: Mutex<bool>,
+        cvar: Condvar,
+    }
+
+    impl Poller {
+        pub(super) fn new() -> io::Result<Self> {
+            Ok(Self {
+                notified: Mutex::new(false),
+                cvar: Condvar::new(),
+            })
+        }
+
+        pub(super) fn wait(
+            &self,
+            timeout: Option<Duration>,
+            _: &mut Vec<Arc<Waiter>>,
+        ) -> io::Result<()> {
+            let notified = self.notified.lock().unwrap();
+            let mut notified = match timeout {
+                Some(timeout) => {
+                    self.cvar
+                        .wait_timeout_while(notified, timeout, |notified| !*notified)
+                        .unwrap()
+                        .0
+                }
+                None => self
+                    .cvar
+                    .wait_while(notified, |notified| !*notified)
+                    .unwrap(),
+            };
+            *notified = false;
+            Ok(())
+        }
+
+        pub(super) fn notify(&self) {
+            *self.notified.lock().unwrap() = true;
+            self.cvar.notify_one();
+        }
+    }
+}
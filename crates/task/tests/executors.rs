@@ -0,0 +1,83 @@
+use std::{
+    collections::HashSet,
+    num::NonZeroUsize,
+    sync::{Barrier, Mutex},
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+use task::{run_scope, Executor};
+
+/// Threads which ran `count` scoped tasks, each blocking for a while so they spread out
+fn threads_running(executor: &mut Executor, count: usize) -> HashSet<ThreadId> {
+    let threads = Mutex::new(HashSet::new());
+
+    executor.execute_blocking(&mut async {
+        run_scope(|scope| {
+            for _ in 0..count {
+                scope.spawn(async {
+                    thread::sleep(Duration::from_millis(1));
+                    threads.lock().unwrap().insert(thread::current().id());
+                });
+            }
+        })
+        .await;
+    });
+
+    threads.into_inner().unwrap()
+}
+
+#[test]
+fn defaults_to_available_parallelism() {
+    let (_executor, thread_ids) = Executor::new(|| {});
+
+    let num_threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    assert_eq!(thread_ids.len(), num_threads);
+}
+
+/// Starts an executor with `num_threads` threads on the calling thread, as executors can't be
+/// sent between threads, and runs `f` with it
+fn with_executor<R>(num_threads: usize, f: impl FnOnce(&mut Executor) -> R) -> (Vec<ThreadId>, R) {
+    let (mut executor, thread_ids) = Executor::with_threads(num_threads, || {});
+    let result = f(&mut executor);
+    (thread_ids, result)
+}
+
+#[test]
+fn runs_tasks_only_on_its_own_threads() {
+    let ((first_ids, first_ran), (second_ids, second_ran)) = thread::scope(|s| {
+        let first = s.spawn(|| with_executor(2, |executor| threads_running(executor, 64)));
+        let second = s.spawn(|| with_executor(3, |executor| threads_running(executor, 64)));
+        (first.join().unwrap(), second.join().unwrap())
+    });
+
+    assert_eq!(first_ids.len(), 2);
+    assert_eq!(second_ids.len(), 3);
+    assert!(first_ids.iter().all(|id| !second_ids.contains(id)));
+
+    assert!(first_ran.iter().all(|id| first_ids.contains(id)));
+    assert!(second_ran.iter().all(|id| second_ids.contains(id)));
+}
+
+#[test]
+fn executors_run_at_the_same_time() {
+    // each executor's only thread waits for the other's, so neither can run the other's task
+    let barrier = Barrier::new(2);
+    let run = |executor: &mut Executor| {
+        let ran_on = Mutex::new(None);
+        executor.execute_blocking(&mut async {
+            barrier.wait();
+            *ran_on.lock().unwrap() = Some(thread::current().id());
+        });
+        ran_on.into_inner().unwrap().unwrap()
+    };
+
+    let ((first_ids, first_ran), (second_ids, second_ran)) = thread::scope(|s| {
+        let first = s.spawn(|| with_executor(1, run));
+        let second = s.spawn(|| with_executor(1, run));
+        (first.join().unwrap(), second.join().unwrap())
+    });
+
+    assert_eq!(first_ids, [first_ran]);
+    assert_eq!(second_ids, [second_ran]);
+}
@@ -0,0 +1,89 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use task::{run_batch, run_parallel, run_scope, run_slice, sleep, Executor};
+
+/// Order in which a batch, a slice and some scoped tasks ran, interleaved
+fn run_order(executor: &mut Executor) -> Vec<usize> {
+    let order = Mutex::new(Vec::new());
+    let values = (0..100).collect::<Vec<usize>>();
+
+    executor.execute_blocking(&mut async {
+        let mut batch = run_batch::<_, 16>(|index| order.lock().unwrap().push(1000 + index));
+        let mut slice = run_slice(&values, |value| order.lock().unwrap().push(*value));
+        let mut scope = run_scope(|scope| {
+            for index in 0..16 {
+                let order = &order;
+                scope.spawn(async move {
+                    order.lock().unwrap().push(2000 + index);
+                    sleep(Duration::from_millis(1)).await;
+                    order.lock().unwrap().push(3000 + index);
+                });
+            }
+        });
+        run_parallel([&mut batch, &mut slice, &mut scope]).await;
+    });
+
+    order.into_inner().unwrap()
+}
+
+#[test]
+fn runs_tasks_on_the_calling_thread() {
+    let registered = Mutex::new(None);
+    let (mut executor, thread_ids) =
+        Executor::inline(|| *registered.lock().unwrap() = Some(thread::current().id()));
+
+    assert_eq!(thread_ids, [thread::current().id()]);
+    assert_eq!(*registered.lock().unwrap(), Some(thread::current().id()));
+
+    let values = vec![1u64; 1000];
+    executor.execute_blocking(&mut async {
+        run_slice(&values, |_| {
+            assert_eq!(thread::current().id(), thread_ids[0])
+        })
+        .await;
+    });
+}
+
+#[test]
+fn runs_tasks_in_the_same_order_every_time() {
+    let (mut executor, _) = Executor::inline(|| {});
+    let order = run_order(&mut executor);
+    assert_eq!(order.len(), 16 + 100 + 2 * 16);
+
+    for _ in 0..10 {
+        let (mut executor, _) = Executor::inline(|| {});
+        assert_eq!(run_order(&mut executor), order);
+    }
+}
+
+#[test]
+fn waits_for_timers() {
+    let (mut executor, _) = Executor::inline(|| {});
+
+    let start = Instant::now();
+    executor.execute_blocking(&mut sleep(Duration::from_millis(20)));
+    assert!(start.elapsed() >= Duration::from_millis(20));
+}
+
+#[test]
+fn panic_reaches_execute_blocking() {
+    let (mut executor, _) = Executor::inline(|| {});
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        executor.execute_blocking(&mut async {
+            run_batch::<_, 8>(|index| assert_ne!(index, 5, "bad index")).await;
+        })
+    }));
+    assert!(result.is_err());
+
+    // still usable afterwards
+    let values = vec![1u64; 100];
+    executor.execute_blocking(&mut async {
+        run_slice(&values, |value| assert_eq!(*value, 1)).await;
+    });
+}
@@ -0,0 +1,168 @@
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Wake, Waker},
+    time::Duration,
+};
+
+use task::{run_batch, run_parallel, run_slice, sleep, Executor, TaskPanic};
+
+fn task_panic(payload: Box<dyn std::any::Any + Send>) -> TaskPanic {
+    *payload.downcast::<TaskPanic>().unwrap()
+}
+
+struct PanickingWaker;
+
+impl Wake for PanickingWaker {
+    fn wake(self: Arc<Self>) {
+        panic!("bad waker");
+    }
+}
+
+#[test]
+fn panic_reaches_execute_blocking() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    let values = (0..1000).collect::<Vec<usize>>();
+    let visited = AtomicUsize::new(0);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        executor.execute_blocking(&mut async {
+            run_slice(&values, |value| {
+                visited.fetch_add(1, Ordering::Relaxed);
+                if *value == 500 {
+                    panic!("bad value");
+                }
+            })
+            .await;
+        })
+    }));
+
+    let panic = task_panic(result.unwrap_err());
+    assert_eq!(panic.message(), Some("bad value"));
+    assert!(
+        panic.task().contains("panic_reaches_execute_blocking"),
+        "{}",
+        panic.task()
+    );
+    // the payload is the one the task panicked with
+    assert_eq!(
+        *panic.into_payload().downcast::<&str>().unwrap(),
+        "bad value"
+    );
+
+    // the other tasks in the batch still ran to completion
+    assert!(visited.into_inner() >= 504);
+}
+
+#[test]
+fn panic_propagates_through_parents() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        executor.execute_blocking(&mut async {
+            let mut fine = async {
+                run_batch::<_, 8>(|_| {}).await;
+            };
+            let mut failing = async {
+                run_batch::<_, 8>(|index| assert_ne!(index, 3)).await;
+            };
+            run_parallel([&mut fine, &mut failing]).await;
+        })
+    }));
+
+    let panic = task_panic(result.unwrap_err());
+    let message = panic.into_payload().downcast::<String>().unwrap();
+    assert!(message.contains("assertion"), "{}", message);
+}
+
+#[test]
+fn executor_survives_panics() {
+    let (mut executor, _) = Executor::with_threads(2, || {});
+
+    for _ in 0..10 {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            executor.execute_blocking(&mut async {
+                run_batch::<_, 4>(|_| panic!("every task")).await;
+            })
+        }));
+        assert!(result.is_err());
+    }
+
+    let count = AtomicUsize::new(0);
+    executor.execute_blocking(&mut async {
+        run_batch::<_, 16>(|_| {
+            count.fetch_add(1, Ordering::Relaxed);
+        })
+        .await;
+    });
+    assert_eq!(count.into_inner(), 16);
+}
+
+#[test]
+fn non_string_payload_is_kept() {
+    let (mut executor, _) = Executor::with_threads(2, || {});
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        executor.execute_blocking(&mut async {
+            run_batch::<_, 2>(|index| {
+                if index == 1 {
+                    panic::panic_any(42u32);
+                }
+            })
+            .await;
+        })
+    }));
+
+    let panic = task_panic(result.unwrap_err());
+    assert_eq!(panic.message(), None);
+    assert_eq!(panic.payload().downcast_ref::<u32>(), Some(&42));
+}
+
+#[test]
+fn register_thread_panic_reaches_the_caller() {
+    let registered = Arc::new(AtomicUsize::new(0));
+
+    let counter = registered.clone();
+    let result = panic::catch_unwind(move || {
+        Executor::with_threads(4, move || {
+            if counter.fetch_add(1, Ordering::SeqCst) == 2 {
+                panic!("registration failed");
+            }
+        })
+    });
+
+    let payload = result.err().unwrap();
+    assert_eq!(*payload.downcast::<&str>().unwrap(), "registration failed");
+    assert_eq!(registered.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn reactor_panic_reaches_execute_blocking() {
+    let (mut executor, _) = Executor::with_threads(2, || {});
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        executor.execute_blocking(&mut async {
+            // the reactor panics waking this one, but still wakes the sleep awaited below
+            let mut timer = Box::pin(sleep(Duration::from_millis(5)));
+            let waker = Waker::from(Arc::new(PanickingWaker));
+            assert!(timer
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending());
+
+            sleep(Duration::from_millis(5)).await;
+            sleep(Duration::from_millis(5)).await;
+        })
+    }));
+
+    let panic = task_panic(result.unwrap_err());
+    assert_eq!(panic.task(), "task reactor");
+    assert_eq!(panic.message(), Some("bad waker"));
+
+    // the reactor still runs afterwards
+    executor.execute_blocking(&mut sleep(Duration::from_millis(5)));
+}
@@ -0,0 +1,125 @@
+use std::{
+    future::{self, Future},
+    panic::{self, AssertUnwindSafe},
+    pin::pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::Poll,
+    time::Duration,
+};
+
+use task::{run_map_reduce, run_scope, run_slice_mut, sleep, Executor};
+
+#[test]
+fn scope_spawns_runtime_count() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    for count in [0, 1, 7, 100] {
+        let mut slots = vec![0; count];
+        executor.execute_blocking(&mut async {
+            run_scope(|scope| {
+                for (index, slot) in slots.iter_mut().enumerate() {
+                    scope.spawn(async move { *slot = index + 1 });
+                }
+            })
+            .await;
+        });
+
+        assert!(slots
+            .iter()
+            .enumerate()
+            .all(|(index, slot)| *slot == index + 1));
+    }
+}
+
+#[test]
+fn scope_joins_before_panicking() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    let finished = AtomicUsize::new(0);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        executor.execute_blocking(&mut async {
+            run_scope(|scope| {
+                for _ in 0..16 {
+                    scope.spawn(async {
+                        std::thread::sleep(std::time::Duration::from_millis(1));
+                        finished.fetch_add(1, Ordering::Relaxed);
+                    });
+                }
+                panic!("spawning failed");
+            })
+            .await;
+        })
+    }));
+
+    assert!(result.is_err());
+    assert_eq!(finished.into_inner(), 16);
+}
+
+#[test]
+fn dropped_scope_waits_for_its_tasks() {
+    let (mut executor, _) = Executor::with_threads(2, || {});
+
+    let finished = AtomicUsize::new(0);
+    executor.execute_blocking(&mut async {
+        {
+            let mut scope = pin!(run_scope(|scope| {
+                for _ in 0..8 {
+                    scope.spawn(async {
+                        sleep(Duration::from_millis(20)).await;
+                        finished.fetch_add(1, Ordering::Relaxed);
+                    });
+                }
+            }));
+
+            // poll once to spawn the tasks, then cancel the scope while they sleep
+            future::poll_fn(|cx| {
+                assert!(scope.as_mut().poll(cx).is_pending());
+                Poll::Ready(())
+            })
+            .await;
+        }
+
+        assert_eq!(finished.load(Ordering::Relaxed), 8);
+    });
+}
+
+#[test]
+fn slice_mut_visits_every_element_once() {
+    let (mut executor, _) = Executor::with_threads(3, || {});
+
+    for len in [0, 1, 8, 9, 1000, 12_345] {
+        let mut values = vec![0u32; len];
+        executor.execute_blocking(&mut async {
+            run_slice_mut(&mut values, |value| *value += 1).await;
+        });
+
+        assert!(values.iter().all(|value| *value == 1), "len {}", len);
+    }
+}
+
+#[test]
+fn map_reduce_keeps_order() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    let values = (0..10_000).collect::<Vec<u64>>();
+    let mut sum = None;
+    let mut concatenated = None;
+    let mut empty = Some(0);
+    executor.execute_blocking(&mut async {
+        sum = run_map_reduce(&values, |value| *value, |a, b| a + b).await;
+        concatenated = run_map_reduce(
+            &values,
+            |value| vec![*value],
+            |mut a, b| {
+                a.extend(b);
+                a
+            },
+        )
+        .await;
+        empty = run_map_reduce(&values[..0], |value| *value, |a, b| a + b).await;
+    });
+
+    assert_eq!(sum, Some(values.iter().sum()));
+    assert_eq!(concatenated, Some(values));
+    assert_eq!(empty, None);
+}
@@ -0,0 +1,51 @@
+use task::{run_parallel, run_scope, run_slice, Executor};
+
+fn work(executor: &mut Executor) {
+    let values = vec![1u64; 1000];
+    executor.execute_blocking(&mut async {
+        let mut slice = run_slice(&values, |value| assert_eq!(*value, 1));
+        let mut scope = run_scope(|scope| scope.spawn_named("custom name", async {}));
+        run_parallel([&mut slice, &mut scope]).await;
+    });
+}
+
+fn trace(executor: &Executor) -> (usize, String) {
+    let profile = executor.stop_profiling();
+    let mut trace = Vec::new();
+    profile.write_chrome_trace(&mut trace).unwrap();
+    (profile.len(), String::from_utf8(trace).unwrap())
+}
+
+#[test]
+fn records_tasks_while_profiling() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    executor.start_profiling();
+    work(&mut executor);
+    let (len, trace) = trace(&executor);
+
+    assert!(len > 2);
+    assert!(trace.starts_with("{\"traceEvents\":["));
+    assert!(trace.trim_end().ends_with("]}"));
+    assert!(trace.contains("\"args\":{\"name\":\"task worker 3\"}"));
+    assert!(trace.contains("\"args\":{\"name\":\"execute_blocking\"}"));
+    // closures and generic arguments are dropped from type names
+    assert!(trace.contains("\"name\":\"profile::work\""));
+    assert!(trace.contains("\"name\":\"custom name\""));
+    assert!(!trace.contains("{{closure}}"));
+}
+
+#[test]
+fn records_nothing_while_stopped() {
+    let (mut executor, _) = Executor::with_threads(2, || {});
+
+    work(&mut executor);
+    assert_eq!(trace(&executor).0, 0);
+
+    executor.start_profiling();
+    work(&mut executor);
+    assert!(trace(&executor).0 > 0);
+
+    work(&mut executor);
+    assert_eq!(trace(&executor).0, 0);
+}
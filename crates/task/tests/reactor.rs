@@ -0,0 +1,150 @@
+use std::{
+    future::{poll_fn, Future},
+    net::UdpSocket,
+    pin::pin,
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+use task::{readable, run_scope, sleep, sleep_until, writable, Executor};
+
+#[test]
+fn sleeps_at_least_the_duration() {
+    let (mut executor, _) = Executor::with_threads(2, || {});
+
+    for millis in [0, 1, 5, 20] {
+        let start = Instant::now();
+        executor.execute_blocking(&mut sleep(Duration::from_millis(millis)));
+        assert!(start.elapsed() >= Duration::from_millis(millis));
+    }
+}
+
+#[test]
+fn sleeps_past_a_full_turn_of_the_wheel() {
+    let (mut executor, _) = Executor::with_threads(1, || {});
+
+    let deadline = Instant::now() + Duration::from_millis(300);
+    executor.execute_blocking(&mut sleep_until(deadline));
+    assert!(Instant::now() >= deadline);
+}
+
+#[test]
+fn concurrent_sleeps_each_wait_for_their_deadline() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    let start = Instant::now();
+    let mut finished = vec![Duration::ZERO; 32];
+    executor.execute_blocking(&mut async {
+        run_scope(|scope| {
+            // spawned latest first, so each timer is due before the one the reactor waits for
+            for (index, finished) in finished.iter_mut().enumerate().rev() {
+                scope.spawn(async move {
+                    sleep_until(start + Duration::from_millis(2 * index as u64)).await;
+                    *finished = start.elapsed();
+                });
+            }
+        })
+        .await;
+    });
+
+    for (index, finished) in finished.iter().enumerate() {
+        assert!(*finished >= Duration::from_millis(2 * index as u64));
+    }
+
+    // all run concurrently rather than one after another
+    assert!(start.elapsed() < Duration::from_millis(2 * 32 * 4));
+}
+
+#[test]
+fn sleeps_can_be_dropped_as_they_fire() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    for round in 0..50 {
+        executor.execute_blocking(&mut async {
+            run_scope(|scope| {
+                for index in 0..16 {
+                    scope.spawn(async move {
+                        let deadline = Instant::now() + Duration::from_millis(1);
+                        let mut first = pin!(sleep_until(deadline));
+                        let mut second = pin!(sleep_until(deadline));
+
+                        // like a select, registers both then finishes once either fires, so the
+                        // task and the other sleep are dropped while it may be being woken
+                        poll_fn(|cx| {
+                            let first = first.as_mut().poll(cx).is_ready();
+                            let second = second.as_mut().poll(cx).is_ready();
+                            if first || second {
+                                Poll::Ready(())
+                            } else {
+                                Poll::Pending
+                            }
+                        })
+                        .await;
+
+                        // or dropped while still pending, around the deadline
+                        let jitter = Duration::from_micros((round * 16 + index) % 1500);
+                        let mut late = pin!(sleep_until(Instant::now() + jitter));
+                        poll_fn(|cx| {
+                            let _ = late.as_mut().poll(cx);
+                            Poll::Ready(())
+                        })
+                        .await;
+                        while Instant::now() < deadline + jitter {}
+                    });
+                }
+            })
+            .await;
+        });
+    }
+}
+
+#[test]
+fn waits_for_socket_readiness() {
+    let (mut executor, _) = Executor::with_threads(2, || {});
+
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver.set_nonblocking(true).unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = receiver.local_addr().unwrap();
+
+    let mut received = [0; 4];
+    executor.execute_blocking(&mut async {
+        run_scope(|scope| {
+            scope.spawn(async {
+                readable(&receiver).await.unwrap();
+                let (len, _) = receiver.recv_from(&mut received).unwrap();
+                assert_eq!(len, 4);
+            });
+
+            scope.spawn(async {
+                sleep(Duration::from_millis(10)).await;
+                writable(&sender).await.unwrap();
+                sender.send_to(&[1, 2, 3, 4], addr).unwrap();
+            });
+        })
+        .await;
+    });
+
+    assert_eq!(received, [1, 2, 3, 4]);
+}
+
+#[test]
+fn readiness_can_be_awaited_repeatedly() {
+    let (mut executor, _) = Executor::with_threads(1, || {});
+
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver.set_nonblocking(true).unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = receiver.local_addr().unwrap();
+
+    for value in 0..10u8 {
+        sender.send_to(&[value], addr).unwrap();
+
+        let mut received = [0];
+        executor.execute_blocking(&mut async {
+            readable(&receiver).await.unwrap();
+            receiver.recv_from(&mut received).unwrap();
+        });
+        assert_eq!(received, [value]);
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    collections::HashSet,
+    future::{self, Future},
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use task::{run_scope, Executor};
+
+#[test]
+fn idle_threads_steal_queued_tasks() {
+    let (mut executor, thread_ids) = Executor::with_threads(4, || {});
+
+    // every task is queued on the thread running the scope, the others can only steal them
+    let threads = Mutex::new(HashSet::new());
+    executor.execute_blocking(&mut async {
+        run_scope(|scope| {
+            for _ in 0..32 {
+                scope.spawn(async {
+                    thread::sleep(Duration::from_millis(2));
+                    threads.lock().unwrap().insert(thread::current().id());
+                });
+            }
+        })
+        .await;
+    });
+
+    let threads = threads.into_inner().unwrap();
+    assert!(threads.len() > 1, "tasks ran on {:?}", threads);
+    assert!(threads.iter().all(|id| thread_ids.contains(id)));
+}
+
+#[test]
+fn waiting_task_runs_its_children() {
+    let (mut executor, _) = Executor::with_threads(1, || {});
+
+    let polling = AtomicBool::new(false);
+    let helped = AtomicUsize::new(0);
+    executor.execute_blocking(&mut async {
+        let mut scope = pin!(run_scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(async {
+                    if polling.load(Ordering::Relaxed) {
+                        helped.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        }));
+
+        // children run while the scope is being polled only if it ran them itself while waiting,
+        // there's no other thread to run them
+        future::poll_fn(|cx| {
+            polling.store(true, Ordering::Relaxed);
+            let poll = scope.as_mut().poll(cx);
+            polling.store(false, Ordering::Relaxed);
+            poll
+        })
+        .await;
+    });
+
+    assert_eq!(helped.into_inner(), 8);
+}
@@ -0,0 +1,71 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use task::{run_parallel, run_slice, Executor};
+
+type BoxFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// Spawns a binary tree of tasks, 2^depth leaves
+fn tree(depth: u32, leaves: &AtomicUsize) -> BoxFuture<'_> {
+    Box::pin(async move {
+        if depth == 0 {
+            leaves.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let mut left = tree(depth - 1, leaves);
+        let mut right = tree(depth - 1, leaves);
+        run_parallel([&mut left, &mut right]).await;
+    })
+}
+
+/// Spawns each task from the previous one
+fn chain(depth: u32, links: &AtomicUsize) -> BoxFuture<'_> {
+    Box::pin(async move {
+        links.fetch_add(1, Ordering::Relaxed);
+
+        if depth > 0 {
+            let mut next = chain(depth - 1, links);
+            run_parallel([&mut next]).await;
+        }
+    })
+}
+
+#[test]
+fn nested_tree() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    let leaves = AtomicUsize::new(0);
+    executor.execute_blocking(&mut tree(16, &leaves));
+
+    assert_eq!(leaves.into_inner(), 1 << 16);
+}
+
+#[test]
+fn nested_chain() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    let links = AtomicUsize::new(0);
+    executor.execute_blocking(&mut chain(20_000, &links));
+
+    assert_eq!(links.into_inner(), 20_001);
+}
+
+#[test]
+fn wide_slice() {
+    let (mut executor, _) = Executor::with_threads(4, || {});
+
+    let values = (0..100_000).collect::<Vec<usize>>();
+    let sum = AtomicUsize::new(0);
+    executor.execute_blocking(&mut async {
+        run_slice(&values, |value| {
+            sum.fetch_add(*value, Ordering::Relaxed);
+        })
+        .await;
+    });
+
+    assert_eq!(sum.into_inner(), values.iter().sum());
+}
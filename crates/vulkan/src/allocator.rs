@@ -249,7 +249,7 @@ impl Allocator {
 
     fn aligned_size(&self, size: vk::DeviceSize) -> vk::DeviceSize {
         let alignment = self.vertex_buffer_alignment;
-        (size + alignment - 1) / alignment * alignment
+        size.div_ceil(alignment) * alignment
     }
 
     /// Flushes the host-visible transfer buffer and transfers the suballocated buffer
@@ -416,6 +416,7 @@ pub fn allocate_buffer(
     }
 }
 
+#[allow(dead_code)]
 pub fn free_buffer(vulkan: &VulkanInfo, buffer: Buffer) {
     unsafe {
         vulkan.device.free_memory(buffer.device_memory, None);
@@ -503,6 +504,7 @@ pub fn allocate_image(
     }
 }
 
+#[allow(dead_code)]
 fn free_image(vulkan: &VulkanInfo, image: Image) {
     unsafe {
         vulkan.device.free_memory(image.device_memory, None);
@@ -245,7 +245,7 @@ fn get_physical_device_queue_families_info(
             .contains(vk::QueueFlags::COMPUTE);
         let has_present_support = unsafe {
             surface_loader
-                .get_physical_device_surface_support(physical_device, i as u32, surface)
+                .get_physical_device_surface_support(physical_device, i, surface)
                 .unwrap()
         };
 
@@ -258,7 +258,7 @@ fn get_physical_device_queue_families_info(
                 // prefer graphics and present queue families to be the same
                 graphics_family_index = Some(i);
                 present_family_index = Some(i);
-            } else if present_family_index == None {
+            } else if present_family_index.is_none() {
                 present_family_index = Some(i);
             }
         }